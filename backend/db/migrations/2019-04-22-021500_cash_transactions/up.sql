@@ -0,0 +1,18 @@
+CREATE TABLE cash_transactions (
+    uuid UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_uuid UUID NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    stock_transaction_uuid UUID REFERENCES stock_transactions(uuid) ON DELETE CASCADE,
+    amount FLOAT NOT NULL,
+    record_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX cash_transactions_user_uuid_idx ON cash_transactions (user_uuid);
+
+-- Users that already exist get the same starting balance as new users.
+INSERT INTO cash_transactions (user_uuid, amount, record_time)
+SELECT uuid, 100000.0, now() at time zone 'utc' FROM users;
+
+-- Trades that already happened are paid for out of that balance, as new trades are.
+INSERT INTO cash_transactions (user_uuid, stock_transaction_uuid, amount, record_time)
+SELECT user_uuid, uuid, -quantity * price_of_stock_at_time_of_trading, record_time
+FROM stock_transactions;
@@ -0,0 +1 @@
+ALTER TABLE stocks DROP CONSTRAINT stocks_symbol_key;
@@ -0,0 +1,17 @@
+-- Point the transactions of duplicated symbols at a single stock row, then remove the duplicates.
+WITH canonical AS (
+    SELECT DISTINCT ON (symbol) symbol, uuid FROM stocks ORDER BY symbol, uuid
+)
+UPDATE stock_transactions
+SET stock_uuid = canonical.uuid
+FROM stocks
+JOIN canonical ON stocks.symbol = canonical.symbol
+WHERE stock_transactions.stock_uuid = stocks.uuid
+    AND stocks.uuid <> canonical.uuid;
+
+DELETE FROM stocks
+USING stocks AS kept
+WHERE stocks.symbol = kept.symbol
+    AND stocks.uuid > kept.uuid;
+
+ALTER TABLE stocks ADD CONSTRAINT stocks_symbol_key UNIQUE (symbol);
@@ -0,0 +1,15 @@
+CREATE TABLE orders (
+    uuid UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_uuid UUID NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    stock_uuid UUID NOT NULL REFERENCES stocks(uuid) ON DELETE CASCADE,
+    kind VARCHAR NOT NULL CHECK (kind IN ('limit', 'stop')),
+    quantity INTEGER NOT NULL CHECK (quantity <> 0),
+    trigger_price FLOAT NOT NULL CHECK (trigger_price > 0),
+    status VARCHAR NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'filled', 'cancelled', 'rejected')),
+    stock_transaction_uuid UUID REFERENCES stock_transactions(uuid) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL,
+    closed_at TIMESTAMP
+);
+
+CREATE INDEX orders_open_idx ON orders (status) WHERE status = 'open';
+CREATE INDEX orders_user_uuid_idx ON orders (user_uuid);
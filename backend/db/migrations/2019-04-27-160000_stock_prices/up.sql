@@ -0,0 +1,8 @@
+CREATE TABLE stock_prices (
+    uuid UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    symbol VARCHAR NOT NULL,
+    price FLOAT NOT NULL,
+    record_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX stock_prices_symbol_record_time_idx ON stock_prices (symbol, record_time);
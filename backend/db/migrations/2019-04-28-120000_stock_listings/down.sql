@@ -0,0 +1,3 @@
+DROP TABLE stock_listings;
+ALTER TABLE stocks DROP COLUMN exchange;
+ALTER TABLE stocks DROP CONSTRAINT stocks_symbol_normalized;
@@ -0,0 +1,43 @@
+-- Symbols are stored trimmed and upper-case.
+-- Point the transactions and orders of stocks that only differ by case or whitespace at a single stock row,
+-- then remove the duplicates.
+WITH canonical AS (
+    SELECT DISTINCT ON (upper(trim(symbol))) upper(trim(symbol)) AS symbol, uuid
+    FROM stocks
+    ORDER BY upper(trim(symbol)), uuid
+)
+UPDATE stock_transactions
+SET stock_uuid = canonical.uuid
+FROM stocks
+JOIN canonical ON upper(trim(stocks.symbol)) = canonical.symbol
+WHERE stock_transactions.stock_uuid = stocks.uuid
+    AND stocks.uuid <> canonical.uuid;
+
+WITH canonical AS (
+    SELECT DISTINCT ON (upper(trim(symbol))) upper(trim(symbol)) AS symbol, uuid
+    FROM stocks
+    ORDER BY upper(trim(symbol)), uuid
+)
+UPDATE orders
+SET stock_uuid = canonical.uuid
+FROM stocks
+JOIN canonical ON upper(trim(stocks.symbol)) = canonical.symbol
+WHERE orders.stock_uuid = stocks.uuid
+    AND stocks.uuid <> canonical.uuid;
+
+DELETE FROM stocks
+USING stocks AS kept
+WHERE upper(trim(stocks.symbol)) = upper(trim(kept.symbol))
+    AND stocks.uuid > kept.uuid;
+
+UPDATE stocks SET symbol = upper(trim(symbol));
+
+ALTER TABLE stocks ADD CONSTRAINT stocks_symbol_normalized CHECK (symbol = upper(trim(symbol)));
+ALTER TABLE stocks ADD COLUMN exchange VARCHAR;
+
+-- The symbols the quote provider lists, cached so they can be searched without asking the provider.
+CREATE TABLE stock_listings (
+    symbol VARCHAR PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL,
+    exchange VARCHAR
+);
@@ -0,0 +1,14 @@
+CREATE TABLE watchlists (
+    uuid UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_uuid UUID NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    name VARCHAR NOT NULL CHECK (name <> ''),
+    created_at TIMESTAMP NOT NULL,
+    UNIQUE (user_uuid, name)
+);
+
+CREATE TABLE watchlist_entries (
+    watchlist_uuid UUID NOT NULL REFERENCES watchlists(uuid) ON DELETE CASCADE,
+    stock_uuid UUID NOT NULL REFERENCES stocks(uuid) ON DELETE CASCADE,
+    added_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (watchlist_uuid, stock_uuid)
+);
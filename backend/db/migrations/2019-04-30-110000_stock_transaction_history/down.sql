@@ -0,0 +1 @@
+DROP INDEX stock_transactions_user_uuid_record_time_idx;
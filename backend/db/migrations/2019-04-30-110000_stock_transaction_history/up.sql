@@ -0,0 +1 @@
+CREATE INDEX stock_transactions_user_uuid_record_time_idx ON stock_transactions (user_uuid, record_time, uuid);
@@ -0,0 +1,5 @@
+DROP INDEX users_display_name_idx;
+ALTER TABLE users DROP CONSTRAINT users_leaderboard_needs_display_name;
+ALTER TABLE users DROP CONSTRAINT users_display_name_not_empty;
+ALTER TABLE users DROP COLUMN leaderboard_opt_in;
+ALTER TABLE users DROP COLUMN display_name;
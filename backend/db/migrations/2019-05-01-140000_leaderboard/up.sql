@@ -0,0 +1,9 @@
+ALTER TABLE users ADD COLUMN display_name VARCHAR;
+ALTER TABLE users ADD COLUMN leaderboard_opt_in BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE users ADD CONSTRAINT users_display_name_not_empty CHECK (display_name <> '');
+-- Only users who have picked a name can be shown on the leaderboard.
+ALTER TABLE users ADD CONSTRAINT users_leaderboard_needs_display_name CHECK (NOT leaderboard_opt_in OR display_name IS NOT NULL);
+
+-- Names that only differ by case would be confusing to tell apart.
+CREATE UNIQUE INDEX users_display_name_idx ON users (lower(display_name));
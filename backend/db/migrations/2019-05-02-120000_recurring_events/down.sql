@@ -0,0 +1,6 @@
+DROP INDEX events_recurrence_id_idx;
+ALTER TABLE events DROP CONSTRAINT events_recurrence_override;
+ALTER TABLE events DROP COLUMN recurrence_id;
+ALTER TABLE events DROP COLUMN recurrence_parent_uuid;
+ALTER TABLE events DROP COLUMN exdates;
+ALTER TABLE events DROP COLUMN rrule;
@@ -0,0 +1,12 @@
+ALTER TABLE events ADD COLUMN rrule VARCHAR;
+ALTER TABLE events ADD COLUMN exdates TIMESTAMP[] NOT NULL DEFAULT '{}';
+-- An event that replaces a single occurrence of a recurring event.
+ALTER TABLE events ADD COLUMN recurrence_parent_uuid UUID REFERENCES events(uuid) ON DELETE CASCADE;
+ALTER TABLE events ADD COLUMN recurrence_id TIMESTAMP;
+
+-- A replacement must say which occurrence it replaces, and can't repeat itself.
+ALTER TABLE events ADD CONSTRAINT events_recurrence_override CHECK (
+    (recurrence_parent_uuid IS NULL) = (recurrence_id IS NULL)
+    AND (recurrence_parent_uuid IS NULL OR rrule IS NULL)
+);
+CREATE UNIQUE INDEX events_recurrence_id_idx ON events (recurrence_parent_uuid, recurrence_id);
@@ -0,0 +1,6 @@
+-- Each user can have one secret feed URL that calendar apps subscribe to without logging in.
+CREATE TABLE calendar_feeds (
+    user_uuid UUID PRIMARY KEY REFERENCES users(uuid) ON DELETE CASCADE,
+    token UUID NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP INDEX events_external_id_idx;
+ALTER TABLE events DROP COLUMN external_id;
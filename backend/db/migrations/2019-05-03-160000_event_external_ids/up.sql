@@ -0,0 +1,3 @@
+-- The id an event has in the calendar it was imported from, so importing it again updates it instead of duplicating it.
+ALTER TABLE events ADD COLUMN external_id VARCHAR;
+CREATE UNIQUE INDEX events_external_id_idx ON events (user_uuid, external_id);
@@ -0,0 +1,2 @@
+ALTER TABLE events DROP COLUMN time_zone;
+ALTER TABLE users DROP COLUMN time_zone;
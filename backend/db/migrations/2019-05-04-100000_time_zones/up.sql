@@ -0,0 +1,4 @@
+-- IANA zone names, like 'America/New_York'. Times are still stored in UTC.
+ALTER TABLE users ADD COLUMN time_zone VARCHAR NOT NULL DEFAULT 'UTC';
+-- The zone an event's local times are in. Recurring events repeat at the same local time in this zone.
+ALTER TABLE events ADD COLUMN time_zone VARCHAR;
@@ -0,0 +1,2 @@
+DROP INDEX events_user_time_idx;
+ALTER TABLE events DROP COLUMN all_day;
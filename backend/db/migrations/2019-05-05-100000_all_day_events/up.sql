@@ -0,0 +1,4 @@
+-- All-day events start and stop at midnight, and cover the same dates in every time zone.
+ALTER TABLE events ADD COLUMN all_day BOOLEAN NOT NULL DEFAULT FALSE;
+-- Range queries look for events that overlap a window of time.
+CREATE INDEX events_user_time_idx ON events (user_uuid, start_at, stop_at);
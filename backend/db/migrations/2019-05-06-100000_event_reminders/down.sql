@@ -0,0 +1,2 @@
+DROP TABLE reminder_outbox;
+DROP TABLE event_reminders;
@@ -0,0 +1,27 @@
+-- How long before each occurrence of an event its owner wants to be reminded.
+CREATE TABLE event_reminders (
+    uuid UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    event_uuid UUID NOT NULL REFERENCES events(uuid) ON DELETE CASCADE,
+    minutes_before INTEGER NOT NULL CHECK (minutes_before >= 0),
+    UNIQUE (event_uuid, minutes_before)
+);
+
+-- Reminders that have come due. Each occurrence is reminded of once, then delivered by a notifier,
+-- and shown to the user until they dismiss it.
+CREATE TABLE reminder_outbox (
+    uuid UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    reminder_uuid UUID NOT NULL REFERENCES event_reminders(uuid) ON DELETE CASCADE,
+    user_uuid UUID NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    event_uuid UUID NOT NULL REFERENCES events(uuid) ON DELETE CASCADE,
+    title VARCHAR NOT NULL,
+    starts_at TIMESTAMP NOT NULL,
+    fired_at TIMESTAMP NOT NULL,
+    delivered_at TIMESTAMP,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error VARCHAR,
+    dismissed_at TIMESTAMP,
+    UNIQUE (reminder_uuid, event_uuid, starts_at)
+);
+
+CREATE INDEX reminder_outbox_undelivered_idx ON reminder_outbox (fired_at) WHERE delivered_at IS NULL;
+CREATE INDEX reminder_outbox_user_idx ON reminder_outbox (user_uuid, fired_at);
@@ -0,0 +1,2 @@
+DROP TABLE event_invitations;
+DROP TABLE calendar_shares;
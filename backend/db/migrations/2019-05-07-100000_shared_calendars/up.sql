@@ -0,0 +1,23 @@
+-- Users can let other users see, or also change, the events in their calendar.
+CREATE TABLE calendar_shares (
+    owner_uuid UUID NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    member_uuid UUID NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    permission VARCHAR NOT NULL CHECK (permission IN ('read', 'write')),
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (owner_uuid, member_uuid),
+    CHECK (owner_uuid <> member_uuid)
+);
+
+CREATE INDEX calendar_shares_member_idx ON calendar_shares (member_uuid);
+
+-- Users invited to a single event, and whether they are going.
+CREATE TABLE event_invitations (
+    event_uuid UUID NOT NULL REFERENCES events(uuid) ON DELETE CASCADE,
+    invitee_uuid UUID NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    status VARCHAR NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'accepted', 'declined')),
+    invited_at TIMESTAMP NOT NULL,
+    responded_at TIMESTAMP,
+    PRIMARY KEY (event_uuid, invitee_uuid)
+);
+
+CREATE INDEX event_invitations_invitee_idx ON event_invitations (invitee_uuid);
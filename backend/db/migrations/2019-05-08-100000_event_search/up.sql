@@ -0,0 +1,5 @@
+-- Lets events be searched by the words in their title and text, with title matches ranked higher.
+-- Queries must use the same expression for the index to be used.
+CREATE INDEX events_search_idx ON events USING GIN (
+    (setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', text), 'B'))
+);
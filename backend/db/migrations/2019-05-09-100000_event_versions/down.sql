@@ -0,0 +1,3 @@
+DROP TRIGGER set_updated_at ON events;
+DROP FUNCTION set_updated_at_utc();
+ALTER TABLE events DROP COLUMN updated_at;
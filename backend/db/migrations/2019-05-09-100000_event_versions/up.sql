@@ -0,0 +1,19 @@
+-- When the event was last changed, which clients compare to avoid overwriting each other's changes.
+-- The trigger keeps it up to date on every change that doesn't set it itself.
+-- Like every other timestamp, it is kept in UTC, regardless of the session's time zone.
+ALTER TABLE events ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc');
+
+CREATE OR REPLACE FUNCTION set_updated_at_utc() RETURNS trigger AS $$
+BEGIN
+    IF (
+        NEW IS DISTINCT FROM OLD AND
+        NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at
+    ) THEN
+        NEW.updated_at := NOW() AT TIME ZONE 'utc';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER set_updated_at BEFORE UPDATE ON events
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_utc();
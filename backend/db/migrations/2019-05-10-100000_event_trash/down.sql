@@ -0,0 +1,6 @@
+DROP INDEX events_recurrence_id_idx;
+DROP INDEX events_external_id_idx;
+DROP INDEX events_deleted_at_idx;
+ALTER TABLE events DROP COLUMN deleted_at;
+CREATE UNIQUE INDEX events_external_id_idx ON events (user_uuid, external_id);
+CREATE UNIQUE INDEX events_recurrence_id_idx ON events (recurrence_parent_uuid, recurrence_id);
@@ -0,0 +1,13 @@
+-- Deleted events are kept in the trash until they are restored, or purged after a while.
+ALTER TABLE events ADD COLUMN deleted_at TIMESTAMP;
+-- The purge looks for events that have been in the trash long enough.
+CREATE INDEX events_deleted_at_idx ON events (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Events in the trash don't keep a live event from having their external id,
+-- so importing a calendar over the one it replaced doesn't clash with it.
+DROP INDEX events_external_id_idx;
+CREATE UNIQUE INDEX events_external_id_idx ON events (user_uuid, external_id) WHERE deleted_at IS NULL;
+-- Nor from replacing an occurrence that a replacement in the trash replaced.
+DROP INDEX events_recurrence_id_idx;
+CREATE UNIQUE INDEX events_recurrence_id_idx ON events (recurrence_parent_uuid, recurrence_id)
+    WHERE deleted_at IS NULL;
@@ -0,0 +1,60 @@
+//! All database queries directly related to calendar subscription feeds are contained within this module.
+use crate::{schema::calendar_feeds, user::User};
+use chrono::NaiveDateTime;
+use diesel::{
+    pg::{upsert::excluded, PgConnection},
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    ExpressionMethods, Identifiable, Insertable, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Struct to represent a row in the `calendar_feeds` table.
+/// A feed lets calendar apps read a user's events using a secret token instead of logging in.
+#[derive(
+    Clone, Copy, Debug, Identifiable, Queryable, Insertable, Associations, Serialize, Deserialize, PartialEq,
+)]
+#[primary_key(user_uuid)]
+#[belongs_to(User, foreign_key = "user_uuid")]
+#[table_name = "calendar_feeds"]
+pub struct CalendarFeed {
+    /// The user whose events the feed serves.
+    pub user_uuid: Uuid,
+    /// The secret that identifies the feed.
+    pub token: Uuid,
+    /// When the token was issued.
+    pub created_at: NaiveDateTime,
+}
+
+impl CalendarFeed {
+    /// Creates the user's feed, replacing the token of any feed they already had.
+    pub fn replace_feed(feed: CalendarFeed, conn: &PgConnection) -> QueryResult<CalendarFeed> {
+        diesel::insert_into(calendar_feeds::table)
+            .values(&feed)
+            .on_conflict(calendar_feeds::user_uuid)
+            .do_update()
+            .set((
+                calendar_feeds::token.eq(excluded(calendar_feeds::token)),
+                calendar_feeds::created_at.eq(excluded(calendar_feeds::created_at)),
+            ))
+            .get_result(conn)
+    }
+
+    /// Gets the user's feed.
+    pub fn get_feed_for_user(user_uuid: Uuid, conn: &PgConnection) -> QueryResult<CalendarFeed> {
+        calendar_feeds::table.find(user_uuid).first(conn)
+    }
+
+    /// Gets the feed a token belongs to.
+    pub fn get_feed_by_token(token: Uuid, conn: &PgConnection) -> QueryResult<CalendarFeed> {
+        calendar_feeds::table
+            .filter(calendar_feeds::token.eq(token))
+            .first(conn)
+    }
+
+    /// Deletes the user's feed, so its token stops working.
+    pub fn delete_feed(user_uuid: Uuid, conn: &PgConnection) -> QueryResult<CalendarFeed> {
+        crate::util::delete_row(calendar_feeds::table, user_uuid, conn)
+    }
+}
@@ -0,0 +1,144 @@
+//! All database queries directly related to sharing calendars between users are contained within this module.
+use crate::schema::calendar_shares;
+use chrono::NaiveDateTime;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::{upsert::excluded, Pg, PgConnection},
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    serialize::{self, Output, ToSql},
+    sql_types::Varchar,
+    ExpressionMethods, Identifiable, Insertable, OptionalExtension, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use uuid::Uuid;
+
+/// What a user can do with a calendar that was shared with them.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Varchar"]
+pub enum SharePermission {
+    /// See the calendar's events.
+    Read,
+    /// See, change, and remove the calendar's events.
+    Write,
+}
+
+impl SharePermission {
+    fn as_str(self) -> &'static str {
+        match self {
+            SharePermission::Read => "read",
+            SharePermission::Write => "write",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for SharePermission {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Varchar, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Varchar, Pg> for SharePermission {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"read" => Ok(SharePermission::Read),
+            b"write" => Ok(SharePermission::Write),
+            _ => Err("Unrecognized share permission".into()),
+        }
+    }
+}
+
+/// Struct to represent a row in the `calendar_shares` table.
+/// A share lets a member see the events in the owner's calendar, and maybe change them.
+#[derive(
+    Clone, Copy, Debug, Identifiable, Queryable, Insertable, Serialize, Deserialize, PartialEq,
+)]
+#[primary_key(owner_uuid, member_uuid)]
+#[table_name = "calendar_shares"]
+pub struct CalendarShare {
+    /// The user whose calendar is shared.
+    pub owner_uuid: Uuid,
+    /// The user the calendar is shared with.
+    pub member_uuid: Uuid,
+    /// What the member can do with the owner's events.
+    pub permission: SharePermission,
+    /// When the calendar was shared with the member.
+    pub created_at: NaiveDateTime,
+}
+
+impl CalendarShare {
+    /// Shares the owner's calendar with the member,
+    /// or changes the permission of a member it is already shared with.
+    pub fn share(share: CalendarShare, conn: &PgConnection) -> QueryResult<CalendarShare> {
+        diesel::insert_into(calendar_shares::table)
+            .values(&share)
+            .on_conflict((calendar_shares::owner_uuid, calendar_shares::member_uuid))
+            .do_update()
+            .set(calendar_shares::permission.eq(excluded(calendar_shares::permission)))
+            .get_result(conn)
+    }
+
+    /// Stops sharing the owner's calendar with the member.
+    pub fn unshare(
+        owner_uuid: Uuid,
+        member_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<CalendarShare> {
+        diesel::delete(calendar_shares::table.find((owner_uuid, member_uuid))).get_result(conn)
+    }
+
+    /// Gets the users the owner shares their calendar with.
+    pub fn shares_by_owner(
+        owner_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<CalendarShare>> {
+        calendar_shares::table
+            .filter(calendar_shares::owner_uuid.eq(owner_uuid))
+            .order_by(calendar_shares::created_at)
+            .load(conn)
+    }
+
+    /// Gets the calendars that are shared with the member.
+    pub fn shares_with_member(
+        member_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<CalendarShare>> {
+        calendar_shares::table
+            .filter(calendar_shares::member_uuid.eq(member_uuid))
+            .order_by(calendar_shares::created_at)
+            .load(conn)
+    }
+
+    /// Gets what a user can do with the events in the owner's calendar.
+    ///
+    /// # Return
+    /// `Write` if the user is the owner, or `None` if the calendar isn't shared with them.
+    pub fn permission(
+        owner_uuid: Uuid,
+        user_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Option<SharePermission>> {
+        if owner_uuid == user_uuid {
+            return Ok(Some(SharePermission::Write));
+        }
+        calendar_shares::table
+            .find((owner_uuid, user_uuid))
+            .select(calendar_shares::permission)
+            .first(conn)
+            .optional()
+    }
+}
@@ -0,0 +1,120 @@
+//! All database queries directly related to the cash users hold for trading are contained within this module.
+use crate::{
+    schema::{self, cash_transactions},
+    stock::StockTransaction,
+    user::User,
+    util,
+};
+use chrono::NaiveDateTime;
+use diesel::{
+    dsl::sum,
+    pg::PgConnection,
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    ExpressionMethods, Identifiable, Insertable, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The amount of cash every user starts out with.
+pub const STARTING_BALANCE: f64 = 100_000.0;
+
+/// Struct to represent a row in the `cash_transactions` table.
+/// A cash transaction records money entering or leaving a user's account.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Identifiable,
+    Queryable,
+    Associations,
+    Serialize,
+    Deserialize,
+    PartialOrd,
+    PartialEq,
+)]
+#[primary_key(uuid)]
+#[belongs_to(User, foreign_key = "user_uuid")]
+#[belongs_to(StockTransaction, foreign_key = "stock_transaction_uuid")]
+#[table_name = "cash_transactions"]
+pub struct CashTransaction {
+    /// Unique identifier.
+    pub uuid: Uuid,
+    /// Uuid of the user the transaction is associated with.
+    pub user_uuid: Uuid,
+    /// The stock trade that caused this transaction, if any.
+    pub stock_transaction_uuid: Option<Uuid>,
+    /// The amount of cash being moved.
+    /// Negative indicates a withdrawal, positive indicates a deposit.
+    pub amount: f64,
+    /// The time at which the transaction occurred.
+    pub record_time: NaiveDateTime,
+}
+
+/// A struct used for creating new rows in the `cash_transactions` table.
+#[derive(Insertable, Debug, Serialize, Deserialize, PartialOrd, PartialEq, Copy, Clone)]
+#[table_name = "cash_transactions"]
+pub struct NewCashTransaction {
+    /// Uuid of the user the transaction is associated with.
+    pub user_uuid: Uuid,
+    /// The stock trade that caused this transaction, if any.
+    pub stock_transaction_uuid: Option<Uuid>,
+    /// The amount of cash being moved.
+    pub amount: f64,
+    /// The time at which the transaction occurred.
+    pub record_time: NaiveDateTime,
+}
+
+impl NewCashTransaction {
+    /// The deposit that opens a new user's account.
+    pub fn opening_deposit(user_uuid: Uuid) -> Self {
+        NewCashTransaction {
+            user_uuid,
+            stock_transaction_uuid: None,
+            amount: STARTING_BALANCE,
+            record_time: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    /// The cash moved by a stock trade.
+    /// Purchases withdraw cash, sales deposit it.
+    pub fn for_stock_transaction(transaction: &StockTransaction) -> Self {
+        NewCashTransaction {
+            user_uuid: transaction.user_uuid,
+            stock_transaction_uuid: Some(transaction.uuid),
+            amount: -(f64::from(transaction.quantity)
+                * transaction.price_of_stock_at_time_of_trading),
+            record_time: transaction.record_time,
+        }
+    }
+}
+
+impl CashTransaction {
+    /// Creates a cash transaction.
+    pub fn create_cash_transaction(
+        new_transaction: NewCashTransaction,
+        conn: &PgConnection,
+    ) -> QueryResult<CashTransaction> {
+        util::create_row(schema::cash_transactions::table, new_transaction, conn)
+    }
+
+    /// Gets every cash transaction for a user.
+    pub fn get_cash_transactions_for_user(
+        user_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<CashTransaction>> {
+        cash_transactions::table
+            .filter(cash_transactions::user_uuid.eq(user_uuid))
+            .order(cash_transactions::record_time.asc())
+            .load(conn)
+    }
+
+    /// Gets the cash available to a user by summing their cash transactions.
+    pub fn get_balance(user_uuid: Uuid, conn: &PgConnection) -> QueryResult<f64> {
+        cash_transactions::table
+            .filter(cash_transactions::user_uuid.eq(user_uuid))
+            .select(sum(cash_transactions::amount))
+            .get_result::<Option<f64>>(conn)
+            .map(|balance| balance.unwrap_or(0.0))
+    }
+}
@@ -1,18 +1,29 @@
 //! All database queries directly related to calendar events are contained within this module.
 use crate::{
+    calendar_share::SharePermission,
+    invitation::InvitationStatus,
+    recurrence::RecurrenceRule,
     schema::{
-        self,
+        self, calendar_shares, event_invitations,
         events::{self, SqlType},
     },
+    time_zone,
     user::User,
 };
 use apply::Apply;
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use chrono_tz::Tz;
 use diesel::{
-    pg::Pg, query_dsl::QueryDsl, result::QueryResult, BoolExpressionMethods, ExpressionMethods,
-    Identifiable, Insertable, PgConnection, Queryable, RunQueryDsl,
+    dsl::sql,
+    pg::Pg,
+    query_dsl::QueryDsl,
+    result::{Error as DieselError, QueryResult},
+    sql_types::{Bool, Float, Text},
+    BoolExpressionMethods, Connection, ExpressionMethods, Identifiable, Insertable,
+    NullableExpressionMethods, PgConnection, Queryable, RunQueryDsl,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 /// A struct that represents a row in the `events` table.
@@ -36,6 +47,27 @@ pub struct Event {
     pub start_at: NaiveDateTime,
     /// When the event stops.
     pub stop_at: NaiveDateTime,
+    /// The RFC 5545 rule the event repeats by, if it is recurring.
+    /// `start_at` and `stop_at` describe the first occurrence.
+    pub rrule: Option<String>,
+    /// The starts of occurrences that have been removed from a recurring event.
+    pub exdates: Vec<NaiveDateTime>,
+    /// If this event replaces a single occurrence of a recurring event, the recurring event.
+    pub recurrence_parent_uuid: Option<Uuid>,
+    /// The original start of the occurrence that this event is, or replaces.
+    pub recurrence_id: Option<NaiveDateTime>,
+    /// The id the event has in the calendar it was imported from, like an iCalendar UID.
+    pub external_id: Option<String>,
+    /// The IANA name of the time zone the event is in, if it has one.
+    /// Recurring events repeat at the same local time in this zone.
+    pub time_zone: Option<String>,
+    /// Whether the event lasts whole days.
+    /// All-day events start and stop at midnight, and cover the same dates in every time zone.
+    pub all_day: bool,
+    /// When the event was last changed, in UTC.
+    pub updated_at: NaiveDateTime,
+    /// When the event was moved to the trash, in UTC, if it is there.
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 /// A struct that facilitates creation of a new row in the `events` table.
@@ -52,6 +84,20 @@ pub struct NewEvent {
     pub start_at: NaiveDateTime,
     /// When the event stops.
     pub stop_at: NaiveDateTime,
+    /// The RFC 5545 rule the event repeats by, if it is recurring.
+    pub rrule: Option<String>,
+    /// The starts of occurrences that have been removed from a recurring event.
+    pub exdates: Vec<NaiveDateTime>,
+    /// If this event replaces a single occurrence of a recurring event, the recurring event.
+    pub recurrence_parent_uuid: Option<Uuid>,
+    /// The original start of the occurrence that this event replaces.
+    pub recurrence_id: Option<NaiveDateTime>,
+    /// The id the event has in the calendar it was imported from.
+    pub external_id: Option<String>,
+    /// The IANA name of the time zone the event is in, if it has one.
+    pub time_zone: Option<String>,
+    /// Whether the event lasts whole days.
+    pub all_day: bool,
 }
 
 /// A changeset that facilitates altering a row in the `events` table.
@@ -68,6 +114,46 @@ pub struct EventChangeset {
     pub start_at: NaiveDateTime,
     /// When the event stops.
     pub stop_at: NaiveDateTime,
+    /// The rule the event repeats by. `None` leaves the rule unchanged.
+    #[serde(default)]
+    pub rrule: Option<String>,
+    /// The time zone the event is in. `None` leaves the zone unchanged.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    /// Whether the event lasts whole days. `None` leaves it unchanged.
+    #[serde(default)]
+    pub all_day: Option<bool>,
+}
+
+/// A partial change to a row in the `events` table. Only the fields that are present are altered.
+#[derive(Clone, Debug, Default, PartialEq, AsChangeset, Serialize, Deserialize)]
+#[table_name = "events"]
+pub struct EventPatch {
+    /// The title of the event.
+    pub title: Option<String>,
+    /// The body of the event.
+    pub text: Option<String>,
+    /// When the event starts.
+    pub start_at: Option<NaiveDateTime>,
+    /// When the event stops.
+    pub stop_at: Option<NaiveDateTime>,
+    /// The rule the event repeats by. `null` stops the event from repeating.
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub rrule: Option<Option<String>>,
+    /// The time zone the event is in. `null` clears the zone.
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<Option<String>>,
+    /// Whether the event lasts whole days.
+    pub all_day: Option<bool>,
+}
+
+/// Deserializes a field that is present, so that `null` can be told apart from a missing field.
+fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 /// A type used for importing and exporting events.
@@ -81,6 +167,162 @@ pub struct ImportExportEvent {
     pub start_at: NaiveDateTime,
     /// When the event stops.
     pub stop_at: NaiveDateTime,
+    /// The RFC 5545 rule the event repeats by, if it is recurring.
+    #[serde(default)]
+    pub rrule: Option<String>,
+    /// The starts of occurrences that have been removed from a recurring event.
+    #[serde(default)]
+    pub exdates: Vec<NaiveDateTime>,
+    /// A stable id for the event in the calendar it comes from, used to match it when importing again.
+    #[serde(default)]
+    pub external_id: Option<String>,
+    /// The IANA name of the time zone the event is in, if it has one.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    /// Whether the event lasts whole days.
+    #[serde(default)]
+    pub all_day: bool,
+}
+
+/// How imported events are combined with the events a user already has.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Every existing event is moved to the trash, then the imported events are created.
+    Replace,
+    /// Imported events are matched with existing events, which are updated instead of duplicated.
+    /// Nothing is deleted.
+    Merge,
+}
+
+impl Default for ImportMode {
+    fn default() -> Self {
+        ImportMode::Replace
+    }
+}
+
+/// What an import did, or would do if it isn't a dry run.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ImportSummary {
+    /// Events that were created.
+    pub created: usize,
+    /// Existing events that were changed to match the imported events.
+    pub updated: usize,
+    /// Imported events that already existed as they are.
+    pub unchanged: usize,
+    /// Imported events that couldn't be matched with certainty, and were skipped.
+    pub conflicting: usize,
+    /// Existing events that were moved to the trash.
+    pub deleted: usize,
+}
+
+/// An event that matched a search.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventSearchResult {
+    /// The matching event.
+    #[serde(flatten)]
+    pub event: Event,
+    /// How well the event matched. Higher is better.
+    pub rank: f32,
+    /// Fragments of the title and text around the matching words, which are wrapped in `<b>` tags.
+    pub snippet: String,
+}
+
+/// The words of an event, weighted so matches in the title rank above matches in the text.
+/// This is the expression `events_search_idx` indexes, so the two must be changed together.
+const SEARCH_VECTOR: &str = concat!(
+    "(setweight(to_tsvector('english', events.title), 'A')",
+    " || setweight(to_tsvector('english', events.text), 'B'))"
+);
+
+/// Everything about an event that an import can set, used to tell whether two events are the same.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct EventContents {
+    title: String,
+    text: String,
+    start_at: NaiveDateTime,
+    stop_at: NaiveDateTime,
+    rrule: Option<String>,
+    exdates: Vec<NaiveDateTime>,
+    time_zone: Option<String>,
+    all_day: bool,
+}
+
+impl<'a> From<&'a Event> for EventContents {
+    fn from(event: &'a Event) -> Self {
+        EventContents {
+            title: event.title.clone(),
+            text: event.text.clone(),
+            start_at: event.start_at,
+            stop_at: event.stop_at,
+            rrule: event.rrule.clone(),
+            exdates: event.exdates.clone(),
+            time_zone: event.time_zone.clone(),
+            all_day: event.all_day,
+        }
+    }
+}
+
+impl<'a> From<&'a ImportExportEvent> for EventContents {
+    fn from(event: &'a ImportExportEvent) -> Self {
+        EventContents {
+            title: event.title.clone(),
+            text: event.text.clone(),
+            start_at: event.start_at,
+            stop_at: event.stop_at,
+            rrule: event.rrule.clone(),
+            exdates: event.exdates.clone(),
+            time_zone: event.time_zone.clone(),
+            all_day: event.all_day,
+        }
+    }
+}
+
+impl ImportExportEvent {
+    /// Attaches the user the event is being imported for.
+    fn into_new_event(self, user_uuid: Uuid) -> NewEvent {
+        NewEvent {
+            user_uuid,
+            title: self.title,
+            text: self.text,
+            start_at: self.start_at,
+            stop_at: self.stop_at,
+            rrule: self.rrule,
+            exdates: self.exdates,
+            recurrence_parent_uuid: None,
+            recurrence_id: None,
+            external_id: self.external_id,
+            time_zone: self.time_zone,
+            all_day: self.all_day,
+        }
+    }
+}
+
+/// Which occurrences of a recurring event a change applies to.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecurrenceScope {
+    /// Only the chosen occurrence.
+    This,
+    /// The chosen occurrence and every one after it.
+    Following,
+}
+
+/// New details for one or more occurrences of a recurring event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OccurrenceChange {
+    /// The title of the event.
+    pub title: String,
+    /// The body of the event.
+    pub text: String,
+    /// When the occurrence starts.
+    pub start_at: NaiveDateTime,
+    /// When the occurrence stops.
+    pub stop_at: NaiveDateTime,
+    /// The rule that the changed occurrences repeat by, when changing following occurrences.
+    /// `None` keeps repeating the way the event did.
+    #[serde(default)]
+    pub rrule: Option<String>,
 }
 
 /// Limits the number to between 0 and 11.
@@ -105,6 +347,11 @@ impl MonthIndex {
             Some(MonthIndex(value))
         }
     }
+
+    /// The month's number, counting January as 1.
+    pub fn to_1_indexed_u32(self) -> u32 {
+        self.0 + 1
+    }
 }
 
 /// Wrapper around year values.
@@ -121,6 +368,48 @@ impl Year {
             Some(Year(value))
         }
     }
+
+    /// The first and last days of a month in the year.
+    pub fn month(self, month: MonthIndex) -> (NaiveDate, NaiveDate) {
+        let first = NaiveDate::from_ymd(self.0, month.to_1_indexed_u32(), 1);
+        let next = match month.to_1_indexed_u32() {
+            12 => NaiveDate::from_ymd(self.0 + 1, 1, 1),
+            number => NaiveDate::from_ymd(self.0, number + 1, 1),
+        };
+        (first, next.pred())
+    }
+
+    /// The Monday and Sunday of an ISO 8601 week in the year.
+    /// Years have 52 or 53 weeks, and the first is the one with the year's first Thursday.
+    pub fn iso_week(self, week: u32) -> Option<(NaiveDate, NaiveDate)> {
+        let monday = NaiveDate::from_isoywd_opt(self.0, week, Weekday::Mon)?;
+        Some((monday, monday + Duration::days(6)))
+    }
+
+    /// A day in the year.
+    pub fn day(self, month: MonthIndex, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(self.0, month.to_1_indexed_u32(), day)
+    }
+}
+
+/// The events shown on one day of a user's calendar.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventDay {
+    /// The day, in the user's time zone.
+    pub date: NaiveDate,
+    /// The events that take place at some point during the day, in order of when they start.
+    pub events: Vec<Event>,
+}
+
+/// Whether an event from `start_at` to `stop_at` overlaps the range from `start` up to `end`.
+/// Events that take no time overlap the range if they start within it.
+fn overlaps(
+    start_at: NaiveDateTime,
+    stop_at: NaiveDateTime,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> bool {
+    start_at < end && (stop_at > start || start_at >= start)
 }
 
 /// A type representing all the columns in the events table.
@@ -134,6 +423,15 @@ type AllColumns = (
     events::text,
     events::start_at,
     events::stop_at,
+    events::rrule,
+    events::exdates,
+    events::recurrence_parent_uuid,
+    events::recurrence_id,
+    events::external_id,
+    events::time_zone,
+    events::all_day,
+    events::updated_at,
+    events::deleted_at,
 );
 
 /// All columns contained within the event's table.
@@ -144,6 +442,15 @@ pub const ALL_COLUMNS: AllColumns = (
     events::text,
     events::start_at,
     events::stop_at,
+    events::rrule,
+    events::exdates,
+    events::recurrence_parent_uuid,
+    events::recurrence_id,
+    events::external_id,
+    events::time_zone,
+    events::all_day,
+    events::updated_at,
+    events::deleted_at,
 );
 
 /// Abstract boxed query specific to the events table and Postgres.
@@ -154,49 +461,252 @@ impl Event {
     pub(crate) fn all() -> All {
         events::table.select(ALL_COLUMNS)
     }
-    /// Abstract query returning all events that belong to a user.
+    /// Abstract query returning all events that belong to a user, except those in the trash.
     pub(crate) fn user_events<'a>(user_uuid: Uuid) -> BoxedQuery<'a> {
         Self::all()
             .filter(events::user_uuid.eq(user_uuid))
+            .filter(events::deleted_at.is_null())
+            .into_boxed()
+    }
+
+    /// Abstract query returning all events shown in a user's calendar.
+    ///
+    /// These are the user's events, the events of calendars shared with them,
+    /// and the events they are invited to and haven't declined,
+    /// along with the events replacing occurrences of those that recur.
+    /// Events in the trash aren't shown.
+    pub(crate) fn calendar_events<'a>(user_uuid: Uuid) -> BoxedQuery<'a> {
+        let shared_owners = calendar_shares::table
+            .select(calendar_shares::owner_uuid)
+            .filter(calendar_shares::member_uuid.eq(user_uuid));
+        let invited = || {
+            event_invitations::table
+                .filter(event_invitations::invitee_uuid.eq(user_uuid))
+                .filter(event_invitations::status.ne(InvitationStatus::Declined))
+        };
+        let invited_events = invited().select(event_invitations::event_uuid);
+        let invited_parents = invited().select(event_invitations::event_uuid.nullable());
+        Self::all()
+            .filter(
+                events::user_uuid
+                    .eq(user_uuid)
+                    .or(events::user_uuid.eq_any(shared_owners))
+                    .or(events::uuid.eq_any(invited_events))
+                    .or(events::recurrence_parent_uuid.eq_any(invited_parents)),
+            )
+            .filter(events::deleted_at.is_null())
             .into_boxed()
     }
 
-    fn delete_events_for_user(user_uuid: Uuid, conn: &PgConnection) -> QueryResult<usize> {
-        diesel::delete(events::table.filter(events::user_uuid.eq(user_uuid))).execute(conn)
+    /// Searches the events shown in a user's calendar for words in their title and text,
+    /// best matches first.
+    ///
+    /// The search is written like a web search. Every word must match, in any form,
+    /// "quoted phrases" must match in order, `or` allows either side to match, and `-word` excludes.
+    ///
+    /// # Arguments
+    /// * user_uuid - The user whose calendar is searched.
+    /// * search - What to search for.
+    /// * range - If present, only events that overlap the range, in UTC, are searched.
+    /// Recurring events are found if one of their occurrences does.
+    /// * limit - The maximum number of events to return.
+    /// * conn - The connection to the database.
+    pub fn search_events(
+        user_uuid: Uuid,
+        search: &str,
+        range: Option<(NaiveDateTime, NaiveDateTime)>,
+        limit: i64,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<EventSearchResult>> {
+        let search_query = "websearch_to_tsquery('english', ";
+        let matches = sql::<Bool>(&format!("{} @@ {}", SEARCH_VECTOR, search_query))
+            .bind::<Text, _>(search.to_string())
+            .sql(")");
+        let rank = || {
+            sql::<Float>(&format!("ts_rank({}, {}", SEARCH_VECTOR, search_query))
+                .bind::<Text, _>(search.to_string())
+                .sql("))")
+        };
+        let snippet = sql::<Text>(&format!(
+            "ts_headline('english', concat_ws(': ', events.title, nullif(events.text, '')), {}",
+            search_query
+        ))
+        .bind::<Text, _>(search.to_string())
+        .sql("), 'MaxFragments=2, MinWords=5, MaxWords=20')");
+
+        let mut query = Self::calendar_events(user_uuid)
+            .select((ALL_COLUMNS, rank(), snippet))
+            .filter(matches)
+            .order((rank().desc(), events::start_at));
+        match range {
+            Some((start, end)) => {
+                // Recurring events are only checked for occurrences once they are loaded,
+                // so the limit is applied after they are, to keep the page full.
+                query = query.filter(
+                    events::start_at.lt(end).and(
+                        events::rrule
+                            .is_not_null()
+                            .or(events::stop_at.gt(start))
+                            .or(events::start_at.ge(start)),
+                    ),
+                );
+            }
+            None => query = query.limit(limit),
+        }
+
+        query
+            .load::<(Event, f32, String)>(conn)?
+            .into_iter()
+            .filter(|(event, _, _)| match range {
+                Some((start, end)) if event.rrule.is_some() => {
+                    !event.occurrences_between(start, end).is_empty()
+                }
+                _ => true,
+            })
+            .take(limit as usize)
+            .map(|(event, rank, snippet)| EventSearchResult {
+                event,
+                rank,
+                snippet,
+            })
+            .collect::<Vec<_>>()
+            .apply(Ok)
+    }
+
+    /// Moves every event a user has to the trash.
+    fn trash_events_for_user(
+        user_uuid: Uuid,
+        deleted_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<usize> {
+        events::table
+            .filter(events::user_uuid.eq(user_uuid))
+            .filter(events::deleted_at.is_null())
+            .apply(diesel::update)
+            .set(events::deleted_at.eq(deleted_at))
+            .execute(conn)
     }
 
     /// Allows the creation of many events at a time.
+    ///
+    /// When merging, an imported event with an external id updates the event that has the same external id.
+    /// Other imported events are skipped if an event with the same contents already exists,
+    /// and are counted as conflicting if an event with the same title and start differs from them.
+    /// The whole import happens in a single transaction.
+    ///
+    /// # Arguments
+    /// * import_events - The events to import.
+    /// * user_uuid - The user the events belong to.
+    /// * mode - How the imported events are combined with the user's events.
+    /// * dry_run - If true, nothing is changed, but the summary still describes what would have been.
+    /// * now - The current time, in UTC, which replaced events are moved to the trash at.
+    /// * conn - The connection to the database.
     pub fn import_events(
         import_events: Vec<ImportExportEvent>,
         user_uuid: Uuid,
+        mode: ImportMode,
+        dry_run: bool,
+        now: NaiveDateTime,
         conn: &PgConnection,
-    ) -> QueryResult<()> {
-        // Requirements call for "deduplication" to be performed by just deleting the every event for the user.
-        Event::delete_events_for_user(user_uuid, conn)?;
+    ) -> QueryResult<ImportSummary> {
+        conn.transaction(|| {
+            let mut summary = ImportSummary::default();
+            let mut new_events: Vec<NewEvent> = vec![];
+            let mut changes: Vec<(Uuid, ImportExportEvent)> = vec![];
 
-        let new_events: Vec<NewEvent> = import_events
-            .into_iter()
-            .map(|event| NewEvent {
-                user_uuid,
-                title: event.title,
-                text: event.text,
-                start_at: event.start_at,
-                stop_at: event.stop_at,
-            })
-            .collect();
+            let existing = Self::user_events(user_uuid).load::<Event>(conn)?;
+            match mode {
+                ImportMode::Replace => {
+                    summary.deleted = existing.len();
+                    new_events = import_events
+                        .into_iter()
+                        .map(|event| event.into_new_event(user_uuid))
+                        .collect();
+                }
+                ImportMode::Merge => {
+                    let by_external_id: HashMap<&str, &Event> = existing
+                        .iter()
+                        .filter_map(|e| Some((e.external_id.as_ref()?.as_str(), e)))
+                        .collect();
+                    let mut contents: HashSet<EventContents> =
+                        existing.iter().map(EventContents::from).collect();
+                    let mut titles_and_starts: HashSet<(String, NaiveDateTime)> = existing
+                        .iter()
+                        .map(|e| (e.title.clone(), e.start_at))
+                        .collect();
+                    let mut imported_ids: HashMap<String, EventContents> = HashMap::new();
+
+                    for event in import_events {
+                        let event_contents = EventContents::from(&event);
+                        if let Some(external_id) = event.external_id.clone() {
+                            // The same id appearing twice with different contents can't be resolved.
+                            if let Some(previous) = imported_ids.get(&external_id) {
+                                if *previous == event_contents {
+                                    summary.unchanged += 1;
+                                } else {
+                                    summary.conflicting += 1;
+                                }
+                                continue;
+                            }
+                            imported_ids.insert(external_id.clone(), event_contents.clone());
+
+                            if let Some(existing) = by_external_id.get(external_id.as_str()) {
+                                if EventContents::from(*existing) == event_contents {
+                                    summary.unchanged += 1;
+                                } else {
+                                    summary.updated += 1;
+                                    changes.push((existing.uuid, event));
+                                }
+                                continue;
+                            }
+                        }
+
+                        let title_and_start = (event.title.clone(), event.start_at);
+                        if contents.contains(&event_contents) {
+                            summary.unchanged += 1;
+                        } else if titles_and_starts.contains(&title_and_start) {
+                            summary.conflicting += 1;
+                        } else {
+                            contents.insert(event_contents);
+                            titles_and_starts.insert(title_and_start);
+                            new_events.push(event.into_new_event(user_uuid));
+                        }
+                    }
+                }
+            }
+            summary.created = new_events.len();
 
-        // An approximate limit for the number of items diesel can insert at once.
-        const DIESEL_ELEMENT_LIMIT: usize = 20_000;
+            if dry_run {
+                return Ok(summary);
+            }
+            if mode == ImportMode::Replace {
+                Event::trash_events_for_user(user_uuid, now, conn)?;
+            }
+            for (uuid, event) in changes {
+                diesel::update(events::table.find(uuid))
+                    .set((
+                        events::title.eq(event.title),
+                        events::text.eq(event.text),
+                        events::start_at.eq(event.start_at),
+                        events::stop_at.eq(event.stop_at),
+                        events::rrule.eq(event.rrule),
+                        events::exdates.eq(event.exdates),
+                        events::time_zone.eq(event.time_zone),
+                        events::all_day.eq(event.all_day),
+                    ))
+                    .execute(conn)?;
+            }
 
-        new_events
-            .chunks(DIESEL_ELEMENT_LIMIT)
-            .map(move |chunk| {
+            // An approximate limit for the number of items diesel can insert at once.
+            const DIESEL_ELEMENT_LIMIT: usize = 20_000;
+
+            for chunk in new_events.chunks(DIESEL_ELEMENT_LIMIT) {
                 diesel::insert_into(events::table)
                     .values(chunk)
-                    .execute(conn)
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .map(|_| ())
+                    .execute(conn)?;
+            }
+            Ok(summary)
+        })
     }
 
     /// Returns every event that belongs to a given user, without user information.
@@ -212,6 +722,11 @@ impl Event {
                 text: e.text,
                 start_at: e.start_at,
                 stop_at: e.stop_at,
+                rrule: e.rrule,
+                exdates: e.exdates,
+                external_id: e.external_id,
+                time_zone: e.time_zone,
+                all_day: e.all_day,
             })
             .collect::<Vec<_>>()
             .apply(Ok)
@@ -222,21 +737,440 @@ impl Event {
         Self::user_events(user_uuid).load::<Event>(conn)
     }
 
-    /// All events occurring from a starting time to an end time, that belong to a user.
+    /// All events that overlap the range from a starting time up to an end time,
+    /// that are shown in a user's calendar.
+    ///
+    /// Events that are still running at the start of the range are included,
+    /// as are events that take no time and start within it.
+    /// All-day events are matched against the dates the range covers in the user's time zone.
+    /// Recurring events are expanded into an event for each of their occurrences within the range,
+    /// which share the recurring event's uuid and have `recurrence_id` set to the occurrence's start.
+    ///
+    /// # Arguments
+    /// * user_uuid - The user whose calendar the events are shown in.
+    /// * start - The start of the range, in UTC.
+    /// * end - The end of the range, in UTC.
+    /// * zone - The user's time zone.
+    /// * conn - The connection to the database.
     pub fn events_from_n_to_n(
         user_uuid: Uuid,
         start: NaiveDateTime,
         end: NaiveDateTime,
+        zone: Tz,
         conn: &PgConnection,
     ) -> QueryResult<Vec<Event>> {
-        Self::user_events(user_uuid)
-            .filter(events::start_at.gt(start).and(events::start_at.lt(end)))
-            .load::<Event>(conn)
+        let local_start = time_zone::to_local(start, zone);
+        let local_end = time_zone::to_local(end, zone);
+
+        let timed = events::all_day.eq(false).and(
+            events::start_at
+                .lt(end)
+                .and(events::stop_at.gt(start).or(events::start_at.ge(start))),
+        );
+        let all_day = events::all_day.eq(true).and(
+            events::start_at
+                .lt(local_end)
+                .and(events::stop_at.gt(local_start).or(events::start_at.ge(local_start))),
+        );
+        let mut events = Self::calendar_events(user_uuid)
+            .filter(events::rrule.is_null())
+            .filter(timed.or(all_day))
+            .load::<Event>(conn)?;
+        let recurring = Self::calendar_events(user_uuid)
+            .filter(events::rrule.is_not_null())
+            .filter(events::start_at.lt(end.max(local_end)))
+            .load::<Event>(conn)?;
+
+        events.extend(recurring.iter().flat_map(|event| {
+            if event.all_day {
+                event.occurrences_between(local_start, local_end)
+            } else {
+                event.occurrences_between(start, end)
+            }
+        }));
+        // All-day events come before the timed events that start at the same time.
+        events.sort_by_key(|event| {
+            if event.all_day {
+                (time_zone::to_utc(event.start_at, zone), false)
+            } else {
+                (event.start_at, true)
+            }
+        });
+        Ok(events)
+    }
+
+    /// Gets the events shown in a user's calendar on each day from `first` to `last`,
+    /// in the user's time zone.
+    ///
+    /// Events that span several days are shown on every day they cover.
+    /// Events that stop at midnight aren't shown on the day they stop.
+    ///
+    /// # Arguments
+    /// * user_uuid - The user whose calendar the events are shown in.
+    /// * first - The first day.
+    /// * last - The last day, which is included.
+    /// * zone - The user's time zone.
+    /// * conn - The connection to the database.
+    pub fn events_by_day(
+        user_uuid: Uuid,
+        first: NaiveDate,
+        last: NaiveDate,
+        zone: Tz,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<EventDay>> {
+        let start = time_zone::to_utc(first.and_hms(0, 0, 0), zone);
+        let end = time_zone::to_utc(last.succ().and_hms(0, 0, 0), zone);
+        let events = Self::events_from_n_to_n(user_uuid, start, end, zone, conn)?;
+
+        let mut days: Vec<EventDay> = first
+            .iter_days()
+            .take_while(|date| *date <= last)
+            .map(|date| EventDay {
+                date,
+                events: vec![],
+            })
+            .collect();
+        for event in events {
+            // The times of all-day events are already local.
+            let (start_at, stop_at) = if event.all_day {
+                (event.start_at, event.stop_at)
+            } else {
+                (
+                    time_zone::to_local(event.start_at, zone),
+                    time_zone::to_local(event.stop_at, zone),
+                )
+            };
+            let first_day = start_at.date().max(first);
+            let last_day = if stop_at > start_at && stop_at.time() == NaiveTime::from_hms(0, 0, 0) {
+                stop_at.date().pred()
+            } else {
+                stop_at.date()
+            }
+            .min(last);
+            if first_day > last_day {
+                continue;
+            }
+            let offset = (first_day - first).num_days() as usize;
+            let length = (last_day - first_day).num_days() as usize + 1;
+            for day in days.iter_mut().skip(offset).take(length) {
+                day.events.push(event.clone());
+            }
+        }
+        Ok(days)
+    }
+
+    /// The rule the event repeats by, if it is recurring.
+    pub fn recurrence_rule(&self) -> Option<RecurrenceRule> {
+        self.rrule.as_ref().and_then(|rrule| rrule.parse().ok())
+    }
+
+    /// The time zone the event is in, if it has one that is known.
+    pub fn zone(&self) -> Option<Tz> {
+        self.time_zone.as_ref().and_then(|name| time_zone::parse_zone(name))
+    }
+
+    /// The starts of the event's occurrences before `end`, including removed occurrences.
+    ///
+    /// Occurrences are found in the event's time zone and converted back to UTC,
+    /// so they keep the same local time of day when daylight saving time starts or ends.
+    /// All-day events fall on the same dates in every zone, so they aren't converted.
+    fn occurrence_starts(&self, rule: &RecurrenceRule, end: NaiveDateTime) -> Vec<NaiveDateTime> {
+        match self.zone().filter(|_| !self.all_day) {
+            Some(zone) => {
+                let local_rule = RecurrenceRule {
+                    until: rule.until.map(|until| time_zone::to_local(until, zone)),
+                    ..rule.clone()
+                };
+                local_rule
+                    .occurrences(time_zone::to_local(self.start_at, zone))
+                    .map(|occurrence| time_zone::to_utc(occurrence, zone))
+                    .take_while(|occurrence| *occurrence < end)
+                    .collect()
+            }
+            None => rule
+                .occurrences(self.start_at)
+                .take_while(|occurrence| *occurrence < end)
+                .collect(),
+        }
+    }
+
+    /// Each occurrence of the event that overlaps the range, excluding removed occurrences.
+    /// An event that doesn't repeat only occurs once.
+    pub fn occurrences_between(&self, start: NaiveDateTime, end: NaiveDateTime) -> Vec<Event> {
+        let rule = match self.recurrence_rule() {
+            Some(rule) => rule,
+            None if overlaps(self.start_at, self.stop_at, start, end) => return vec![self.clone()],
+            None => return vec![],
+        };
+        let duration = self.stop_at - self.start_at;
+        self.occurrence_starts(&rule, end)
+            .into_iter()
+            .filter(|occurrence| {
+                overlaps(*occurrence, *occurrence + duration, start, end)
+                    && !self.exdates.contains(occurrence)
+            })
+            .map(|occurrence| Event {
+                start_at: occurrence,
+                stop_at: occurrence + duration,
+                recurrence_id: Some(occurrence),
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Changes one or more occurrences of a recurring event.
+    ///
+    /// Changing a single occurrence removes it from the recurring event and creates an event to replace it.
+    /// Changing the following occurrences ends the recurring event before the occurrence,
+    /// and creates a new recurring event starting from it.
+    /// Replacements after the change are moved to the trash,
+    /// and removed occurrences after it are discarded.
+    ///
+    /// # Arguments
+    /// * event - The recurring event.
+    /// * occurrence - The original start of the occurrence being changed.
+    /// * scope - Which occurrences to change.
+    /// * change - The new details.
+    /// * now - The current time, in UTC.
+    /// * conn - The connection to the database.
+    ///
+    /// # Return
+    /// The event replacing the changed occurrences.
+    /// It fails with `NotFound` if the event doesn't have an occurrence at that time.
+    pub fn change_occurrences(
+        event: &Event,
+        occurrence: NaiveDateTime,
+        scope: RecurrenceScope,
+        change: OccurrenceChange,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        let rule = event.occurrence_rule(occurrence, scope)?;
+        let new_rrule = change.rrule;
+        let new_event = NewEvent {
+            user_uuid: event.user_uuid,
+            title: change.title,
+            text: change.text,
+            start_at: change.start_at,
+            stop_at: change.stop_at,
+            rrule: None,
+            exdates: vec![],
+            recurrence_parent_uuid: None,
+            recurrence_id: None,
+            external_id: None,
+            time_zone: event.time_zone.clone(),
+            all_day: event.all_day,
+        };
+
+        conn.transaction(|| match scope {
+            RecurrenceScope::This => {
+                let mut exdates = event.exdates.clone();
+                exdates.push(occurrence);
+                Self::set_recurrence(event.uuid, event.rrule.clone(), exdates, conn)?;
+                NewEvent {
+                    recurrence_parent_uuid: Some(event.uuid),
+                    recurrence_id: Some(occurrence),
+                    ..new_event
+                }
+                .apply(|new_event| Self::create_event(new_event, conn))
+            }
+            RecurrenceScope::Following if occurrence == event.start_at => {
+                // Every occurrence follows the first one, so the whole event changes.
+                Self::delete_replacements(event.uuid, occurrence, now, conn)?;
+                diesel::update(events::table.find(event.uuid))
+                    .set((
+                        events::title.eq(new_event.title),
+                        events::text.eq(new_event.text),
+                        events::start_at.eq(new_event.start_at),
+                        events::stop_at.eq(new_event.stop_at),
+                        events::rrule.eq(new_rrule.or_else(|| event.rrule.clone())),
+                        events::exdates.eq(Vec::<NaiveDateTime>::new()),
+                    ))
+                    .get_result(conn)
+            }
+            RecurrenceScope::Following => {
+                let rrule = new_rrule.unwrap_or_else(|| {
+                    let before = event.occurrence_starts(&rule, occurrence).len() as u32;
+                    let count = rule.count.map(|count| count - before);
+                    RecurrenceRule {
+                        count,
+                        ..rule.clone()
+                    }
+                    .to_string()
+                });
+                Self::end_before(event, &rule, occurrence, now, conn)?;
+                NewEvent {
+                    rrule: Some(rrule),
+                    ..new_event
+                }
+                .apply(|new_event| Self::create_event(new_event, conn))
+            }
+        })
+    }
+
+    /// Removes one or more occurrences of a recurring event.
+    ///
+    /// Removing the following occurrences from the first one moves the whole event to the trash,
+    /// otherwise the events replacing the removed occurrences are moved there.
+    ///
+    /// # Arguments
+    /// * event - The recurring event.
+    /// * occurrence - The original start of the occurrence being removed.
+    /// * scope - Which occurrences to remove.
+    /// * now - The current time, in UTC.
+    /// * conn - The connection to the database.
+    ///
+    /// # Return
+    /// The recurring event, after the occurrences have been removed.
+    /// It fails with `NotFound` if the event doesn't have an occurrence at that time.
+    pub fn delete_occurrences(
+        event: &Event,
+        occurrence: NaiveDateTime,
+        scope: RecurrenceScope,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        let rule = event.occurrence_rule(occurrence, scope)?;
+        conn.transaction(|| match scope {
+            RecurrenceScope::This => {
+                let mut exdates = event.exdates.clone();
+                exdates.push(occurrence);
+                Self::set_recurrence(event.uuid, event.rrule.clone(), exdates, conn)
+            }
+            RecurrenceScope::Following if occurrence == event.start_at => {
+                Self::delete_event(event.uuid, now, conn)
+            }
+            RecurrenceScope::Following => Self::end_before(event, &rule, occurrence, now, conn),
+        })
+    }
+
+    /// The event's rule, if it has an occurrence starting at the time.
+    /// A single occurrence can't be changed once it has been removed or replaced,
+    /// but the occurrences following it can.
+    fn occurrence_rule(
+        &self,
+        occurrence: NaiveDateTime,
+        scope: RecurrenceScope,
+    ) -> QueryResult<RecurrenceRule> {
+        self.recurrence_rule()
+            .filter(|rule| {
+                self.occurrence_starts(rule, occurrence + Duration::seconds(1))
+                    .contains(&occurrence)
+            })
+            .filter(|_| scope == RecurrenceScope::Following || !self.exdates.contains(&occurrence))
+            .ok_or(DieselError::NotFound)
+    }
+
+    /// Stops a recurring event from occurring at or after the occurrence.
+    fn end_before(
+        event: &Event,
+        rule: &RecurrenceRule,
+        occurrence: NaiveDateTime,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        let rule = RecurrenceRule {
+            count: None,
+            until: Some(occurrence - Duration::seconds(1)),
+            ..rule.clone()
+        };
+        let exdates = event
+            .exdates
+            .iter()
+            .cloned()
+            .filter(|exdate| *exdate < occurrence)
+            .collect();
+        Self::delete_replacements(event.uuid, occurrence, now, conn)?;
+        Self::set_recurrence(event.uuid, Some(rule.to_string()), exdates, conn)
+    }
+
+    /// Moves the events replacing occurrences of a recurring event to the trash,
+    /// from the occurrence onwards.
+    fn delete_replacements(
+        uuid: Uuid,
+        since: NaiveDateTime,
+        deleted_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<usize> {
+        events::table
+            .filter(events::recurrence_parent_uuid.eq(uuid))
+            .filter(events::recurrence_id.ge(since))
+            .filter(events::deleted_at.is_null())
+            .apply(diesel::update)
+            .set(events::deleted_at.eq(deleted_at))
+            .execute(conn)
     }
 
-    /// Gets a single event.
+    /// Sets the rule and removed occurrences of a recurring event.
+    fn set_recurrence(
+        uuid: Uuid,
+        rrule: Option<String>,
+        exdates: Vec<NaiveDateTime>,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        diesel::update(events::table.find(uuid))
+            .set((events::rrule.eq(rrule), events::exdates.eq(exdates)))
+            .get_result(conn)
+    }
+
+    /// Gets a single event, unless it is in the trash.
     pub fn get_event(uuid: Uuid, conn: &PgConnection) -> QueryResult<Event> {
-        crate::util::get_row(schema::events::table, uuid, conn)
+        Self::all()
+            .filter(events::uuid.eq(uuid))
+            .filter(events::deleted_at.is_null())
+            .first(conn)
+    }
+
+    /// Gets a single event, if it is shown in the user's calendar.
+    pub fn get_calendar_event(
+        uuid: Uuid,
+        user_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        Self::calendar_events(user_uuid)
+            .filter(events::uuid.eq(uuid))
+            .first(conn)
+    }
+
+    /// Gets a single event that is in the trash.
+    pub fn get_trashed_event(uuid: Uuid, conn: &PgConnection) -> QueryResult<Event> {
+        Self::all()
+            .filter(events::uuid.eq(uuid))
+            .filter(events::deleted_at.is_not_null())
+            .first(conn)
+    }
+
+    /// Gets the events in the trash of the calendars a user can change, most recently deleted first.
+    /// These are the user's own calendar, and those shared with them with write permission.
+    ///
+    /// Events replacing occurrences of a recurring event aren't listed
+    /// when they were moved to the trash along with it.
+    pub fn trash(user_uuid: Uuid, conn: &PgConnection) -> QueryResult<Vec<Event>> {
+        let writable_owners = calendar_shares::table
+            .select(calendar_shares::owner_uuid)
+            .filter(calendar_shares::member_uuid.eq(user_uuid))
+            .filter(calendar_shares::permission.eq(SharePermission::Write));
+        let trashed = Self::all()
+            .filter(
+                events::user_uuid
+                    .eq(user_uuid)
+                    .or(events::user_uuid.eq_any(writable_owners)),
+            )
+            .filter(events::deleted_at.is_not_null())
+            .order((events::deleted_at.desc(), events::start_at))
+            .load::<Event>(conn)?;
+        let deletions: HashMap<Uuid, Option<NaiveDateTime>> = trashed
+            .iter()
+            .map(|event| (event.uuid, event.deleted_at))
+            .collect();
+        trashed
+            .into_iter()
+            .filter(|event| match event.recurrence_parent_uuid {
+                Some(parent) => deletions.get(&parent) != Some(&event.deleted_at),
+                None => true,
+            })
+            .collect::<Vec<_>>()
+            .apply(Ok)
     }
 
     /// Creates a new event.
@@ -244,13 +1178,149 @@ impl Event {
         crate::util::create_row(schema::events::table, new_event, conn)
     }
 
-    /// Deletes an event.
-    pub fn delete_event(uuid: Uuid, conn: &PgConnection) -> QueryResult<Event> {
-        crate::util::delete_row(schema::events::table, uuid, conn)
+    /// Moves an event to the trash, along with the events replacing its occurrences.
+    /// It fails with `NotFound` if the event doesn't exist, or is already in the trash.
+    ///
+    /// # Arguments
+    /// * uuid - The uuid of the event.
+    /// * deleted_at - The current time, in UTC.
+    /// * conn - The connection to the database.
+    pub fn delete_event(
+        uuid: Uuid,
+        deleted_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        Self::trash_event(uuid, None, deleted_at, conn)
+    }
+
+    /// Moves an event to the trash like `delete_event`,
+    /// unless it has changed since it was last updated at `updated_at`.
+    /// It fails with `NotFound` if the event has changed, or doesn't exist.
+    pub fn delete_unmodified_event(
+        uuid: Uuid,
+        updated_at: NaiveDateTime,
+        deleted_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        Self::trash_event(uuid, Some(updated_at), deleted_at, conn)
+    }
+
+    /// Moves an event and its replacements to the trash,
+    /// optionally only if it was last updated at a given time.
+    fn trash_event(
+        uuid: Uuid,
+        updated_at: Option<NaiveDateTime>,
+        deleted_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        conn.transaction(|| {
+            // Locking the event keeps it from changing between the check and the update.
+            let event: Event = Self::all()
+                .filter(events::uuid.eq(uuid))
+                .filter(events::deleted_at.is_null())
+                .for_update()
+                .first(conn)?;
+            if updated_at.map_or(false, |updated_at| updated_at != event.updated_at) {
+                return Err(DieselError::NotFound);
+            }
+            // Replacements share the event's deletion time, so they are restored with it.
+            events::table
+                .filter(events::recurrence_parent_uuid.eq(uuid))
+                .filter(events::deleted_at.is_null())
+                .apply(diesel::update)
+                .set(events::deleted_at.eq(deleted_at))
+                .execute(conn)?;
+            diesel::update(events::table.find(uuid))
+                .set(events::deleted_at.eq(deleted_at))
+                .get_result(conn)
+        })
+    }
+
+    /// Gets the events that keep a trashed event from being restored, because they aren't in the trash
+    /// and have the same external id, or replace the same occurrence.
+    pub fn restore_clashes(event: &Event, conn: &PgConnection) -> QueryResult<Vec<Event>> {
+        let mut clashes = vec![];
+        if let Some(ref external_id) = event.external_id {
+            let same_id: Vec<Event> = Self::user_events(event.user_uuid)
+                .filter(events::external_id.eq(external_id))
+                .load(conn)?;
+            clashes.extend(same_id);
+        }
+        if let Some(parent_uuid) = event.recurrence_parent_uuid {
+            let same_occurrence: Vec<Event> = Self::user_events(event.user_uuid)
+                .filter(events::recurrence_parent_uuid.eq(parent_uuid))
+                .filter(events::recurrence_id.eq(event.recurrence_id))
+                .load(conn)?;
+            clashes.extend(same_occurrence);
+        }
+        Ok(clashes)
+    }
+
+    /// Takes an event out of the trash, along with the replacements that were moved there with it.
+    /// It fails with `NotFound` if the event isn't in the trash.
+    pub fn restore_event(uuid: Uuid, conn: &PgConnection) -> QueryResult<Event> {
+        conn.transaction(|| {
+            let event = Self::get_trashed_event(uuid, conn)?;
+            events::table
+                .filter(events::recurrence_parent_uuid.eq(uuid))
+                .filter(events::deleted_at.eq(event.deleted_at))
+                .apply(diesel::update)
+                .set(events::deleted_at.eq(None::<NaiveDateTime>))
+                .execute(conn)?;
+            diesel::update(events::table.find(uuid))
+                .set(events::deleted_at.eq(None::<NaiveDateTime>))
+                .get_result(conn)
+        })
+    }
+
+    /// Permanently deletes the events that were moved to the trash before a time.
+    ///
+    /// # Return
+    /// The number of events that were deleted.
+    pub fn purge_trash(before: NaiveDateTime, conn: &PgConnection) -> QueryResult<usize> {
+        events::table
+            .filter(events::deleted_at.lt(before))
+            .apply(diesel::delete)
+            .execute(conn)
     }
 
     /// Alters an event.
     pub fn change_event(changeset: EventChangeset, conn: &PgConnection) -> QueryResult<Event> {
-        crate::util::update_row(schema::events::table, changeset, conn)
+        let target = schema::events::table.find(changeset.uuid);
+        crate::util::update_row(target, changeset, conn)
+    }
+
+    /// Alters an event, unless it has changed since it was last updated at `updated_at`.
+    /// It fails with `NotFound` if the event has changed, or doesn't exist.
+    pub fn change_unmodified_event(
+        changeset: EventChangeset,
+        updated_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        let target = schema::events::table
+            .find(changeset.uuid)
+            .filter(events::updated_at.eq(updated_at));
+        crate::util::update_row(target, changeset, conn)
+    }
+
+    /// Alters the fields of an event that are present in the patch.
+    pub fn patch_event(uuid: Uuid, patch: EventPatch, conn: &PgConnection) -> QueryResult<Event> {
+        let target = schema::events::table.find(uuid);
+        crate::util::update_row(target, patch, conn)
+    }
+
+    /// Alters the fields of an event that are present in the patch,
+    /// unless it has changed since it was last updated at `updated_at`.
+    /// It fails with `NotFound` if the event has changed, or doesn't exist.
+    pub fn patch_unmodified_event(
+        uuid: Uuid,
+        patch: EventPatch,
+        updated_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Event> {
+        let target = schema::events::table
+            .find(uuid)
+            .filter(events::updated_at.eq(updated_at));
+        crate::util::update_row(target, patch, conn)
     }
 }
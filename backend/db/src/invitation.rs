@@ -0,0 +1,168 @@
+//! All database queries directly related to inviting users to events are contained within this module.
+use crate::{
+    event::{self, Event},
+    schema::{event_invitations, events},
+};
+use chrono::NaiveDateTime;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::{Pg, PgConnection},
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    serialize::{self, Output, ToSql},
+    sql_types::Varchar,
+    ExpressionMethods, Identifiable, Insertable, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use uuid::Uuid;
+
+/// Whether an invited user is going to an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Varchar"]
+pub enum InvitationStatus {
+    /// The invitee hasn't responded yet.
+    Pending,
+    /// The invitee is going.
+    Accepted,
+    /// The invitee isn't going. The event is no longer shown in their calendar.
+    Declined,
+}
+
+impl InvitationStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            InvitationStatus::Pending => "pending",
+            InvitationStatus::Accepted => "accepted",
+            InvitationStatus::Declined => "declined",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for InvitationStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Varchar, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Varchar, Pg> for InvitationStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"pending" => Ok(InvitationStatus::Pending),
+            b"accepted" => Ok(InvitationStatus::Accepted),
+            b"declined" => Ok(InvitationStatus::Declined),
+            _ => Err("Unrecognized invitation status".into()),
+        }
+    }
+}
+
+/// Struct to represent a row in the `event_invitations` table.
+/// An invitation shows a single event in the invitee's calendar, until they decline it.
+#[derive(
+    Clone, Copy, Debug, Identifiable, Queryable, Associations, Serialize, Deserialize, PartialEq,
+)]
+#[primary_key(event_uuid, invitee_uuid)]
+#[belongs_to(Event, foreign_key = "event_uuid")]
+#[table_name = "event_invitations"]
+pub struct EventInvitation {
+    /// The event the user is invited to.
+    pub event_uuid: Uuid,
+    /// The invited user.
+    pub invitee_uuid: Uuid,
+    /// Whether the invitee is going.
+    pub status: InvitationStatus,
+    /// When the user was invited.
+    pub invited_at: NaiveDateTime,
+    /// When the invitee last responded, if they have.
+    pub responded_at: Option<NaiveDateTime>,
+}
+
+/// A struct used for creating new rows in the `event_invitations` table.
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "event_invitations"]
+struct NewEventInvitation {
+    event_uuid: Uuid,
+    invitee_uuid: Uuid,
+    invited_at: NaiveDateTime,
+}
+
+impl EventInvitation {
+    /// Invites a user to an event.
+    ///
+    /// # Arguments
+    /// * event_uuid - The event the user is invited to.
+    /// * invitee_uuid - The user being invited.
+    /// * invited_at - The current time.
+    /// * conn - The connection to the database.
+    pub fn invite(
+        event_uuid: Uuid,
+        invitee_uuid: Uuid,
+        invited_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<EventInvitation> {
+        let invitation = NewEventInvitation {
+            event_uuid,
+            invitee_uuid,
+            invited_at,
+        };
+        crate::util::create_row(event_invitations::table, invitation, conn)
+    }
+
+    /// Withdraws a user's invitation to an event.
+    pub fn uninvite(
+        event_uuid: Uuid,
+        invitee_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<EventInvitation> {
+        diesel::delete(event_invitations::table.find((event_uuid, invitee_uuid))).get_result(conn)
+    }
+
+    /// Gets everyone invited to an event, in the order they were invited.
+    pub fn invitations_for_event(
+        event_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<EventInvitation>> {
+        event_invitations::table
+            .filter(event_invitations::event_uuid.eq(event_uuid))
+            .order_by(event_invitations::invited_at)
+            .load(conn)
+    }
+
+    /// Gets the events a user is invited to, soonest first, except those in the trash.
+    pub fn invitations_for_user(
+        invitee_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<(EventInvitation, Event)>> {
+        event_invitations::table
+            .inner_join(events::table)
+            .filter(event_invitations::invitee_uuid.eq(invitee_uuid))
+            .filter(events::deleted_at.is_null())
+            .order_by(events::start_at)
+            .select((event_invitations::all_columns, event::ALL_COLUMNS))
+            .load(conn)
+    }
+
+    /// Records whether the invitee is going to the event.
+    ///
+    /// # Arguments
+    /// * event_uuid - The event the user is invited to.
+    /// * invitee_uuid - The invited user.
+    /// * status - Whether they are going.
+    /// * responded_at - The current time.
+    /// * conn - The connection to the database.
+    pub fn respond(
+        event_uuid: Uuid,
+        invitee_uuid: Uuid,
+        status: InvitationStatus,
+        responded_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<EventInvitation> {
+        diesel::update(event_invitations::table.find((event_uuid, invitee_uuid)))
+            .set((
+                event_invitations::status.eq(status),
+                event_invitations::responded_at.eq(responded_at),
+            ))
+            .get_result(conn)
+    }
+}
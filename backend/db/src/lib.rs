@@ -15,8 +15,19 @@
 extern crate diesel;
 
 pub mod adaptive_health;
+pub mod calendar_feed;
+pub mod calendar_share;
+pub mod cash;
 pub mod event;
+pub mod invitation;
+pub mod order;
+pub mod recurrence;
+pub mod reminder;
 mod schema;
 pub mod stock;
+pub mod stock_listing;
+pub mod stock_price;
+pub mod time_zone;
 pub mod user;
 mod util;
+pub mod watchlist;
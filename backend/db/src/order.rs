@@ -0,0 +1,247 @@
+//! All database queries directly related to limit and stop orders are contained within this module.
+use crate::{
+    schema::{self, orders, stocks},
+    stock::{Stock, StockTransaction},
+    user::User,
+    util,
+};
+use chrono::NaiveDateTime;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::{Pg, PgConnection},
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    serialize::{self, Output, ToSql},
+    sql_types::Varchar,
+    BoolExpressionMethods, ExpressionMethods, Identifiable, Insertable, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use uuid::Uuid;
+
+/// The conditions under which an order is filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Varchar"]
+pub enum OrderKind {
+    /// Buy at or below, or sell at or above the trigger price.
+    Limit,
+    /// Buy at or above, or sell at or below the trigger price.
+    /// A stop sell is also known as a stop-loss.
+    Stop,
+}
+
+impl OrderKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            OrderKind::Limit => "limit",
+            OrderKind::Stop => "stop",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for OrderKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Varchar, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Varchar, Pg> for OrderKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"limit" => Ok(OrderKind::Limit),
+            b"stop" => Ok(OrderKind::Stop),
+            _ => Err("Unrecognized order kind".into()),
+        }
+    }
+}
+
+/// Where an order is in its lifecycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Varchar"]
+pub enum OrderStatus {
+    /// The order is waiting for its trigger price.
+    Open,
+    /// The order was triggered and recorded as a stock transaction.
+    Filled,
+    /// The user cancelled the order.
+    Cancelled,
+    /// The order was triggered, but the user could not afford it, or no longer had the stocks to sell.
+    Rejected,
+}
+
+impl OrderStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            OrderStatus::Open => "open",
+            OrderStatus::Filled => "filled",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl ToSql<Varchar, Pg> for OrderStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Varchar, Pg>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Varchar, Pg> for OrderStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"open" => Ok(OrderStatus::Open),
+            b"filled" => Ok(OrderStatus::Filled),
+            b"cancelled" => Ok(OrderStatus::Cancelled),
+            b"rejected" => Ok(OrderStatus::Rejected),
+            _ => Err("Unrecognized order status".into()),
+        }
+    }
+}
+
+/// Struct to represent a row in the `orders` table.
+/// An order is a request to trade a stock once its price reaches a given value.
+#[derive(
+    Clone, Copy, Debug, Identifiable, Queryable, Associations, Serialize, Deserialize, PartialEq,
+)]
+#[primary_key(uuid)]
+#[belongs_to(Stock, foreign_key = "stock_uuid")]
+#[belongs_to(User, foreign_key = "user_uuid")]
+#[belongs_to(StockTransaction, foreign_key = "stock_transaction_uuid")]
+#[table_name = "orders"]
+pub struct Order {
+    /// Unique identifier.
+    pub uuid: Uuid,
+    /// Uuid of the user the order belongs to.
+    pub user_uuid: Uuid,
+    /// The uuid of the stock being ordered.
+    pub stock_uuid: Uuid,
+    /// The conditions under which the order is filled.
+    pub kind: OrderKind,
+    /// The quantity of stocks to exchange.
+    /// Negative indicates a sale, positive indicates a purchase.
+    pub quantity: i32,
+    /// The price that triggers the order.
+    pub trigger_price: f64,
+    /// Where the order is in its lifecycle.
+    pub status: OrderStatus,
+    /// The transaction that filled the order.
+    pub stock_transaction_uuid: Option<Uuid>,
+    /// When the order was placed.
+    pub created_at: NaiveDateTime,
+    /// When the order was filled, cancelled, or rejected.
+    pub closed_at: Option<NaiveDateTime>,
+}
+
+/// A struct used for creating new rows in the `orders` table.
+#[derive(Insertable, Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
+#[table_name = "orders"]
+pub struct NewOrder {
+    /// Uuid of the user the order belongs to.
+    pub user_uuid: Uuid,
+    /// The uuid of the stock being ordered.
+    pub stock_uuid: Uuid,
+    /// The conditions under which the order is filled.
+    pub kind: OrderKind,
+    /// The quantity of stocks to exchange.
+    pub quantity: i32,
+    /// The price that triggers the order.
+    pub trigger_price: f64,
+    /// When the order was placed.
+    pub created_at: NaiveDateTime,
+}
+
+/// A response struct containing an order, as well as the stock it is for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserOrderResponse {
+    /// The order.
+    pub order: Order,
+    /// The stock being ordered.
+    pub stock: Stock,
+}
+
+impl From<(Order, Stock)> for UserOrderResponse {
+    fn from((order, stock): (Order, Stock)) -> Self {
+        UserOrderResponse { order, stock }
+    }
+}
+
+impl Order {
+    /// Creates an order.
+    pub fn create_order(new_order: NewOrder, conn: &PgConnection) -> QueryResult<Order> {
+        util::create_row(schema::orders::table, new_order, conn)
+    }
+
+    /// Gets a single order along with its stock.
+    pub fn get_order(uuid: Uuid, conn: &PgConnection) -> QueryResult<UserOrderResponse> {
+        orders::table
+            .find(uuid)
+            .inner_join(stocks::table)
+            .first::<(Order, Stock)>(conn)
+            .map(UserOrderResponse::from)
+    }
+
+    /// Gets an order, locking its row until the end of the current transaction.
+    pub fn get_order_for_update(uuid: Uuid, conn: &PgConnection) -> QueryResult<Order> {
+        orders::table.find(uuid).for_update().first(conn)
+    }
+
+    /// Gets the open orders belonging to a user.
+    pub fn get_open_orders_for_user(
+        user_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<UserOrderResponse>> {
+        orders::table
+            .filter(
+                orders::user_uuid
+                    .eq(user_uuid)
+                    .and(orders::status.eq(OrderStatus::Open)),
+            )
+            .inner_join(stocks::table)
+            .order(orders::created_at.asc())
+            .load::<(Order, Stock)>(conn)
+            .map(|orders| orders.into_iter().map(UserOrderResponse::from).collect())
+    }
+
+    /// Gets every open order across all users.
+    pub fn get_open_orders(conn: &PgConnection) -> QueryResult<Vec<UserOrderResponse>> {
+        orders::table
+            .filter(orders::status.eq(OrderStatus::Open))
+            .inner_join(stocks::table)
+            .order(orders::created_at.asc())
+            .load::<(Order, Stock)>(conn)
+            .map(|orders| orders.into_iter().map(UserOrderResponse::from).collect())
+    }
+
+    /// Closes an order with the given status.
+    ///
+    /// Only open orders can be closed, so if the order was already closed,
+    /// a `NotFound` error is returned.
+    ///
+    /// # Arguments
+    /// * uuid - The order to close.
+    /// * status - The status to close the order with.
+    /// * stock_transaction_uuid - The transaction that filled the order, if it was filled.
+    /// * conn - The connection to the database.
+    pub fn close_order(
+        uuid: Uuid,
+        status: OrderStatus,
+        stock_transaction_uuid: Option<Uuid>,
+        conn: &PgConnection,
+    ) -> QueryResult<Order> {
+        diesel::update(
+            orders::table.filter(
+                orders::uuid
+                    .eq(uuid)
+                    .and(orders::status.eq(OrderStatus::Open)),
+            ),
+        )
+            .set((
+                orders::status.eq(status),
+                orders::stock_transaction_uuid.eq(stock_transaction_uuid),
+                orders::closed_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result(conn)
+    }
+}
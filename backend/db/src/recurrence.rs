@@ -0,0 +1,679 @@
+//! Recurrence rules for events that repeat, as described by [RFC 5545](https://tools.ietf.org/html/rfc5545#section-3.3.10).
+//!
+//! Only the parts of the RRULE grammar that calendars commonly produce are understood:
+//! `FREQ` (`DAILY`, `WEEKLY`, `MONTHLY` and `YEARLY`), `INTERVAL`, `COUNT`, `UNTIL`, `BYDAY`, `BYMONTHDAY`, `BYMONTH` and `WKST`.
+//! Rules using anything else are rejected, rather than being expanded incorrectly.
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    error::Error as StdError,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// Rules that stop producing occurrences, like one for the 30th of February, are abandoned after this many periods.
+const MAX_EMPTY_PERIODS: u32 = 1_000;
+
+/// The most periods there can be between repetitions.
+const MAX_INTERVAL: u32 = 1_000;
+
+/// The format of `UNTIL` when it is a UTC date-time.
+const UNTIL_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// How often an event repeats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    /// Every day.
+    Daily,
+    /// Every week.
+    Weekly,
+    /// Every month.
+    Monthly,
+    /// Every year.
+    Yearly,
+}
+
+/// A day of the week, optionally limited to its nth occurrence within a month.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeekdayNum {
+    /// Which occurrence of the day within the month. Negative values count back from the end of the month.
+    pub ordinal: Option<i32>,
+    /// The day of the week.
+    pub weekday: Weekday,
+}
+
+/// A parsed `RRULE`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    /// How often the event repeats.
+    pub frequency: Frequency,
+    /// The number of periods between each repetition.
+    pub interval: u32,
+    /// The number of occurrences, including the first one.
+    pub count: Option<u32>,
+    /// The last moment an occurrence may start at.
+    pub until: Option<NaiveDateTime>,
+    /// Limits occurrences to these days of the week.
+    pub by_day: Vec<WeekdayNum>,
+    /// Limits occurrences to these days of the month. Negative values count back from the end of the month.
+    pub by_month_day: Vec<i32>,
+    /// Limits occurrences to these months, numbered from 1.
+    pub by_month: Vec<u32>,
+    /// The day weeks start on.
+    pub week_start: Weekday,
+}
+
+/// The reason a recurrence rule could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecurrenceError(String);
+
+impl Display for RecurrenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid recurrence rule: {}", self.0)
+    }
+}
+
+impl StdError for RecurrenceError {}
+
+impl RecurrenceError {
+    fn new<T: Into<String>>(reason: T) -> Self {
+        RecurrenceError(reason.into())
+    }
+}
+
+fn parse_weekday(code: &str) -> Result<Weekday, RecurrenceError> {
+    match code {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(RecurrenceError::new(format!(
+            "'{}' is not a day of the week",
+            code
+        ))),
+    }
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, RecurrenceError> {
+    value
+        .parse()
+        .map_err(|_| RecurrenceError::new(format!("{} must be a number", name)))
+}
+
+fn parse_list<T, F>(value: &str, parse: F) -> Result<Vec<T>, RecurrenceError>
+where
+    F: Fn(&str) -> Result<T, RecurrenceError>,
+{
+    value.split(',').map(parse).collect()
+}
+
+/// `UNTIL` is either a date, which includes the whole day, or a date-time.
+fn parse_until(value: &str) -> Result<NaiveDateTime, RecurrenceError> {
+    let invalid = || RecurrenceError::new("UNTIL must be a date or a date-time");
+    if value.len() == 8 {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|date| date.and_hms(23, 59, 59))
+            .map_err(|_| invalid())
+    } else {
+        NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+            .map_err(|_| invalid())
+    }
+}
+
+fn parse_weekday_num(value: &str) -> Result<WeekdayNum, RecurrenceError> {
+    // Day codes are ASCII, so anything else can't be split into a week and a day.
+    if value.len() < 2 || !value.is_ascii() {
+        return Err(RecurrenceError::new(format!(
+            "'{}' is not a day of the week",
+            value
+        )));
+    }
+    let (ordinal, code) = value.split_at(value.len() - 2);
+    let ordinal = if ordinal.is_empty() {
+        None
+    } else {
+        let ordinal: i32 = parse_number("The week in BYDAY", ordinal.trim_start_matches('+'))?;
+        if ordinal == 0 || ordinal.abs() > 5 {
+            return Err(RecurrenceError::new(
+                "The week in BYDAY must be between 1 and 5",
+            ));
+        }
+        Some(ordinal)
+    };
+    Ok(WeekdayNum {
+        ordinal,
+        weekday: parse_weekday(code)?,
+    })
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = RecurrenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_uppercase();
+        let s = s.trim_start_matches("RRULE:");
+
+        let mut frequency = None;
+        let mut rule = RecurrenceRule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: vec![],
+            by_month_day: vec![],
+            by_month: vec![],
+            week_start: Weekday::Mon,
+        };
+        let mut seen: Vec<&str> = vec![];
+
+        for part in s.split(';').filter(|part| !part.is_empty()) {
+            let mut pair = part.splitn(2, '=');
+            let name = pair.next().unwrap_or("");
+            let value = pair
+                .next()
+                .ok_or_else(|| RecurrenceError::new(format!("{} has no value", name)))?;
+            if seen.contains(&name) {
+                return Err(RecurrenceError::new(format!(
+                    "{} appears more than once",
+                    name
+                )));
+            }
+            seen.push(name);
+
+            match name {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => {
+                            return Err(RecurrenceError::new(format!(
+                                "FREQ={} is not supported",
+                                value
+                            )))
+                        }
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = parse_number("INTERVAL", value)?;
+                    if rule.interval == 0 || rule.interval > MAX_INTERVAL {
+                        return Err(RecurrenceError::new(format!(
+                            "INTERVAL must be between 1 and {}",
+                            MAX_INTERVAL
+                        )));
+                    }
+                }
+                "COUNT" => {
+                    let count = parse_number("COUNT", value)?;
+                    if count == 0 {
+                        return Err(RecurrenceError::new("COUNT must be at least 1"));
+                    }
+                    rule.count = Some(count);
+                }
+                "UNTIL" => rule.until = Some(parse_until(value)?),
+                "BYDAY" => rule.by_day = parse_list(value, parse_weekday_num)?,
+                "BYMONTHDAY" => {
+                    rule.by_month_day = parse_list(value, |day| {
+                        let day: i32 = parse_number("BYMONTHDAY", day)?;
+                        if day == 0 || day.abs() > 31 {
+                            Err(RecurrenceError::new("BYMONTHDAY must be between 1 and 31"))
+                        } else {
+                            Ok(day)
+                        }
+                    })?
+                }
+                "BYMONTH" => {
+                    rule.by_month = parse_list(value, |month| {
+                        let month: u32 = parse_number("BYMONTH", month)?;
+                        if month == 0 || month > 12 {
+                            Err(RecurrenceError::new("BYMONTH must be between 1 and 12"))
+                        } else {
+                            Ok(month)
+                        }
+                    })?
+                }
+                "WKST" => rule.week_start = parse_weekday(value)?,
+                _ => return Err(RecurrenceError::new(format!("{} is not supported", name))),
+            }
+        }
+
+        rule.frequency = frequency.ok_or_else(|| RecurrenceError::new("FREQ is required"))?;
+        if rule.count.is_some() && rule.until.is_some() {
+            return Err(RecurrenceError::new(
+                "COUNT and UNTIL can't be used together",
+            ));
+        }
+        let has_ordinals = rule.by_day.iter().any(|day| day.ordinal.is_some());
+        match rule.frequency {
+            Frequency::Daily | Frequency::Weekly if has_ordinals => {
+                return Err(RecurrenceError::new(
+                    "BYDAY can only pick a week of the month in MONTHLY and YEARLY rules",
+                ))
+            }
+            Frequency::Weekly if !rule.by_month_day.is_empty() => {
+                return Err(RecurrenceError::new(
+                    "BYMONTHDAY can't be used in WEEKLY rules",
+                ))
+            }
+            Frequency::Yearly if !rule.by_day.is_empty() && rule.by_month.is_empty() => {
+                return Err(RecurrenceError::new("BYDAY in YEARLY rules needs BYMONTH"))
+            }
+            _ => {}
+        }
+        Ok(rule)
+    }
+}
+
+impl Display for RecurrenceRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format(UNTIL_FORMAT))?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|day| match day.ordinal {
+                    Some(ordinal) => format!("{}{}", ordinal, weekday_code(day.weekday)),
+                    None => weekday_code(day.weekday).to_string(),
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(i32::to_string).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+        if !self.by_month.is_empty() {
+            let months: Vec<String> = self.by_month.iter().map(u32::to_string).collect();
+            write!(f, ";BYMONTH={}", months.join(","))?;
+        }
+        if self.week_start != Weekday::Mon {
+            write!(f, ";WKST={}", weekday_code(self.week_start))?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of days in the month, or 0 if the month is outside the dates that can be represented.
+fn days_in_month(year: i32, month: u32) -> u32 {
+    (28..=31)
+        .rev()
+        .find(|day| NaiveDate::from_ymd_opt(year, month, *day).is_some())
+        .unwrap_or(0)
+}
+
+impl RecurrenceRule {
+    /// The start of every occurrence, in order, beginning with `dtstart`.
+    ///
+    /// The iterator is endless if the rule has neither `COUNT` nor `UNTIL`.
+    pub fn occurrences(&self, dtstart: NaiveDateTime) -> Occurrences<'_> {
+        Occurrences {
+            rule: self,
+            dtstart,
+            period: 0,
+            pending: VecDeque::new(),
+            emitted: 0,
+            empty_periods: 0,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Whether the rule produces an occurrence starting at exactly this time.
+    pub fn is_occurrence(&self, dtstart: NaiveDateTime, time: NaiveDateTime) -> bool {
+        self.occurrences(dtstart)
+            .take_while(|occurrence| *occurrence <= time)
+            .any(|occurrence| occurrence == time)
+    }
+
+    /// The number of occurrences that start before this time.
+    pub fn count_before(&self, dtstart: NaiveDateTime, time: NaiveDateTime) -> usize {
+        self.occurrences(dtstart)
+            .take_while(|occurrence| *occurrence < time)
+            .count()
+    }
+
+    /// Whether the day is allowed by the `BYMONTH`, `BYMONTHDAY` and `BYDAY` parts.
+    fn matches(&self, date: NaiveDate) -> bool {
+        let days_in_month = days_in_month(date.year(), date.month()) as i32;
+        let day = date.day() as i32;
+
+        let month_matches = self.by_month.is_empty() || self.by_month.contains(&date.month());
+        let month_day_matches = self.by_month_day.is_empty()
+            || self
+                .by_month_day
+                .iter()
+                .any(|&d| d == day || d == day - days_in_month - 1);
+        let weekday_matches = self.by_day.is_empty()
+            || self.by_day.iter().any(|d| {
+                d.weekday == date.weekday()
+                    && match d.ordinal {
+                        None => true,
+                        Some(n) if n > 0 => (day - 1) / 7 + 1 == n,
+                        Some(n) => -((days_in_month - day) / 7 + 1) == n,
+                    }
+            });
+        month_matches && month_day_matches && weekday_matches
+    }
+
+    /// The days in a month that the rule selects.
+    fn month_dates(&self, year: i32, month: u32, dtstart: NaiveDate) -> Vec<NaiveDate> {
+        if self.by_day.is_empty() && self.by_month_day.is_empty() {
+            // Repeat on the same day of the month as the first occurrence, skipping months that are too short.
+            return NaiveDate::from_ymd_opt(year, month, dtstart.day())
+                .into_iter()
+                .collect();
+        }
+        (1..=days_in_month(year, month))
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+            .filter(|date| self.matches(*date))
+            .collect()
+    }
+
+    /// The days within the nth period after the one containing `dtstart`.
+    ///
+    /// # Return
+    /// `None` if the period is past the dates that can be represented, so the rule can't go on.
+    fn period_dates(&self, dtstart: NaiveDate, period: i64) -> Option<Vec<NaiveDate>> {
+        let step = period * i64::from(self.interval);
+        let dates = match self.frequency {
+            Frequency::Daily => {
+                let date = dtstart.checked_add_signed(Duration::days(step))?;
+                if self.matches(date) {
+                    vec![date]
+                } else {
+                    vec![]
+                }
+            }
+            Frequency::Weekly => {
+                let days_into_week = (7 + dtstart.weekday().num_days_from_monday()
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let week = dtstart
+                    .checked_sub_signed(Duration::days(i64::from(days_into_week)))?
+                    .checked_add_signed(Duration::weeks(step))?;
+                (0..7)
+                    .filter_map(|day| week.checked_add_signed(Duration::days(day)))
+                    .filter(|date| {
+                        let weekday_matches = if self.by_day.is_empty() {
+                            date.weekday() == dtstart.weekday()
+                        } else {
+                            self.by_day.iter().any(|d| d.weekday == date.weekday())
+                        };
+                        weekday_matches
+                            && (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = i64::from(dtstart.year()) * 12 + i64::from(dtstart.month0()) + step;
+                let (year, month) = (i32::try_from(months / 12).ok()?, (months % 12) as u32 + 1);
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                if self.by_month.is_empty() || self.by_month.contains(&month) {
+                    self.month_dates(year, month, dtstart)
+                } else {
+                    vec![]
+                }
+            }
+            Frequency::Yearly => {
+                let year = i32::try_from(i64::from(dtstart.year()) + step).ok()?;
+                NaiveDate::from_ymd_opt(year, 12, 31)?;
+                if self.by_month.is_empty() {
+                    self.month_dates(year, dtstart.month(), dtstart)
+                } else {
+                    let mut months = self.by_month.clone();
+                    months.sort();
+                    months
+                        .into_iter()
+                        .flat_map(|month| self.month_dates(year, month, dtstart))
+                        .collect()
+                }
+            }
+        };
+        Some(dates)
+    }
+}
+
+/// An iterator over the start of each occurrence of a recurrence rule.
+#[derive(Debug)]
+pub struct Occurrences<'a> {
+    rule: &'a RecurrenceRule,
+    dtstart: NaiveDateTime,
+    period: i64,
+    pending: VecDeque<NaiveDateTime>,
+    emitted: u32,
+    empty_periods: u32,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> Iterator for Occurrences<'a> {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<NaiveDateTime> {
+        loop {
+            if self.done {
+                return None;
+            }
+            if let Some(next) = self.pending.pop_front() {
+                let past_until = self.rule.until.map_or(false, |until| next > until);
+                let past_count = self.rule.count.map_or(false, |count| self.emitted >= count);
+                if past_until || past_count {
+                    self.done = true;
+                    return None;
+                }
+                self.emitted += 1;
+                return Some(next);
+            }
+
+            if !self.started {
+                // The first occurrence is always the event's own start.
+                self.started = true;
+                self.pending.push_back(self.dtstart);
+            }
+            let time = self.dtstart.time();
+            let dtstart = self.dtstart;
+            let dates = match self.rule.period_dates(dtstart.date(), self.period) {
+                Some(dates) => dates,
+                None => {
+                    // The rule has run past the last date that can be represented, so it ends.
+                    self.done = self.pending.is_empty();
+                    continue;
+                }
+            };
+            let candidates: Vec<NaiveDateTime> = dates
+                .into_iter()
+                .map(|date| date.and_time(time))
+                .filter(|candidate| *candidate > dtstart)
+                .collect();
+            self.period += 1;
+
+            if candidates.is_empty() {
+                self.empty_periods += 1;
+                if self.empty_periods > MAX_EMPTY_PERIODS && self.pending.is_empty() {
+                    self.done = true;
+                }
+            } else {
+                self.empty_periods = 0;
+                self.pending.extend(candidates);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(year, month, day).and_hms(9, 0, 0)
+    }
+
+    fn expand(rule: &str, dtstart: NaiveDateTime, limit: usize) -> Vec<NaiveDateTime> {
+        rule.parse::<RecurrenceRule>()
+            .unwrap()
+            .occurrences(dtstart)
+            .take(limit)
+            .collect()
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        let rule: RecurrenceRule = "rrule:freq=weekly;interval=2;byday=mo,we;until=20190601"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=WEEKLY;INTERVAL=2;UNTIL=20190601T235959Z;BYDAY=MO,WE"
+        );
+        assert_eq!(rule.to_string().parse::<RecurrenceRule>(), Ok(rule));
+    }
+
+    #[test]
+    fn unsupported_rules_are_rejected() {
+        assert!("INTERVAL=2".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=HOURLY".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=DAILY;BYHOUR=9".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=DAILY;COUNT=2;UNTIL=20190601"
+            .parse::<RecurrenceRule>()
+            .is_err());
+        assert!("FREQ=WEEKLY;BYDAY=1MO".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=DAILY;FREQ=WEEKLY".parse::<RecurrenceRule>().is_err());
+    }
+
+    #[test]
+    fn daily_with_count() {
+        let occurrences = expand("FREQ=DAILY;INTERVAL=2;COUNT=3", at(2019, 4, 30), 10);
+        assert_eq!(
+            occurrences,
+            vec![at(2019, 4, 30), at(2019, 5, 2), at(2019, 5, 4)]
+        );
+    }
+
+    #[test]
+    fn weekly_on_several_days() {
+        // The 1st of May 2019 is a Wednesday.
+        let occurrences = expand("FREQ=WEEKLY;BYDAY=MO,WE,FR", at(2019, 5, 1), 5);
+        assert_eq!(
+            occurrences,
+            vec![
+                at(2019, 5, 1),
+                at(2019, 5, 3),
+                at(2019, 5, 6),
+                at(2019, 5, 8),
+                at(2019, 5, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn until_is_inclusive() {
+        let occurrences = expand("FREQ=WEEKLY;UNTIL=20190515T090000Z", at(2019, 5, 1), 10);
+        assert_eq!(
+            occurrences,
+            vec![at(2019, 5, 1), at(2019, 5, 8), at(2019, 5, 15)]
+        );
+    }
+
+    #[test]
+    fn monthly_skips_short_months() {
+        let occurrences = expand("FREQ=MONTHLY;COUNT=3", at(2019, 1, 31), 10);
+        assert_eq!(
+            occurrences,
+            vec![at(2019, 1, 31), at(2019, 3, 31), at(2019, 5, 31)]
+        );
+    }
+
+    #[test]
+    fn monthly_on_the_last_friday() {
+        let occurrences = expand("FREQ=MONTHLY;BYDAY=-1FR", at(2019, 4, 26), 3);
+        assert_eq!(
+            occurrences,
+            vec![at(2019, 4, 26), at(2019, 5, 31), at(2019, 6, 28)]
+        );
+    }
+
+    #[test]
+    fn yearly_on_the_fourth_thursday_of_november() {
+        let occurrences = expand("FREQ=YEARLY;BYMONTH=11;BYDAY=4TH", at(2019, 11, 28), 2);
+        assert_eq!(occurrences, vec![at(2019, 11, 28), at(2020, 11, 26)]);
+    }
+
+    #[test]
+    fn impossible_rules_end() {
+        let occurrences = expand("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30", at(2019, 1, 1), 10);
+        assert_eq!(occurrences, vec![at(2019, 1, 1)]);
+    }
+
+    #[test]
+    fn malformed_days_are_rejected() {
+        assert!("FREQ=MONTHLY;BYDAY=ém".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=MONTHLY;BYDAY=1É".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=é".parse::<RecurrenceRule>().is_err());
+    }
+
+    #[test]
+    fn intervals_are_limited() {
+        assert!("FREQ=DAILY;INTERVAL=1000".parse::<RecurrenceRule>().is_ok());
+        assert!("FREQ=DAILY;INTERVAL=1001"
+            .parse::<RecurrenceRule>()
+            .is_err());
+        assert!("FREQ=DAILY;INTERVAL=100000000"
+            .parse::<RecurrenceRule>()
+            .is_err());
+    }
+
+    #[test]
+    fn rules_end_with_the_last_representable_date() {
+        for rule in &[
+            "FREQ=DAILY;INTERVAL=1000",
+            "FREQ=WEEKLY;INTERVAL=1000",
+            "FREQ=MONTHLY;INTERVAL=1000",
+            "FREQ=YEARLY;INTERVAL=1000;BYMONTHDAY=1",
+            "FREQ=YEARLY;INTERVAL=1000;BYMONTH=12;BYDAY=-1FR",
+        ] {
+            let occurrences = expand(rule, at(2019, 1, 1), 1_000_000);
+            assert!(occurrences.len() < 1_000_000, "{} should end", rule);
+            assert!(occurrences.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    fn occurrences_can_be_counted_and_checked() {
+        let rule: RecurrenceRule = "FREQ=DAILY".parse().unwrap();
+        assert!(rule.is_occurrence(at(2019, 5, 1), at(2019, 5, 3)));
+        assert!(!rule.is_occurrence(at(2019, 5, 1), at(2019, 5, 3) + Duration::hours(1)));
+        assert_eq!(rule.count_before(at(2019, 5, 1), at(2019, 5, 3)), 2);
+    }
+}
@@ -0,0 +1,309 @@
+//! All database queries directly related to event reminders are contained within this module.
+use crate::{
+    event::{self, Event},
+    schema::{event_reminders, events, reminder_outbox, users},
+    time_zone,
+};
+use chrono::{Duration, NaiveDateTime};
+use chrono_tz::Tz;
+use diesel::{
+    pg::PgConnection,
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    BoolExpressionMethods, Connection, ExpressionMethods, Identifiable, Insertable, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Struct to represent a row in the `event_reminders` table.
+/// A reminder fires a set time before each occurrence of an event.
+#[derive(
+    Clone, Copy, Debug, Identifiable, Queryable, Associations, Serialize, Deserialize, PartialEq,
+)]
+#[primary_key(uuid)]
+#[belongs_to(Event, foreign_key = "event_uuid")]
+#[table_name = "event_reminders"]
+pub struct EventReminder {
+    /// Unique identifier.
+    pub uuid: Uuid,
+    /// The event to be reminded of.
+    pub event_uuid: Uuid,
+    /// How many minutes before the event starts the reminder fires.
+    pub minutes_before: i32,
+}
+
+/// A struct used for creating new rows in the `event_reminders` table.
+#[derive(Clone, Copy, Debug, Insertable, Serialize, Deserialize)]
+#[table_name = "event_reminders"]
+pub struct NewEventReminder {
+    /// The event to be reminded of.
+    pub event_uuid: Uuid,
+    /// How many minutes before the event starts the reminder fires.
+    pub minutes_before: i32,
+}
+
+/// Struct to represent a row in the `reminder_outbox` table.
+/// It is a reminder that has come due for one occurrence of an event.
+#[derive(Clone, Debug, Identifiable, Queryable, Serialize, Deserialize, PartialEq)]
+#[primary_key(uuid)]
+#[table_name = "reminder_outbox"]
+pub struct FiredReminder {
+    /// Unique identifier.
+    pub uuid: Uuid,
+    /// The reminder that fired.
+    pub reminder_uuid: Uuid,
+    /// The user being reminded.
+    pub user_uuid: Uuid,
+    /// The event being reminded of.
+    /// For occurrences that were moved, this is the event replacing the occurrence.
+    pub event_uuid: Uuid,
+    /// The title of the event when the reminder fired.
+    pub title: String,
+    /// When the occurrence starts, in UTC.
+    pub starts_at: NaiveDateTime,
+    /// When the reminder fired.
+    pub fired_at: NaiveDateTime,
+    /// When a notifier delivered the reminder, if it has been.
+    pub delivered_at: Option<NaiveDateTime>,
+    /// How many times delivering the reminder has failed.
+    pub attempts: i32,
+    /// Why delivering the reminder last failed.
+    pub last_error: Option<String>,
+    /// When the user dismissed the reminder, if they have.
+    pub dismissed_at: Option<NaiveDateTime>,
+}
+
+/// A struct used for creating new rows in the `reminder_outbox` table.
+#[derive(Clone, Debug, Insertable)]
+#[table_name = "reminder_outbox"]
+struct NewFiredReminder {
+    reminder_uuid: Uuid,
+    user_uuid: Uuid,
+    event_uuid: Uuid,
+    title: String,
+    starts_at: NaiveDateTime,
+    fired_at: NaiveDateTime,
+}
+
+impl EventReminder {
+    /// Adds a reminder to an event.
+    pub fn create_reminder(
+        new_reminder: NewEventReminder,
+        conn: &PgConnection,
+    ) -> QueryResult<EventReminder> {
+        crate::util::create_row(event_reminders::table, new_reminder, conn)
+    }
+
+    /// Gets a single reminder.
+    pub fn get_reminder(uuid: Uuid, conn: &PgConnection) -> QueryResult<EventReminder> {
+        crate::util::get_row(event_reminders::table, uuid, conn)
+    }
+
+    /// Gets the reminders of an event, soonest before the event first.
+    pub fn reminders_for_event(
+        event_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<EventReminder>> {
+        event_reminders::table
+            .filter(event_reminders::event_uuid.eq(event_uuid))
+            .order_by(event_reminders::minutes_before)
+            .load(conn)
+    }
+
+    /// Removes a reminder from its event.
+    pub fn delete_reminder(uuid: Uuid, conn: &PgConnection) -> QueryResult<EventReminder> {
+        crate::util::delete_row(event_reminders::table, uuid, conn)
+    }
+}
+
+impl FiredReminder {
+    /// Adds every reminder that has come due to the outbox.
+    ///
+    /// A reminder is due for an occurrence once it is `minutes_before` the occurrence's start.
+    /// Reminders that came due more than `late_limit` ago are skipped,
+    /// so a scheduler that was stopped doesn't remind users of events long past.
+    /// Occurrences of a recurring event that were moved are reminded of using the recurring event's
+    /// reminders. Each reminder is only added once for each occurrence.
+    ///
+    /// # Arguments
+    /// * now - The current time, in UTC.
+    /// * late_limit - How late a reminder can fire.
+    /// * conn - The connection to the database.
+    ///
+    /// # Return
+    /// The number of reminders added to the outbox.
+    pub fn fire_due_reminders(
+        now: NaiveDateTime,
+        late_limit: Duration,
+        conn: &PgConnection,
+    ) -> QueryResult<usize> {
+        // An event that started over a day ago can't be due, even if its times are local.
+        let earliest_start = now - late_limit - Duration::days(1);
+        let reminders: Vec<(EventReminder, Event, String)> = event_reminders::table
+            .inner_join(events::table.inner_join(users::table))
+            .filter(events::deleted_at.is_null())
+            .filter(
+                events::rrule
+                    .is_not_null()
+                    .or(events::start_at.gt(earliest_start)),
+            )
+            .select((
+                event_reminders::all_columns,
+                event::ALL_COLUMNS,
+                users::time_zone,
+            ))
+            .load(conn)?;
+
+        let recurring: Vec<Uuid> = reminders
+            .iter()
+            .filter(|(_, event, _)| event.rrule.is_some())
+            .map(|(_, event, _)| event.uuid)
+            .collect();
+        let mut replacements: HashMap<Uuid, Vec<Event>> = HashMap::new();
+        for replacement in events::table
+            .select(event::ALL_COLUMNS)
+            .filter(events::recurrence_parent_uuid.eq_any(recurring))
+            .filter(events::start_at.gt(earliest_start))
+            .filter(events::deleted_at.is_null())
+            .load::<Event>(conn)?
+        {
+            if let Some(parent) = replacement.recurrence_parent_uuid {
+                replacements.entry(parent).or_default().push(replacement);
+            }
+        }
+
+        let mut fired = vec![];
+        for (reminder, event, user_zone) in &reminders {
+            let zone = time_zone::parse_zone(user_zone).unwrap_or(Tz::UTC);
+            let events =
+                std::iter::once(event).chain(replacements.get(&event.uuid).into_iter().flatten());
+            for event in events {
+                for starts_at in due_starts(event, reminder.minutes_before, zone, now, late_limit) {
+                    fired.push(NewFiredReminder {
+                        reminder_uuid: reminder.uuid,
+                        user_uuid: event.user_uuid,
+                        event_uuid: event.uuid,
+                        title: event.title.clone(),
+                        starts_at,
+                        fired_at: now,
+                    });
+                }
+            }
+        }
+
+        diesel::insert_into(reminder_outbox::table)
+            .values(&fired)
+            .on_conflict_do_nothing()
+            .execute(conn)
+    }
+
+    /// Gets the fired reminders that haven't been delivered yet, oldest first.
+    ///
+    /// # Arguments
+    /// * max_attempts - Reminders that have failed to be delivered this many times are left out.
+    /// * conn - The connection to the database.
+    pub fn undelivered(max_attempts: i32, conn: &PgConnection) -> QueryResult<Vec<FiredReminder>> {
+        reminder_outbox::table
+            .filter(reminder_outbox::delivered_at.is_null())
+            .filter(reminder_outbox::attempts.lt(max_attempts))
+            .order_by(reminder_outbox::fired_at)
+            .load(conn)
+    }
+
+    /// Records that the reminder was delivered.
+    pub fn mark_delivered(
+        uuid: Uuid,
+        delivered_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<FiredReminder> {
+        diesel::update(reminder_outbox::table.find(uuid))
+            .set((
+                reminder_outbox::delivered_at.eq(delivered_at),
+                reminder_outbox::attempts.eq(reminder_outbox::attempts + 1),
+                reminder_outbox::last_error.eq(None::<String>),
+            ))
+            .get_result(conn)
+    }
+
+    /// Records that delivering the reminder failed, so it can be tried again.
+    pub fn mark_failed(
+        uuid: Uuid,
+        error: String,
+        conn: &PgConnection,
+    ) -> QueryResult<FiredReminder> {
+        diesel::update(reminder_outbox::table.find(uuid))
+            .set((
+                reminder_outbox::attempts.eq(reminder_outbox::attempts + 1),
+                reminder_outbox::last_error.eq(error),
+            ))
+            .get_result(conn)
+    }
+
+    /// Gets the user's fired reminders that they haven't dismissed, most recent first.
+    pub fn fired_for_user(user_uuid: Uuid, conn: &PgConnection) -> QueryResult<Vec<FiredReminder>> {
+        reminder_outbox::table
+            .filter(reminder_outbox::user_uuid.eq(user_uuid))
+            .filter(reminder_outbox::dismissed_at.is_null())
+            .order_by(reminder_outbox::fired_at.desc())
+            .then_order_by(reminder_outbox::starts_at)
+            .load(conn)
+    }
+
+    /// Dismisses one of the user's fired reminders, so it isn't shown to them again.
+    ///
+    /// # Return
+    /// The dismissed reminder. It fails with `NotFound` if the reminder isn't the user's.
+    pub fn dismiss(
+        uuid: Uuid,
+        user_uuid: Uuid,
+        dismissed_at: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<FiredReminder> {
+        conn.transaction(|| {
+            let target = reminder_outbox::table
+                .filter(reminder_outbox::uuid.eq(uuid))
+                .filter(reminder_outbox::user_uuid.eq(user_uuid));
+            let reminder: FiredReminder = target.first(conn)?;
+            if reminder.dismissed_at.is_some() {
+                return Ok(reminder);
+            }
+            diesel::update(target)
+                .set(reminder_outbox::dismissed_at.eq(dismissed_at))
+                .get_result(conn)
+        })
+    }
+}
+
+/// The starts, in UTC, of the occurrences of an event that a reminder has come due for.
+///
+/// # Arguments
+/// * event - The event, or the event replacing one of its occurrences.
+/// * minutes_before - How long before each occurrence the reminder fires.
+/// * zone - The user's time zone, which the dates of all-day events are in.
+/// * now - The current time, in UTC.
+/// * late_limit - How late a reminder can fire.
+fn due_starts(
+    event: &Event,
+    minutes_before: i32,
+    zone: Tz,
+    now: NaiveDateTime,
+    late_limit: Duration,
+) -> Vec<NaiveDateTime> {
+    let before = Duration::minutes(i64::from(minutes_before));
+    let (after, until) = (now - late_limit + before, now + before);
+    // The times of all-day events are local, so a day either side covers every zone.
+    let day = Duration::days(1);
+    event
+        .occurrences_between(after - day, until + day)
+        .into_iter()
+        .map(|occurrence| {
+            if occurrence.all_day {
+                time_zone::to_utc(occurrence.start_at, zone)
+            } else {
+                occurrence.start_at
+            }
+        })
+        .filter(|starts_at| *starts_at > after && *starts_at <= until)
+        .collect()
+}
@@ -8,6 +8,51 @@ table! {
     }
 }
 
+table! {
+    calendar_feeds (user_uuid) {
+        user_uuid -> Uuid,
+        token -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    calendar_shares (owner_uuid, member_uuid) {
+        owner_uuid -> Uuid,
+        member_uuid -> Uuid,
+        permission -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    cash_transactions (uuid) {
+        uuid -> Uuid,
+        user_uuid -> Uuid,
+        stock_transaction_uuid -> Nullable<Uuid>,
+        amount -> Float8,
+        record_time -> Timestamp,
+    }
+}
+
+table! {
+    event_invitations (event_uuid, invitee_uuid) {
+        event_uuid -> Uuid,
+        invitee_uuid -> Uuid,
+        status -> Varchar,
+        invited_at -> Timestamp,
+        responded_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    event_reminders (uuid) {
+        uuid -> Uuid,
+        event_uuid -> Uuid,
+        minutes_before -> Int4,
+    }
+}
+
 table! {
     events (uuid) {
         uuid -> Uuid,
@@ -16,6 +61,63 @@ table! {
         text -> Varchar,
         start_at -> Timestamp,
         stop_at -> Timestamp,
+        rrule -> Nullable<Varchar>,
+        exdates -> Array<Timestamp>,
+        recurrence_parent_uuid -> Nullable<Uuid>,
+        recurrence_id -> Nullable<Timestamp>,
+        external_id -> Nullable<Varchar>,
+        time_zone -> Nullable<Varchar>,
+        all_day -> Bool,
+        updated_at -> Timestamp,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    orders (uuid) {
+        uuid -> Uuid,
+        user_uuid -> Uuid,
+        stock_uuid -> Uuid,
+        kind -> Varchar,
+        quantity -> Int4,
+        trigger_price -> Float8,
+        status -> Varchar,
+        stock_transaction_uuid -> Nullable<Uuid>,
+        created_at -> Timestamp,
+        closed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    reminder_outbox (uuid) {
+        uuid -> Uuid,
+        reminder_uuid -> Uuid,
+        user_uuid -> Uuid,
+        event_uuid -> Uuid,
+        title -> Varchar,
+        starts_at -> Timestamp,
+        fired_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        attempts -> Int4,
+        last_error -> Nullable<Varchar>,
+        dismissed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    stock_prices (uuid) {
+        uuid -> Uuid,
+        symbol -> Varchar,
+        price -> Float8,
+        record_time -> Timestamp,
+    }
+}
+
+table! {
+    stock_listings (symbol) {
+        symbol -> Varchar,
+        name -> Varchar,
+        exchange -> Nullable<Varchar>,
     }
 }
 
@@ -24,6 +126,7 @@ table! {
         uuid -> Uuid,
         symbol -> Varchar,
         stock_name -> Varchar,
+        exchange -> Nullable<Varchar>,
     }
 }
 
@@ -43,11 +146,63 @@ table! {
         uuid -> Uuid,
         twitter_user_id -> Varchar,
         zip_code -> Nullable<Varchar>,
+        display_name -> Nullable<Varchar>,
+        leaderboard_opt_in -> Bool,
+        time_zone -> Varchar,
+    }
+}
+
+table! {
+    watchlist_entries (watchlist_uuid, stock_uuid) {
+        watchlist_uuid -> Uuid,
+        stock_uuid -> Uuid,
+        added_at -> Timestamp,
+    }
+}
+
+table! {
+    watchlists (uuid) {
+        uuid -> Uuid,
+        user_uuid -> Uuid,
+        name -> Varchar,
+        created_at -> Timestamp,
     }
 }
 
+joinable!(cash_transactions -> stock_transactions (stock_transaction_uuid));
+joinable!(calendar_feeds -> users (user_uuid));
+joinable!(cash_transactions -> users (user_uuid));
+joinable!(event_invitations -> events (event_uuid));
+joinable!(event_invitations -> users (invitee_uuid));
+joinable!(event_reminders -> events (event_uuid));
 joinable!(events -> users (user_uuid));
+joinable!(orders -> stock_transactions (stock_transaction_uuid));
+joinable!(orders -> stocks (stock_uuid));
+joinable!(orders -> users (user_uuid));
+joinable!(reminder_outbox -> event_reminders (reminder_uuid));
+joinable!(reminder_outbox -> events (event_uuid));
+joinable!(reminder_outbox -> users (user_uuid));
 joinable!(stock_transactions -> stocks (stock_uuid));
 joinable!(stock_transactions -> users (user_uuid));
+joinable!(watchlist_entries -> stocks (stock_uuid));
+joinable!(watchlist_entries -> watchlists (watchlist_uuid));
+joinable!(watchlists -> users (user_uuid));
 
-allow_tables_to_appear_in_same_query!(adaptive_health, events, stocks, stock_transactions, users,);
+allow_tables_to_appear_in_same_query!(
+    adaptive_health,
+    calendar_feeds,
+    calendar_shares,
+    cash_transactions,
+    event_invitations,
+    event_reminders,
+    events,
+    orders,
+    reminder_outbox,
+    stock_listings,
+    stock_prices,
+    stocks,
+    stock_transactions,
+    users,
+    watchlist_entries,
+    watchlists,
+);
@@ -39,6 +39,8 @@ pub struct Stock {
     pub symbol: String,
     /// The name of the company the stock is associated with.
     pub stock_name: String,
+    /// The exchange the stock is listed on, if it is known.
+    pub exchange: Option<String>,
 }
 
 /// A struct used for creating new rows in the `stocks` table.
@@ -49,6 +51,8 @@ pub struct NewStock {
     pub symbol: String,
     /// The name of the company the stock is associated with.
     pub stock_name: String,
+    /// The exchange the stock is listed on, if it is known.
+    pub exchange: Option<String>,
 }
 
 /// Struct to represent a row in the `stock_transactions` table.
@@ -101,12 +105,103 @@ pub struct NewStockTransaction {
     pub record_time: NaiveDateTime,
 }
 
+/// Which way stocks were exchanged in a transaction.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionSide {
+    /// Purchases, which have a positive quantity.
+    Buy,
+    /// Sales, which have a negative quantity.
+    Sell,
+}
+
+/// The order transactions are returned in, by the time they were recorded.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Oldest first.
+    Asc,
+    /// Newest first.
+    Desc,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        SortOrder::Desc
+    }
+}
+
+/// The position of the last transaction on a page, so the next page can start after it.
+///
+/// Transactions are ordered by their record time, then by their uuid, so that transactions recorded at the same time
+/// are neither skipped nor repeated between pages.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TransactionCursor {
+    /// The time the last transaction was recorded.
+    pub record_time: NaiveDateTime,
+    /// The uuid of the last transaction.
+    pub uuid: Uuid,
+}
+
+/// Criteria for selecting a page of a user's transactions.
+#[derive(Clone, Debug, Default)]
+pub struct TransactionFilter {
+    /// Only include transactions recorded at or after this time.
+    pub start: Option<NaiveDateTime>,
+    /// Only include transactions recorded at or before this time.
+    pub stop: Option<NaiveDateTime>,
+    /// Only include purchases, or only include sales.
+    pub side: Option<TransactionSide>,
+    /// Only include transactions for the stock with this symbol.
+    pub symbol: Option<String>,
+    /// The order to return transactions in.
+    pub sort: SortOrder,
+    /// Only include transactions that come after this one in the sort order.
+    pub after: Option<TransactionCursor>,
+}
+
+/// A transaction, along with the stock that was exchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionWithStock {
+    /// The transaction.
+    pub transaction: StockTransaction,
+    /// The stock that was exchanged.
+    pub stock: Stock,
+}
+
 impl Stock {
     /// Creates a stock.
     pub fn create_stock(new_stock: NewStock, conn: &PgConnection) -> QueryResult<Stock> {
         util::create_row(schema::stocks::table, new_stock, conn)
     }
 
+    /// Gets the stock with the symbol of the provided stock, creating it if it doesn't exist yet.
+    ///
+    /// Because symbols are unique, concurrent calls for the same symbol will all resolve to the same row.
+    pub fn get_or_create_stock(new_stock: NewStock, conn: &PgConnection) -> QueryResult<Stock> {
+        diesel::insert_into(schema::stocks::table)
+            .values(&new_stock)
+            .on_conflict(schema::stocks::symbol)
+            .do_nothing()
+            .execute(conn)?;
+        Self::get_stock_by_symbol(new_stock.symbol, conn)
+    }
+
+    /// Sets the company name and exchange of a stock.
+    pub fn update_listing(
+        stock_uuid: Uuid,
+        stock_name: String,
+        exchange: Option<String>,
+        conn: &PgConnection,
+    ) -> QueryResult<Stock> {
+        diesel::update(schema::stocks::table.find(stock_uuid))
+            .set((
+                schema::stocks::stock_name.eq(stock_name),
+                schema::stocks::exchange.eq(exchange),
+            ))
+            .get_result(conn)
+    }
+
     /// Creates a transaction.
     pub fn create_transaction(
         new_transaction: NewStockTransaction,
@@ -177,6 +272,85 @@ impl Stock {
             )
             .load(conn)
     }
+
+    /// Gets every transaction a user has made, oldest first, along with the stocks that were exchanged.
+    pub fn export_transactions(
+        user_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<TransactionWithStock>> {
+        stock_transactions::table
+            .inner_join(stocks::table)
+            .filter(stock_transactions::user_uuid.eq(user_uuid))
+            .order((
+                stock_transactions::record_time.asc(),
+                stock_transactions::uuid.asc(),
+            ))
+            .load::<(StockTransaction, Stock)>(conn)?
+            .into_iter()
+            .map(|(transaction, stock)| TransactionWithStock { transaction, stock })
+            .collect::<Vec<_>>()
+            .apply(Ok)
+    }
+
+    /// Gets a page of a user's transactions across all of their stocks.
+    ///
+    /// # Arguments
+    /// * user_uuid - The user the transactions belong to.
+    /// * filter - Which transactions to include, and the order to get them in.
+    /// * limit - The most transactions to get.
+    pub fn get_user_transactions(
+        user_uuid: Uuid,
+        filter: &TransactionFilter,
+        limit: i64,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<TransactionWithStock>> {
+        let (record_time, uuid) = (stock_transactions::record_time, stock_transactions::uuid);
+        let mut query = stock_transactions::table
+            .inner_join(stocks::table)
+            .filter(stock_transactions::user_uuid.eq(user_uuid))
+            .into_boxed();
+
+        if let Some(start) = filter.start {
+            query = query.filter(record_time.ge(start));
+        }
+        if let Some(stop) = filter.stop {
+            query = query.filter(record_time.le(stop));
+        }
+        match filter.side {
+            Some(TransactionSide::Buy) => query = query.filter(stock_transactions::quantity.gt(0)),
+            Some(TransactionSide::Sell) => query = query.filter(stock_transactions::quantity.lt(0)),
+            None => {}
+        }
+        if let Some(ref symbol) = filter.symbol {
+            query = query.filter(stocks::symbol.eq(symbol.clone()));
+        }
+
+        query = match (filter.sort, filter.after) {
+            (SortOrder::Asc, Some(after)) => query.filter(
+                record_time
+                    .gt(after.record_time)
+                    .or(record_time.eq(after.record_time).and(uuid.gt(after.uuid))),
+            ),
+            (SortOrder::Desc, Some(after)) => query.filter(
+                record_time
+                    .lt(after.record_time)
+                    .or(record_time.eq(after.record_time).and(uuid.lt(after.uuid))),
+            ),
+            (_, None) => query,
+        };
+        query = match filter.sort {
+            SortOrder::Asc => query.order((record_time.asc(), uuid.asc())),
+            SortOrder::Desc => query.order((record_time.desc(), uuid.desc())),
+        };
+
+        query
+            .limit(limit)
+            .load::<(StockTransaction, Stock)>(conn)?
+            .into_iter()
+            .map(|(transaction, stock)| TransactionWithStock { transaction, stock })
+            .collect::<Vec<_>>()
+            .apply(Ok)
+    }
 }
 
 /// A response struct containing a stock row, as well as its associated transactions.
@@ -0,0 +1,74 @@
+//! All database queries directly related to the cached list of symbols the quote provider supports are contained within this module.
+use crate::schema::stock_listings;
+use diesel::{
+    dsl::sql,
+    pg::PgConnection,
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    sql_types::Integer,
+    BoolExpressionMethods, Connection, Insertable, PgTextExpressionMethods, Queryable,
+};
+use serde::{Deserialize, Serialize};
+
+/// Struct to represent a row in the `stock_listings` table.
+/// A listing is a symbol that can be traded, along with the company it belongs to.
+#[derive(Clone, Debug, Queryable, Insertable, Serialize, Deserialize, PartialEq)]
+#[table_name = "stock_listings"]
+pub struct StockListing {
+    /// The upper-case ticker symbol.
+    pub symbol: String,
+    /// The name of the company.
+    pub name: String,
+    /// The exchange the stock is listed on, if it is known.
+    pub exchange: Option<String>,
+}
+
+impl StockListing {
+    /// Replaces every cached listing with the provided ones.
+    pub fn replace_listings(listings: &[StockListing], conn: &PgConnection) -> QueryResult<usize> {
+        conn.transaction(|| {
+            diesel::delete(stock_listings::table).execute(conn)?;
+            // Postgres limits the number of parameters in one statement, so insert the listings in chunks.
+            listings.chunks(5_000).try_fold(0, |count, chunk| {
+                diesel::insert_into(stock_listings::table)
+                    .values(chunk)
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .map(|inserted| count + inserted)
+            })
+        })
+    }
+
+    /// Gets the listing for a symbol.
+    pub fn get_listing(symbol: &str, conn: &PgConnection) -> QueryResult<StockListing> {
+        stock_listings::table.find(symbol).first(conn)
+    }
+
+    /// Finds listings whose symbol starts with, or whose company name contains, the query.
+    ///
+    /// # Arguments
+    /// * query - The text to search for. It is matched case-insensitively.
+    /// * limit - The maximum number of listings to return.
+    /// * conn - The connection to the database.
+    pub fn search(query: &str, limit: i64, conn: &PgConnection) -> QueryResult<Vec<StockListing>> {
+        let escaped = escape_like(query);
+        stock_listings::table
+            .filter(
+                stock_listings::symbol
+                    .ilike(format!("{}%", escaped))
+                    .or(stock_listings::name.ilike(format!("%{}%", escaped))),
+            )
+            // Shorter symbols are more likely to be the ones being searched for.
+            .order((sql::<Integer>("length(symbol)"), stock_listings::symbol))
+            .limit(limit)
+            .load(conn)
+    }
+}
+
+/// Escapes the characters that have special meaning in a LIKE pattern.
+fn escape_like(query: &str) -> String {
+    query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -0,0 +1,95 @@
+//! All database queries directly related to recorded stock prices are contained within this module.
+use crate::schema::stock_prices;
+use chrono::NaiveDateTime;
+use diesel::{
+    pg::PgConnection,
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    BoolExpressionMethods, ExpressionMethods, Identifiable, Insertable, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Struct to represent a row in the `stock_prices` table.
+/// A stock price is a quote that was fetched from the quote provider at a point in time.
+#[derive(Clone, Debug, Identifiable, Queryable, Serialize, Deserialize, PartialEq)]
+#[primary_key(uuid)]
+#[table_name = "stock_prices"]
+pub struct StockPrice {
+    /// Unique identifier.
+    pub uuid: Uuid,
+    /// The upper-case ticker symbol of the stock.
+    pub symbol: String,
+    /// The price of the stock.
+    pub price: f64,
+    /// The time at which the price was fetched.
+    pub record_time: NaiveDateTime,
+}
+
+/// A struct used for creating new rows in the `stock_prices` table.
+#[derive(Insertable, Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[table_name = "stock_prices"]
+pub struct NewStockPrice {
+    /// The upper-case ticker symbol of the stock.
+    pub symbol: String,
+    /// The price of the stock.
+    pub price: f64,
+    /// The time at which the price was fetched.
+    pub record_time: NaiveDateTime,
+}
+
+impl StockPrice {
+    /// Records a batch of fetched prices.
+    pub fn create_prices(
+        new_prices: Vec<NewStockPrice>,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<StockPrice>> {
+        diesel::insert_into(stock_prices::table)
+            .values(&new_prices)
+            .get_results(conn)
+    }
+
+    /// Gets the recorded prices for a stock within a time range, oldest first.
+    ///
+    /// # Arguments
+    /// * symbol - The upper-case ticker symbol of the stock.
+    /// * start - The beginning of the range.
+    /// * stop - The end of the range.
+    /// * conn - The connection to the database.
+    pub fn get_price_history(
+        symbol: &str,
+        start: NaiveDateTime,
+        stop: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<StockPrice>> {
+        stock_prices::table
+            .filter(
+                stock_prices::symbol
+                    .eq(symbol)
+                    .and(stock_prices::record_time.between(start, stop)),
+            )
+            .order(stock_prices::record_time.asc())
+            .load(conn)
+    }
+
+    /// Gets the recorded prices for several stocks since a point in time, oldest first.
+    ///
+    /// # Arguments
+    /// * symbols - The upper-case ticker symbols of the stocks.
+    /// * since - The beginning of the range.
+    /// * conn - The connection to the database.
+    pub fn get_prices_since(
+        symbols: &[String],
+        since: NaiveDateTime,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<StockPrice>> {
+        stock_prices::table
+            .filter(
+                stock_prices::symbol
+                    .eq_any(symbols)
+                    .and(stock_prices::record_time.ge(since)),
+            )
+            .order(stock_prices::record_time.asc())
+            .load(conn)
+    }
+}
@@ -0,0 +1,25 @@
+//! Conversions between UTC, which times are stored in, and the local times of a time zone.
+use chrono::{Duration, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+/// Looks up a time zone by its IANA name, like `America/New_York`.
+pub fn parse_zone(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+/// Converts a UTC time to the local time in the zone.
+pub fn to_local(utc: NaiveDateTime, zone: Tz) -> NaiveDateTime {
+    zone.from_utc_datetime(&utc).naive_local()
+}
+
+/// Converts a local time in the zone to UTC.
+///
+/// Times that happen twice when clocks go back use the first of the two.
+/// Times skipped when clocks go forward are moved an hour later.
+pub fn to_utc(local: NaiveDateTime, zone: Tz) -> NaiveDateTime {
+    zone.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| zone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|time| time.naive_utc())
+        .unwrap_or(local)
+}
@@ -1,8 +1,11 @@
 //! All database queries directly related to users are contained within this module.
-use crate::schema::{self, users};
+use crate::{
+    cash::{CashTransaction, NewCashTransaction},
+    schema::{self, users},
+};
 use diesel::{
-    pg::PgConnection, query_dsl::QueryDsl, result::QueryResult, ExpressionMethods, Identifiable,
-    Insertable, Queryable, RunQueryDsl,
+    pg::PgConnection, query_dsl::QueryDsl, result::QueryResult, Connection, ExpressionMethods,
+    Identifiable, Insertable, Queryable, RunQueryDsl,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -48,6 +51,12 @@ pub struct User {
     pub twitter_user_id: String,
     /// Zip code that the user resides within
     pub zip_code: Option<String>,
+    /// The name the user is shown as to other users.
+    pub display_name: Option<String>,
+    /// Whether the user wants to be ranked on the market leaderboard.
+    pub leaderboard_opt_in: bool,
+    /// The IANA name of the time zone the user's calendar is shown in.
+    pub time_zone: String,
 }
 
 /// Struct used to create new users.
@@ -59,9 +68,16 @@ pub struct NewUser {
 }
 
 impl User {
-    /// Creates a user
+    /// Creates a user, and opens their cash account with the starting balance.
     pub fn create_user(user: NewUser, conn: &PgConnection) -> QueryResult<User> {
-        crate::util::create_row(schema::users::table, user, conn)
+        conn.transaction(|| {
+            let user: User = crate::util::create_row(schema::users::table, user, conn)?;
+            CashTransaction::create_cash_transaction(
+                NewCashTransaction::opening_deposit(user.uuid),
+                conn,
+            )?;
+            Ok(user)
+        })
     }
 
     /// Gets a user using its unique identifier.
@@ -69,6 +85,13 @@ impl User {
         crate::util::get_row(schema::users::table, uuid, conn)
     }
 
+    /// Gets a user, locking their row until the end of the current transaction.
+    ///
+    /// This is used to serialize operations that read and then modify a user's holdings.
+    pub fn get_user_for_update(uuid: Uuid, conn: &PgConnection) -> QueryResult<User> {
+        users::table.find(uuid).for_update().first::<User>(conn)
+    }
+
     /// Gets a user by the client id.
     pub fn get_user_by_twitter_id(client_id: &str, conn: &PgConnection) -> QueryResult<User> {
         users::table
@@ -91,4 +114,37 @@ impl User {
             .select(users::zip_code)
             .get_result(conn)
     }
+
+    /// Sets the name the user is shown as, and whether they are ranked on the leaderboard.
+    pub fn set_leaderboard_preferences(
+        user_uuid: Uuid,
+        display_name: String,
+        opt_in: bool,
+        conn: &PgConnection,
+    ) -> QueryResult<User> {
+        diesel::update(users::table.find(user_uuid))
+            .set((
+                users::display_name.eq(display_name),
+                users::leaderboard_opt_in.eq(opt_in),
+            ))
+            .get_result(conn)
+    }
+
+    /// Sets the time zone the user's calendar is shown in.
+    pub fn set_time_zone(
+        user_uuid: Uuid,
+        time_zone: String,
+        conn: &PgConnection,
+    ) -> QueryResult<User> {
+        diesel::update(users::table.find(user_uuid))
+            .set(users::time_zone.eq(time_zone))
+            .get_result(conn)
+    }
+
+    /// Gets every user who wants to be ranked on the leaderboard.
+    pub fn get_leaderboard_users(conn: &PgConnection) -> QueryResult<Vec<User>> {
+        users::table
+            .filter(users::leaderboard_opt_in.eq(true))
+            .load::<User>(conn)
+    }
 }
@@ -11,7 +11,7 @@ use diesel::{
         AsChangeset, DeleteStatement, InsertStatement, IntoUpdateTarget, QueryFragment, QueryId,
     },
     query_dsl::{filter_dsl::FindDsl, LoadQuery, RunQueryDsl},
-    query_source::{QuerySource, Table},
+    query_source::Table,
     result::QueryResult,
     sql_types::HasSqlType,
 };
@@ -32,19 +32,19 @@ where
         .get_result::<Model>(connection)
 }
 
-/// Updates a generic row.
+/// Updates the rows of a generic target, like `table.find(uuid)`, returning the first one.
 #[inline(always)]
 pub fn update_row<Model, Chg, Tab>(
-    table: Tab,
+    target: Tab,
     changeset: Chg,
     conn: &PgConnection,
 ) -> QueryResult<Model>
 where
     Chg: AsChangeset<Target = <Tab as HasTable>::Table>,
-    Tab: QuerySource + IntoUpdateTarget,
+    Tab: IntoUpdateTarget,
     Update<Tab, Chg>: LoadQuery<PgConnection, Model>,
 {
-    diesel::update(table)
+    diesel::update(target)
         .set(changeset)
         .get_result::<Model>(conn)
 }
@@ -0,0 +1,168 @@
+//! All database queries directly related to watchlists are contained within this module.
+use crate::{
+    schema::{stocks, watchlist_entries, watchlists},
+    stock::Stock,
+    user::User,
+    util,
+};
+use chrono::NaiveDateTime;
+use diesel::{
+    pg::PgConnection,
+    query_dsl::{QueryDsl, RunQueryDsl},
+    result::QueryResult,
+    BelongingToDsl, BoolExpressionMethods, ExpressionMethods, GroupedBy, Identifiable,
+    Insertable, Queryable,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Struct to represent a row in the `watchlists` table.
+/// A watchlist is a named set of stocks a user wants to keep an eye on.
+#[derive(Clone, Debug, Identifiable, Queryable, Associations, Serialize, Deserialize, PartialEq)]
+#[primary_key(uuid)]
+#[belongs_to(User, foreign_key = "user_uuid")]
+#[table_name = "watchlists"]
+pub struct Watchlist {
+    /// Unique identifier.
+    pub uuid: Uuid,
+    /// Uuid of the user the watchlist belongs to.
+    pub user_uuid: Uuid,
+    /// The name of the watchlist, unique for each user.
+    pub name: String,
+    /// When the watchlist was created.
+    pub created_at: NaiveDateTime,
+}
+
+/// A struct used for creating new rows in the `watchlists` table.
+#[derive(Insertable, Debug, Serialize, Deserialize, Clone)]
+#[table_name = "watchlists"]
+pub struct NewWatchlist {
+    /// Uuid of the user the watchlist belongs to.
+    pub user_uuid: Uuid,
+    /// The name of the watchlist, unique for each user.
+    pub name: String,
+    /// When the watchlist was created.
+    pub created_at: NaiveDateTime,
+}
+
+/// Struct to represent a row in the `watchlist_entries` table.
+/// An entry places a stock on a watchlist.
+#[derive(
+    Clone, Copy, Debug, Identifiable, Queryable, Insertable, Associations, Serialize, Deserialize, PartialEq,
+)]
+#[primary_key(watchlist_uuid, stock_uuid)]
+#[belongs_to(Watchlist, foreign_key = "watchlist_uuid")]
+#[belongs_to(Stock, foreign_key = "stock_uuid")]
+#[table_name = "watchlist_entries"]
+pub struct WatchlistEntry {
+    /// The watchlist the stock is on.
+    pub watchlist_uuid: Uuid,
+    /// The stock being watched.
+    pub stock_uuid: Uuid,
+    /// When the stock was added to the watchlist.
+    pub added_at: NaiveDateTime,
+}
+
+/// A response struct containing a watchlist, as well as the stocks on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchlistResponse {
+    /// The watchlist.
+    pub watchlist: Watchlist,
+    /// The stocks on the watchlist, in the order they were added.
+    pub stocks: Vec<Stock>,
+}
+
+impl Watchlist {
+    /// Creates a watchlist.
+    pub fn create_watchlist(
+        new_watchlist: NewWatchlist,
+        conn: &PgConnection,
+    ) -> QueryResult<Watchlist> {
+        util::create_row(watchlists::table, new_watchlist, conn)
+    }
+
+    /// Gets a watchlist without its stocks.
+    pub fn get_watchlist(uuid: Uuid, conn: &PgConnection) -> QueryResult<Watchlist> {
+        util::get_row(watchlists::table, uuid, conn)
+    }
+
+    /// Gets a watchlist along with the stocks on it.
+    pub fn get_watchlist_with_stocks(
+        uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<WatchlistResponse> {
+        let watchlist = Self::get_watchlist(uuid, conn)?;
+        let stocks = WatchlistEntry::belonging_to(&watchlist)
+            .inner_join(stocks::table)
+            .order(watchlist_entries::added_at.asc())
+            .select(stocks::all_columns)
+            .load::<Stock>(conn)?;
+        Ok(WatchlistResponse { watchlist, stocks })
+    }
+
+    /// Gets every watchlist belonging to a user, along with the stocks on them.
+    pub fn get_watchlists_for_user(
+        user_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<Vec<WatchlistResponse>> {
+        let lists = watchlists::table
+            .filter(watchlists::user_uuid.eq(user_uuid))
+            .order(watchlists::name.asc())
+            .load::<Watchlist>(conn)?;
+        let entries = WatchlistEntry::belonging_to(&lists)
+            .inner_join(stocks::table)
+            .order(watchlist_entries::added_at.asc())
+            .load::<(WatchlistEntry, Stock)>(conn)?
+            .grouped_by(&lists);
+
+        let responses = lists
+            .into_iter()
+            .zip(entries)
+            .map(|(watchlist, entries)| WatchlistResponse {
+                watchlist,
+                stocks: entries.into_iter().map(|(_, stock)| stock).collect(),
+            })
+            .collect();
+        Ok(responses)
+    }
+
+    /// Renames a watchlist.
+    pub fn rename_watchlist(uuid: Uuid, name: String, conn: &PgConnection) -> QueryResult<Watchlist> {
+        diesel::update(watchlists::table.find(uuid))
+            .set(watchlists::name.eq(name))
+            .get_result(conn)
+    }
+
+    /// Deletes a watchlist, along with its entries.
+    pub fn delete_watchlist(uuid: Uuid, conn: &PgConnection) -> QueryResult<Watchlist> {
+        util::delete_row(watchlists::table, uuid, conn)
+    }
+
+    /// Adds a stock to a watchlist.
+    /// Adding a stock that is already on the watchlist has no effect.
+    pub fn add_stock(entry: WatchlistEntry, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::insert_into(watchlist_entries::table)
+            .values(&entry)
+            .on_conflict_do_nothing()
+            .execute(conn)
+    }
+
+    /// Removes a stock from a watchlist.
+    ///
+    /// # Return
+    /// The number of entries that were removed.
+    pub fn remove_stock(
+        watchlist_uuid: Uuid,
+        stock_uuid: Uuid,
+        conn: &PgConnection,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            watchlist_entries::table.filter(
+                watchlist_entries::watchlist_uuid
+                    .eq(watchlist_uuid)
+                    .and(watchlist_entries::stock_uuid.eq(stock_uuid)),
+            ),
+        )
+        .execute(conn)
+    }
+}
@@ -38,7 +38,6 @@ pub fn ad_api(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rej
         .and_then(err_to_rejection)
         .untuple_one() // converts `()` to ``
         .and(warp::fs::file(ad_path)) // ad_path is immutable after startup, so restrictions related to `and_then` can be worked around by just using `and`
-
 }
 
 /// Api for accessing health information related to serving the advertisement.
@@ -102,6 +101,9 @@ fn determine_and_record_ad_serving(
     if should_send_advertisement {
         Ok(())
     } else {
-        Err(Error::internal_server_error(format!(r##"The server load was determined to be too high, and therefore the "advertisement" was not sent. load: {}, servers: {}"##, load.0, available_servers.0)))
+        Err(Error::internal_server_error(format!(
+            r##"The server load was determined to be too high, and therefore the "advertisement" was not sent. load: {}, servers: {}"##,
+            load.0, available_servers.0
+        )))
     }
 }
@@ -30,6 +30,13 @@ pub static TEST_CLIENT_ID: &str = "yeet";
 /// this is because the keys are empty.
 #[cfg(test)]
 pub fn get_jwt(state: &State) -> String {
+    get_jwt_for(state, TEST_CLIENT_ID)
+}
+
+/// Gets a basic JWT for another user, who is created if they don't exist yet.
+/// This is useful for testing what users can do with each other's data.
+#[cfg(test)]
+pub fn get_jwt_for(state: &State, twitter_user_id: &str) -> String {
     use std::borrow::Cow;
     let secret: Secret = warp::test::request().filter(&state.secret()).unwrap();
     let conn: PooledConn = warp::test::request().filter(&state.db()).unwrap();
@@ -43,7 +50,7 @@ pub fn get_jwt(state: &State) -> String {
             secret: Cow::from(""),
         },
     };
-    let id = String::from(TEST_CLIENT_ID);
+    let id = String::from(twitter_user_id);
     get_or_create_user(token, id, secret, conn).expect("Should get or create user")
 }
 
@@ -82,24 +89,25 @@ pub fn auth_api(state: &State) -> impl Filter<Extract = (impl Reply,), Error = R
         .and(warp::get2())
         .and(warp::query::query())
         .and(state.twitter_consumer_token())
-        .and_then(|q_params: TwitterCallbackQueryParams, consumer_token: KeyPair| {
-            info!("{:?}", q_params);
-            // A key pair has to be constructed from the query parameters,
-            // but apparently the secret isn't needed.
-            let what_request_token = KeyPair {
-                key: q_params.oauth_token.into(),
-                secret: "".into(),
-            };
-            egg_mode::access_token(
-                consumer_token,
-                &what_request_token,
-                q_params.oauth_verifier,
-            )
-            .map_err(|e| {
-                Error::InternalServerError(Some(format!("Could not get access token: {}", e)))
-                    .reject()
-            })
-        })
+        .and_then(
+            |q_params: TwitterCallbackQueryParams, consumer_token: KeyPair| {
+                info!("{:?}", q_params);
+                // A key pair has to be constructed from the query parameters,
+                // but apparently the secret isn't needed.
+                let what_request_token = KeyPair {
+                    key: q_params.oauth_token.into(),
+                    secret: "".into(),
+                };
+                egg_mode::access_token(consumer_token, &what_request_token, q_params.oauth_verifier)
+                    .map_err(|e| {
+                        Error::InternalServerError(Some(format!(
+                            "Could not get access token: {}",
+                            e
+                        )))
+                        .reject()
+                    })
+            },
+        )
         .untuple_one()
         .and(state.secret())
         .and(state.db())
@@ -133,10 +141,7 @@ pub fn auth_api(state: &State) -> impl Filter<Extract = (impl Reply,), Error = R
         });
 
     let api_root = path!("auth");
-    api_root
-        .and(
-            link.or(callback).or(refresh)
-        )
+    api_root.and(link.or(callback).or(refresh))
 }
 
 /// The JSON returned by the /api/auth/link route.
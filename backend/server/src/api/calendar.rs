@@ -1,18 +1,40 @@
 //! Responsible for hosting everything related to calendar data.
 use crate::state::State;
-use warp::{path, Reply};
+use warp::{
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, ETAG},
+        Response,
+    },
+    path, Reply,
+};
 
 use crate::{
     error::Error,
+    ical,
     server_auth::user_filter,
-    util::{self, json_body_filter},
+    util::{self, json_body_filter, text_body_filter},
 };
 use apply::Apply;
-use chrono::{DateTime, NaiveDateTime, Utc};
-use db::event::{Event, EventChangeset, ImportExportEvent, NewEvent};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::result::{DatabaseErrorKind, Error as DieselError, OptionalExtension};
+use db::{
+    calendar_feed::CalendarFeed,
+    calendar_share::{CalendarShare, SharePermission},
+    event::{
+        Event, EventChangeset, EventPatch, ImportExportEvent, ImportMode, ImportSummary,
+        MonthIndex, NewEvent, OccurrenceChange, RecurrenceScope, Year,
+    },
+    invitation::{EventInvitation, InvitationStatus},
+    recurrence::RecurrenceRule,
+    reminder::{EventReminder, FiredReminder, NewEventReminder},
+    time_zone,
+    user::User,
+};
 use log::info;
 use pool::PooledConn;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use uuid::Uuid;
 use warp::{Filter, Rejection};
 
@@ -23,6 +45,16 @@ pub struct NewEventRequest {
     pub text: String,
     pub start_at: NaiveDateTime,
     pub stop_at: NaiveDateTime,
+    /// The RFC 5545 rule the event repeats by, if it is recurring.
+    #[serde(default)]
+    pub rrule: Option<String>,
+    /// The IANA name of the time zone the event is in. Defaults to the user's time zone.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    /// Whether the event lasts whole days.
+    /// Its times are moved to midnight, so it covers every date it touches.
+    #[serde(default)]
+    pub all_day: bool,
 }
 
 impl NewEventRequest {
@@ -37,17 +69,236 @@ impl NewEventRequest {
             text: self.text,
             start_at: self.start_at,
             stop_at: self.stop_at,
+            rrule: self.rrule,
+            exdates: vec![],
+            recurrence_parent_uuid: None,
+            recurrence_id: None,
+            external_id: None,
+            time_zone: self.time_zone,
+            all_day: self.all_day,
+        }
+    }
+}
+
+/// An event, along with when it starts and stops in its time zone.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ZonedEvent {
+    #[serde(flatten)]
+    pub event: Event,
+    /// The zone the local times are in: the event's, or the user's if the event doesn't have one.
+    /// All-day events are always shown in the user's zone, on the dates they cover.
+    pub zone: String,
+    /// When the event starts, with the zone's UTC offset at that time.
+    pub local_start_at: DateTime<FixedOffset>,
+    /// When the event stops, with the zone's UTC offset at that time.
+    pub local_stop_at: DateTime<FixedOffset>,
+}
+
+impl ZonedEvent {
+    /// Finds the local times of the event.
+    ///
+    /// # Arguments
+    /// * event - The event, with its times in UTC.
+    /// * user_zone - The zone of the user the event is shown to.
+    fn new(event: Event, user_zone: Tz) -> Self {
+        let all_day = event.all_day;
+        let zone = event.zone().filter(|_| !all_day).unwrap_or(user_zone);
+        let local = |time: &NaiveDateTime| {
+            // The times of all-day events are already local.
+            let utc = if all_day {
+                time_zone::to_utc(*time, zone)
+            } else {
+                *time
+            };
+            let time = zone.from_utc_datetime(&utc);
+            time.with_timezone(&time.offset().fix())
+        };
+        ZonedEvent {
+            zone: zone.name().to_string(),
+            local_start_at: local(&event.start_at),
+            local_stop_at: local(&event.stop_at),
+            event,
+        }
+    }
+}
+
+/// A request for changing one or more occurrences of a recurring event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OccurrenceChangeRequest {
+    /// The original start of the occurrence being changed.
+    pub occurrence: NaiveDateTime,
+    /// Whether to change only this occurrence, or the following ones as well.
+    pub scope: RecurrenceScope,
+    /// The new details.
+    #[serde(flatten)]
+    pub change: OccurrenceChange,
+}
+
+/// A request for adding a reminder to an event.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ReminderRequest {
+    /// How many minutes before each occurrence of the event the reminder fires.
+    pub minutes_before: i32,
+}
+
+/// Query parameters for creating or modifying an event.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ConflictQuery {
+    /// If true, the event isn't saved if it overlaps the events the user is busy with.
+    #[serde(default)]
+    pub reject_conflicts: bool,
+}
+
+/// A span of time the user is busy, in UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BusyInterval {
+    pub start_at: NaiveDateTime,
+    pub stop_at: NaiveDateTime,
+}
+
+/// A request for sharing the user's calendar with another user.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ShareRequest {
+    /// The user to share the calendar with.
+    pub user_uuid: Uuid,
+    /// What they can do with the calendar's events.
+    pub permission: SharePermission,
+}
+
+/// A request for inviting another user to an event.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct InviteRequest {
+    /// The user to invite.
+    pub user_uuid: Uuid,
+}
+
+/// An invitee's response to an invitation.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct InvitationReply {
+    /// Whether the invitee is going. It can't be `pending`.
+    pub status: InvitationStatus,
+}
+
+/// An event the user is invited to, along with whether they are going.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InvitedEvent {
+    pub invitation: EventInvitation,
+    pub event: ZonedEvent,
+}
+
+/// Reminders can be set at most four weeks before an event.
+const MAX_MINUTES_BEFORE: i32 = 4 * 7 * 24 * 60;
+
+/// Query parameters for searching events.
+///
+/// The bounds are read like those of /events. They can be left out, but not just one of them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchQuery {
+    /// What to search for, written like a web search.
+    pub q: String,
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default)]
+    pub stop: Option<String>,
+}
+
+/// An event that matched a search, along with its local times.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub event: ZonedEvent,
+    /// How well the event matched. Higher is better.
+    pub rank: f32,
+    /// Fragments of the title and text around the matching words, which are wrapped in `<b>` tags.
+    pub snippet: String,
+}
+
+/// The most events a search returns.
+const SEARCH_LIMIT: i64 = 50;
+
+/// The events on one day of a calendar view.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DayEvents {
+    /// The day, in the user's time zone.
+    pub date: NaiveDate,
+    /// How many events are on the day.
+    pub count: usize,
+    /// The events that take place at some point during the day, in order of when they start.
+    pub events: Vec<ZonedEvent>,
+}
+
+/// The events in a month, week or day of the user's calendar, grouped by day.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CalendarView {
+    /// The first day of the view.
+    pub first: NaiveDate,
+    /// The last day of the view, which is included.
+    pub last: NaiveDate,
+    /// How many events are in the view. Events that span several days are only counted once.
+    pub count: usize,
+    /// Every day of the view, including the ones without events.
+    pub days: Vec<DayEvents>,
+}
+
+/// Query parameters for removing one or more occurrences of a recurring event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OccurrenceQuery {
+    /// The original start of the occurrence being removed.
+    pub occurrence: NaiveDateTime,
+    /// Whether to remove only this occurrence, or the following ones as well.
+    pub scope: RecurrenceScope,
+}
+
+/// A secret feed of a user's events that calendar apps can subscribe to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CalendarFeedResponse {
+    /// The secret in the feed's URL.
+    pub token: Uuid,
+    /// Where the feed is served, relative to the server.
+    pub path: String,
+    /// When the token was issued.
+    pub created_at: NaiveDateTime,
+}
+
+impl From<CalendarFeed> for CalendarFeedResponse {
+    fn from(feed: CalendarFeed) -> Self {
+        CalendarFeedResponse {
+            token: feed.token,
+            path: format!("/api/calendar/feed/{}.ics", feed.token),
+            created_at: feed.created_at,
         }
     }
 }
 
-/// Query parameters for /events
+/// Query parameters for importing events.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ImportQuery {
+    /// Whether to replace the user's events, or merge the imported events into them.
+    #[serde(default)]
+    pub mode: ImportMode,
+    /// If true, nothing is changed, but the summary describes what the import would do.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Query parameters for a range of times.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TimeBoundaries {
     pub start: DateTime<Utc>,
     pub stop: DateTime<Utc>,
 }
 
+/// Query parameters for /events.
+///
+/// Times with an offset, like `2019-05-06T09:00:00Z`, are exact.
+/// Times without one, like `2019-05-06T09:00:00`, and dates, like `2019-05-06`,
+/// are in the user's time zone.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventBoundaries {
+    pub start: String,
+    pub stop: String,
+}
+
 /// Calendar api.
 ///
 /// # Arguments
@@ -78,19 +329,58 @@ pub fn calendar_api(
         .and(path::end())
         .and(warp::post2())
         .and(json_body_filter(350)) // you can import a bunch 'o events
+        .and(warp::query())
         .and(user_filter(state))
         .and(state.db())
         .map(
             |events: Vec<ImportExportEvent>,
+             query: ImportQuery,
              user_uuid: Uuid,
-             conn: PooledConn|
-             -> Result<(), diesel::result::Error> {
+             conn: PooledConn| {
                 info!("Importing events for user: {}", user_uuid);
-                Event::import_events(events, user_uuid, &conn)
+                import_events(events, query, user_uuid, &conn)
+            },
+        )
+        .and_then(util::json_or_reject);
+
+    // Get all events as an iCalendar file.
+    let export_ics = warp::get2()
+        .and(path!("events" / "export" / "ics"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .and_then(|user_uuid: Uuid, conn: PooledConn| {
+            info!("Exporting events as iCalendar for user: {}", user_uuid);
+            let events = Event::events(user_uuid, &conn).map_err(Error::from_reject)?;
+            calendar_response(&events, Some("calendar.ics"))
+        });
+
+    let import_ics = warp::post2()
+        .and(path!("events" / "import" / "ics"))
+        .and(path::end())
+        .and(text_body_filter(350))
+        .and(warp::query())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(
+            |ics: String, query: ImportQuery, user_uuid: Uuid, conn: PooledConn| {
+                info!("Importing iCalendar events for user: {}", user_uuid);
+                ical::read_calendar(&ics)
+                    .map_err(Error::bad_request)
+                    .and_then(|events| import_events(events, query, user_uuid, &conn))
             },
         )
         .and_then(util::json_or_reject);
 
+    let search = warp::get2()
+        .and(path!("search"))
+        .and(path::end())
+        .and(warp::query())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(search_events)
+        .and_then(util::json_or_reject);
+
     // Events with time bounds
     let events = warp::get2()
         .and(path!("events"))
@@ -98,125 +388,1354 @@ pub fn calendar_api(
         .and(user_filter(state))
         .and(state.db())
         .map(
-            |tb: TimeBoundaries,
+            |tb: EventBoundaries,
              user_uuid: Uuid,
              conn: PooledConn|
-             -> Result<Vec<Event>, diesel::result::Error> {
-                info!("getting time-bounded events for user: {}, from {} until {}", user_uuid, tb.start, tb.stop);
+             -> Result<Vec<ZonedEvent>, Error> {
+                info!(
+                    "getting time-bounded events for user: {}, from {} until {}",
+                    user_uuid, tb.start, tb.stop
+                );
+                let zone = user_zone(user_uuid, &conn)?;
                 Event::events_from_n_to_n(
                     user_uuid,
-                    tb.start.naive_utc(),
-                    tb.stop.naive_utc(),
+                    parse_boundary(&tb.start, zone)?,
+                    parse_boundary(&tb.stop, zone)?,
+                    zone,
                     &conn,
-                )
+                )?
+                .into_iter()
+                .map(|event| ZonedEvent::new(event, zone))
+                .collect::<Vec<_>>()
+                .apply(Ok)
             },
         )
         .and_then(util::json_or_reject);
 
     let create_event = warp::post2()
         .and(json_body_filter(50))
+        .and(warp::query())
         .and(user_filter(state))
         .and(state.db())
-        .map(|e: NewEventRequest, user_uuid: Uuid, conn: PooledConn| {
-            let new_event = e.into_new_event(user_uuid);
-            // check logical ordering of start and stop times
-            if new_event.start_at > new_event.stop_at {
-                Error::bad_request("Request can't start after it has ended.").apply(Err)
-            } else {
-                Event::create_event(new_event, &conn).map_err(Error::from)
-            }
+        .map(
+            |e: NewEventRequest,
+             query: ConflictQuery,
+             user_uuid: Uuid,
+             conn: PooledConn|
+             -> Result<ZonedEvent, Error> {
+                let zone = user_zone(user_uuid, &conn)?;
+                let mut new_event = e.into_new_event(user_uuid);
+                new_event.rrule = normalize_rrule(new_event.rrule)?;
+                new_event.time_zone = normalize_zone(new_event.time_zone)?
+                    .or_else(|| Some(zone.name().to_string()));
+                if new_event.all_day {
+                    let (start_at, stop_at) = whole_days(new_event.start_at, new_event.stop_at);
+                    new_event.start_at = start_at;
+                    new_event.stop_at = stop_at;
+                }
+                // check logical ordering of start and stop times
+                if new_event.start_at > new_event.stop_at {
+                    Error::bad_request("Request can't start after it has ended.").apply(Err)
+                } else {
+                    conn.build_transaction().run(|| -> Result<ZonedEvent, Error> {
+                        if query.reject_conflicts {
+                            let times = (new_event.start_at, new_event.stop_at, new_event.all_day);
+                            reject_conflicts(user_uuid, None, times, &conn)?;
+                        }
+                        let event = Event::create_event(new_event, &conn)?;
+                        Ok(ZonedEvent::new(event, zone))
+                    })
+                }
+            },
+        )
+        .and_then(|result: Result<ZonedEvent, Error>| {
+            result.map_err(Error::reject).and_then(event_reply)
+        });
+
+    let change_occurrences = warp::put2()
+        .and(path!(Uuid / "occurrence"))
+        .and(path::end())
+        .and(json_body_filter(50))
+        .and(user_filter(state))
+        .and(state.db())
+        .and_then(change_occurrences);
+
+    let delete_occurrences = warp::delete2()
+        .and(path!(Uuid / "occurrence"))
+        .and(path::end())
+        .and(warp::query())
+        .and(user_filter(state))
+        .and(state.db())
+        .and_then(delete_occurrences);
+
+    let get_reminders = warp::get2()
+        .and(path!(Uuid / "reminder"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|event_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            get_owned_event(event_uuid, user_uuid, &conn).and_then(|_| {
+                EventReminder::reminders_for_event(event_uuid, &conn).map_err(Error::from)
+            })
+        })
+        .and_then(util::json_or_reject);
+
+    let create_reminder = warp::post2()
+        .and(path!(Uuid / "reminder"))
+        .and(path::end())
+        .and(json_body_filter(1))
+        .and(user_filter(state))
+        .and(state.db())
+        .map(create_reminder)
+        .and_then(util::json_or_reject);
+
+    let delete_reminder = warp::delete2()
+        .and(path!(Uuid / "reminder" / Uuid))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(delete_reminder)
+        .and_then(util::json_or_reject);
+
+    let get_invitees = warp::get2()
+        .and(path!(Uuid / "invitee"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|event_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            get_writable_event(event_uuid, user_uuid, &conn).and_then(|_| {
+                EventInvitation::invitations_for_event(event_uuid, &conn).map_err(Error::from)
+            })
+        })
+        .and_then(util::json_or_reject);
+
+    let invite = warp::post2()
+        .and(path!(Uuid / "invitee"))
+        .and(path::end())
+        .and(json_body_filter(1))
+        .and(user_filter(state))
+        .and(state.db())
+        .map(invite)
+        .and_then(util::json_or_reject);
+
+    let uninvite = warp::delete2()
+        .and(path!(Uuid / "invitee" / Uuid))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(
+            |event_uuid: Uuid, invitee_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+                get_writable_event(event_uuid, user_uuid, &conn).and_then(|_| {
+                    EventInvitation::uninvite(event_uuid, invitee_uuid, &conn).map_err(Error::from)
+                })
+            },
+        )
+        .and_then(util::json_or_reject);
+
+    // Only the invitee can answer their invitation.
+    let reply_to_invitation = warp::put2()
+        .and(path!(Uuid / "invitation"))
+        .and(path::end())
+        .and(json_body_filter(1))
+        .and(user_filter(state))
+        .and(state.db())
+        .map(
+            |event_uuid: Uuid, reply: InvitationReply, user_uuid: Uuid, conn: PooledConn| {
+                if reply.status == InvitationStatus::Pending {
+                    return Error::bad_request("An invitation can only be accepted or declined.")
+                        .apply(Err);
+                }
+                let now = Utc::now().naive_utc();
+                EventInvitation::respond(event_uuid, user_uuid, reply.status, now, &conn)
+                    .map_err(Error::from)
+            },
+        )
+        .and_then(util::json_or_reject);
+
+    let trash = warp::get2()
+        .and(path!("trash"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| -> Result<Vec<ZonedEvent>, Error> {
+            let zone = user_zone(user_uuid, &conn)?;
+            Event::trash(user_uuid, &conn)?
+                .into_iter()
+                .map(|event| ZonedEvent::new(event, zone))
+                .collect::<Vec<_>>()
+                .apply(Ok)
         })
         .and_then(util::json_or_reject);
 
+    let restore_event = warp::post2()
+        .and(path!(Uuid / "restore"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .and_then(|event_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            restore_event(event_uuid, user_uuid, conn)
+                .map_err(Error::reject)
+                .and_then(event_reply)
+        });
+
+    let get_event = warp::get2()
+        .and(path!(Uuid))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .and_then(|event_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            get_visible_event(event_uuid, user_uuid, &conn)
+                .and_then(|event| Ok(ZonedEvent::new(event, user_zone(user_uuid, &conn)?)))
+                .map_err(Error::reject)
+                .and_then(event_reply)
+        });
+
     let delete_event = warp::delete2()
         .and(path!(Uuid))
+        .and(warp::header::optional::<String>("if-match"))
         .and(user_filter(state))
         .and(state.db())
         .and_then(delete_event);
 
     let modify_event = warp::put2()
         .and(json_body_filter(50))
+        .and(warp::query())
+        .and(warp::header::optional::<String>("if-match"))
         .and(user_filter(state))
         .and(state.db())
         .and_then(modify_event);
 
+    let patch_event = warp::patch()
+        .and(path!(Uuid))
+        .and(path::end())
+        .and(json_body_filter(50))
+        .and(warp::query())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(user_filter(state))
+        .and(state.db())
+        .and_then(patch_event);
+
     let events = path!("event").and(
-        export_events
+        export_ics
+            .or(import_ics)
+            .or(export_events)
             .or(import_events)
+            .or(search)
+            .or(trash)
             .or(events)
             .or(create_event)
+            .or(change_occurrences)
+            .or(delete_occurrences)
+            .or(get_reminders)
+            .or(create_reminder)
+            .or(delete_reminder)
+            .or(get_invitees)
+            .or(invite)
+            .or(uninvite)
+            .or(reply_to_invitation)
+            .or(restore_event)
+            .or(get_event)
             .or(delete_event)
-            .or(modify_event),
+            .or(modify_event)
+            .or(patch_event),
     );
 
-    path!("calendar").and(events)
-}
+    let get_feed = warp::get2()
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| {
+            CalendarFeed::get_feed_for_user(user_uuid, &conn).map(CalendarFeedResponse::from)
+        })
+        .and_then(util::json_or_reject);
 
-/// Deletes the event after checking that it belongs to the user.
-/// First, it gets the event from the database, then it checks if the event belongs to the user,
-/// then it deletes the event.
-///
-/// # Arguments
-/// * event_uuid - The uuid of the event to be deleted.
-/// * user_uuid - The user's uuid.
-/// Used to validate that the user owns the event being modified.
-/// * conn - The connection to the database.
-fn delete_event(
-    event_uuid: Uuid,
-    user_uuid: Uuid,
-    conn: PooledConn,
-) -> Result<impl Reply, Rejection> {
-    Event::get_event(event_uuid, &conn)
-        .map_err(Error::from)
-        .and_then(|event: Event| {
-            if event.user_uuid != user_uuid {
-                Err(Error::not_authorized("User UUIDs do not match"))
-            } else {
-                Event::delete_event(event_uuid, &conn).map_err(Error::from)
-            }
+    // Issue a new token, so anyone holding the old feed URL loses access.
+    let replace_feed = warp::post2()
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| {
+            info!("Issuing a calendar feed for user: {}", user_uuid);
+            let feed = CalendarFeed {
+                user_uuid,
+                token: Uuid::new_v4(),
+                created_at: Utc::now().naive_utc(),
+            };
+            CalendarFeed::replace_feed(feed, &conn).map(CalendarFeedResponse::from)
         })
-        .map_err(Error::reject)
-        .map(util::json)
-}
+        .and_then(util::json_or_reject);
 
-/// Modifies an existing event.
-///
-/// # Arguments
-/// * changeset - The changeset used to modify the event.
-/// * user_uuid - The user's uuid.
-/// Used to validate that the user owns the event being modified.
-/// * conn - The connection to the database.
-fn modify_event(
-    changeset: EventChangeset,
-    user_uuid: Uuid,
-    conn: PooledConn,
-) -> Result<impl Reply, Rejection> {
-    // check logical ordering of start and stop times
-    if changeset.start_at > changeset.stop_at {
-        Error::bad_request("Request can't start after it has ended.").reject_result()
-    } else {
-        // Check if the user has authority to change the event.
-        Event::get_event(changeset.uuid, &conn)
-            .map_err(Error::from)
-            .and_then(|event: Event| {
-                if event.user_uuid != user_uuid {
-                    Err(Error::not_authorized("User UUIDs do not match"))
-                } else {
-                    Event::change_event(changeset, &conn).map_err(Error::from)
-                }
-            })
-            .map_err(Error::reject)
-            .map(util::json)
-    }
-}
+    let delete_feed = warp::delete2()
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| {
+            CalendarFeed::delete_feed(user_uuid, &conn).map(CalendarFeedResponse::from)
+        })
+        .and_then(util::json_or_reject);
 
-#[cfg(test)]
-mod unit_test {
-    use super::*;
-    use crate::{api::auth::get_jwt, testing_fixtures::user::UserFixture};
-    use authorization::{Secret, AUTHORIZATION_HEADER_KEY, BEARER};
-    use pool::Pool;
-    use testing_common::setup::setup_warp;
+    // The feed itself is read by calendar apps, which authenticate with the token in the URL.
+    let serve_feed = warp::get2()
+        .and(path!(String))
+        .and(path::end())
+        .and(state.db())
+        .and_then(serve_feed);
+
+    let feed = path!("feed").and(get_feed.or(replace_feed).or(delete_feed).or(serve_feed));
+
+    let fired_reminders = warp::get2()
+        .and(path!("fired"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| FiredReminder::fired_for_user(user_uuid, &conn))
+        .and_then(util::json_or_reject);
+
+    let dismiss_reminder = warp::delete2()
+        .and(path!("fired" / Uuid))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            FiredReminder::dismiss(uuid, user_uuid, Utc::now().naive_utc(), &conn)
+        })
+        .and_then(util::json_or_reject);
+
+    let reminders = path!("reminder").and(fired_reminders.or(dismiss_reminder));
+
+    let get_shares = warp::get2()
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| CalendarShare::shares_by_owner(user_uuid, &conn))
+        .and_then(util::json_or_reject);
+
+    let get_received_shares = warp::get2()
+        .and(path!("received"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| {
+            CalendarShare::shares_with_member(user_uuid, &conn)
+        })
+        .and_then(util::json_or_reject);
+
+    let share = warp::put2()
+        .and(path::end())
+        .and(json_body_filter(1))
+        .and(user_filter(state))
+        .and(state.db())
+        .map(share_calendar)
+        .and_then(util::json_or_reject);
+
+    let unshare = warp::delete2()
+        .and(path!(Uuid))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|member_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            CalendarShare::unshare(user_uuid, member_uuid, &conn)
+        })
+        .and_then(util::json_or_reject);
+
+    // Members can stop seeing a calendar that was shared with them.
+    let leave_share = warp::delete2()
+        .and(path!("received" / Uuid))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|owner_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            CalendarShare::unshare(owner_uuid, user_uuid, &conn)
+        })
+        .and_then(util::json_or_reject);
+
+    let shares = path!("share").and(
+        get_received_shares
+            .or(leave_share)
+            .or(get_shares)
+            .or(share)
+            .or(unshare),
+    );
+
+    let invitations = warp::get2()
+        .and(path!("invitation"))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| -> Result<Vec<InvitedEvent>, Error> {
+            let zone = user_zone(user_uuid, &conn)?;
+            EventInvitation::invitations_for_user(user_uuid, &conn)?
+                .into_iter()
+                .map(|(invitation, event)| InvitedEvent {
+                    invitation,
+                    event: ZonedEvent::new(event, zone),
+                })
+                .collect::<Vec<_>>()
+                .apply(Ok)
+        })
+        .and_then(util::json_or_reject);
+
+    let freebusy = warp::get2()
+        .and(path!("freebusy"))
+        .and(path::end())
+        .and(warp::query())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(
+            |tb: EventBoundaries,
+             user_uuid: Uuid,
+             conn: PooledConn|
+             -> Result<Vec<BusyInterval>, Error> {
+                let zone = user_zone(user_uuid, &conn)?;
+                let start = parse_boundary(&tb.start, zone)?;
+                let stop = parse_boundary(&tb.stop, zone)?;
+                let events = busy_events(user_uuid, start, stop, zone, &conn)?;
+                Ok(busy_intervals(&events, zone, start, stop))
+            },
+        )
+        .and_then(util::json_or_reject);
+
+    let month_view = warp::get2()
+        .and(path!("month" / i32 / u32))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|year: i32, month: u32, user_uuid: Uuid, conn: PooledConn| {
+            let (first, last) = Year::from_i32(year)
+                .and_then(|year| MonthIndex::from_1_indexed_u32(month).map(|m| year.month(m)))
+                .ok_or_else(|| Error::bad_request("Invalid year or month."))?;
+            calendar_view(user_uuid, first, last, &conn)
+        })
+        .and_then(util::json_or_reject);
+
+    let week_view = warp::get2()
+        .and(path!("week" / i32 / u32))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|year: i32, week: u32, user_uuid: Uuid, conn: PooledConn| {
+            let (first, last) = Year::from_i32(year)
+                .and_then(|year| year.iso_week(week))
+                .ok_or_else(|| Error::bad_request("Invalid year or ISO week."))?;
+            calendar_view(user_uuid, first, last, &conn)
+        })
+        .and_then(util::json_or_reject);
+
+    let day_view = warp::get2()
+        .and(path!("day" / i32 / u32 / u32))
+        .and(path::end())
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|year: i32, month: u32, day: u32, user_uuid: Uuid, conn: PooledConn| {
+            let date = Year::from_i32(year)
+                .and_then(|year| {
+                    MonthIndex::from_1_indexed_u32(month).and_then(|m| year.day(m, day))
+                })
+                .ok_or_else(|| Error::bad_request("Invalid date."))?;
+            calendar_view(user_uuid, date, date, &conn)
+        })
+        .and_then(util::json_or_reject);
+
+    let views = month_view.or(week_view).or(day_view);
+
+    path!("calendar").and(
+        events
+            .or(feed)
+            .or(reminders)
+            .or(shares)
+            .or(invitations)
+            .or(freebusy)
+            .or(views),
+    )
+}
+
+/// Replies with the events as an iCalendar file.
+///
+/// # Arguments
+/// * events - The events to write.
+/// * file_name - The name the file should be saved as, if it should be downloaded.
+fn calendar_response(
+    events: &[Event],
+    file_name: Option<&str>,
+) -> Result<Response<String>, Rejection> {
+    let mut response = Response::builder();
+    response.header(CONTENT_TYPE, ical::CONTENT_TYPE);
+    if let Some(file_name) = file_name {
+        response.header(
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        );
+    }
+    response
+        .body(ical::write_calendar(events, Utc::now().naive_utc()))
+        .map_err(|e| Error::internal_server_error(e.to_string()).reject())
+}
+
+/// Serves the events of the user that a feed token belongs to.
+///
+/// # Arguments
+/// * file - The last segment of the feed's path: the token, followed by `.ics`.
+/// * conn - The connection to the database.
+fn serve_feed(file: String, conn: PooledConn) -> Result<Response<String>, Rejection> {
+    let not_found = || Error::not_found("calendar feed").reject();
+    let token = file
+        .trim_end_matches(".ics")
+        .parse::<Uuid>()
+        .map_err(|_| not_found())?;
+    let feed = CalendarFeed::get_feed_by_token(token, &conn).map_err(|_| not_found())?;
+    let events = Event::events(feed.user_uuid, &conn).map_err(Error::from_reject)?;
+    calendar_response(&events, None)
+}
+
+/// Imports events for the user, after checking their times, recurrence rules and time zones.
+///
+/// # Arguments
+/// * events - The events to import.
+/// * query - How the events should be imported.
+/// * user_uuid - The user the events are imported for.
+/// * conn - The connection to the database.
+fn import_events(
+    mut events: Vec<ImportExportEvent>,
+    query: ImportQuery,
+    user_uuid: Uuid,
+    conn: &PooledConn,
+) -> Result<ImportSummary, Error> {
+    for event in &mut events {
+        if event.start_at > event.stop_at {
+            return Err(Error::bad_request(format!(
+                "The event '{}' can't start after it has ended.",
+                event.title
+            )));
+        }
+        event.rrule = normalize_rrule(event.rrule.take())?;
+        event.time_zone = normalize_zone(event.time_zone.take())?;
+        if event.all_day {
+            let (start_at, stop_at) = whole_days(event.start_at, event.stop_at);
+            event.start_at = start_at;
+            event.stop_at = stop_at;
+        }
+    }
+    let now = Utc::now().naive_utc();
+    Event::import_events(events, user_uuid, query.mode, query.dry_run, now, conn).map_err(|e| {
+        match e {
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                Error::bad_request("More than one imported event has the same external id.")
+            }
+            e => Error::from(e),
+        }
+    })
+}
+
+/// Checks that a recurrence rule can be understood, and writes it in a consistent form.
+///
+/// # Arguments
+/// * rrule - The rule, if the event is recurring.
+fn normalize_rrule(rrule: Option<String>) -> Result<Option<String>, Error> {
+    match rrule {
+        Some(rrule) => rrule
+            .parse::<RecurrenceRule>()
+            .map(|rule| Some(rule.to_string()))
+            .map_err(|e| Error::bad_request(e.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Widens the times of an all-day event to midnight, so it covers every date it touches.
+/// An event that stops at midnight doesn't cover the date it stops on, unless it starts on it.
+///
+/// # Arguments
+/// * start_at - When the event starts.
+/// * stop_at - When the event stops. It can't be before `start_at`.
+fn whole_days(start_at: NaiveDateTime, stop_at: NaiveDateTime) -> (NaiveDateTime, NaiveDateTime) {
+    let start = start_at.date().and_hms(0, 0, 0);
+    let mut stop = stop_at.date().and_hms(0, 0, 0);
+    if stop < stop_at || stop == start {
+        stop += Duration::days(1);
+    }
+    (start, stop)
+}
+
+/// Checks that a time zone is known.
+///
+/// # Arguments
+/// * time_zone - The IANA name of the zone, if one was given.
+fn normalize_zone(time_zone: Option<String>) -> Result<Option<String>, Error> {
+    match time_zone {
+        Some(name) => time_zone::parse_zone(&name)
+            .map(|zone| Some(zone.name().to_string()))
+            .ok_or_else(|| Error::bad_request(format!("The time zone '{}' is not known.", name))),
+        None => Ok(None),
+    }
+}
+
+/// Gets the time zone the user's calendar is shown in.
+///
+/// # Arguments
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn user_zone(user_uuid: Uuid, conn: &PooledConn) -> Result<Tz, Error> {
+    let user = User::get_user(user_uuid, conn)?;
+    Ok(time_zone::parse_zone(&user.time_zone).unwrap_or(Tz::UTC))
+}
+
+/// Reads a time boundary of an events query as UTC.
+///
+/// # Arguments
+/// * value - An RFC 3339 time, or a date or time without an offset.
+/// * zone - The zone times without an offset are in.
+fn parse_boundary(value: &str, zone: Tz) -> Result<NaiveDateTime, Error> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.naive_utc());
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_hms(0, 0, 0)))
+        .map(|local| time_zone::to_utc(local, zone))
+        .map_err(|_| Error::bad_request(format!("'{}' is not a date or time.", value)))
+}
+
+/// The times of an event in UTC. The times of all-day events are local, so they are converted.
+///
+/// # Arguments
+/// * start_at - When the event starts.
+/// * stop_at - When the event stops.
+/// * all_day - Whether the event lasts whole days.
+/// * zone - The zone of the user the event is shown to.
+fn utc_times(
+    start_at: NaiveDateTime,
+    stop_at: NaiveDateTime,
+    all_day: bool,
+    zone: Tz,
+) -> (NaiveDateTime, NaiveDateTime) {
+    if all_day {
+        (time_zone::to_utc(start_at, zone), time_zone::to_utc(stop_at, zone))
+    } else {
+        (start_at, stop_at)
+    }
+}
+
+/// Gets the events the user is busy with during a range of time.
+/// These are their own events, and the events they accepted invitations to.
+/// Events of calendars that are shared with them don't make them busy.
+///
+/// # Arguments
+/// * user_uuid - The user's uuid.
+/// * start - The start of the range, in UTC.
+/// * stop - The end of the range, in UTC.
+/// * zone - The user's time zone.
+/// * conn - The connection to the database.
+fn busy_events(
+    user_uuid: Uuid,
+    start: NaiveDateTime,
+    stop: NaiveDateTime,
+    zone: Tz,
+    conn: &PooledConn,
+) -> Result<Vec<Event>, Error> {
+    let accepted: HashSet<Uuid> = EventInvitation::invitations_for_user(user_uuid, conn)?
+        .into_iter()
+        .filter(|(invitation, _)| invitation.status == InvitationStatus::Accepted)
+        .map(|(invitation, _)| invitation.event_uuid)
+        .collect();
+    Event::events_from_n_to_n(user_uuid, start, stop, zone, conn)?
+        .into_iter()
+        .filter(|event| {
+            event.user_uuid == user_uuid
+                || accepted.contains(&event.uuid)
+                || event
+                    .recurrence_parent_uuid
+                    .map_or(false, |parent| accepted.contains(&parent))
+        })
+        .collect::<Vec<_>>()
+        .apply(Ok)
+}
+
+/// Merges the times of events into the spans of time that they keep the user busy.
+/// The spans are limited to the range, and events that take no time are left out.
+///
+/// # Arguments
+/// * events - The events the user is busy with.
+/// * zone - The user's time zone.
+/// * start - The start of the range, in UTC.
+/// * stop - The end of the range, in UTC.
+fn busy_intervals(
+    events: &[Event],
+    zone: Tz,
+    start: NaiveDateTime,
+    stop: NaiveDateTime,
+) -> Vec<BusyInterval> {
+    let mut times: Vec<(NaiveDateTime, NaiveDateTime)> = events
+        .iter()
+        .map(|event| utc_times(event.start_at, event.stop_at, event.all_day, zone))
+        .map(|(start_at, stop_at)| (start_at.max(start), stop_at.min(stop)))
+        .filter(|(start_at, stop_at)| start_at < stop_at)
+        .collect();
+    times.sort();
+
+    let mut busy: Vec<BusyInterval> = vec![];
+    for (start_at, stop_at) in times {
+        match busy.last_mut() {
+            Some(last) if start_at <= last.stop_at => last.stop_at = last.stop_at.max(stop_at),
+            _ => busy.push(BusyInterval { start_at, stop_at }),
+        }
+    }
+    busy
+}
+
+/// Fails with a conflict listing the events that the times overlap, if there are any.
+///
+/// Only the times given are checked, so later occurrences of a recurring event may still conflict.
+///
+/// # Note
+/// The owner is locked, so that concurrent checks for their calendar wait on this one.
+/// This should be run within the same database transaction that writes the event,
+/// so another event can't be written into the checked times before this one is.
+///
+/// # Arguments
+/// * user_uuid - The user whose events are checked. This is the owner of the event.
+/// * event_uuid - The event being changed, which doesn't conflict with itself.
+/// * times - When the event starts and stops, and whether it lasts whole days.
+/// * conn - The connection to the database.
+fn reject_conflicts(
+    user_uuid: Uuid,
+    event_uuid: Option<Uuid>,
+    times: (NaiveDateTime, NaiveDateTime, bool),
+    conn: &PooledConn,
+) -> Result<(), Error> {
+    User::get_user_for_update(user_uuid, conn)?;
+    let zone = user_zone(user_uuid, conn)?;
+    let (start_at, stop_at, all_day) = times;
+    let (start, stop) = utc_times(start_at, stop_at, all_day, zone);
+    if start >= stop {
+        // Events that take no time don't keep the user busy.
+        return Ok(());
+    }
+    let conflicts: Vec<ZonedEvent> = busy_events(user_uuid, start, stop, zone, conn)?
+        .into_iter()
+        .filter(|event| Some(event.uuid) != event_uuid)
+        .filter(|event| {
+            let (other_start, other_stop) =
+                utc_times(event.start_at, event.stop_at, event.all_day, zone);
+            other_start < other_stop && other_start < stop && start < other_stop
+        })
+        .map(|event| ZonedEvent::new(event, zone))
+        .collect();
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Error::conflict(
+            format!("The event overlaps {} other events.", conflicts.len()),
+            &conflicts,
+        )
+        .apply(Err)
+    }
+}
+
+/// Searches the events in the user's calendar.
+///
+/// # Arguments
+/// * query - What to search for, and the optional bounds of the search.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn search_events(
+    query: SearchQuery,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<Vec<SearchResult>, Error> {
+    if query.q.trim().is_empty() {
+        return Error::bad_request("Search for at least one word.").apply(Err);
+    }
+    let zone = user_zone(user_uuid, &conn)?;
+    let range = match (query.start, query.stop) {
+        (Some(start), Some(stop)) => Some((
+            parse_boundary(&start, zone)?,
+            parse_boundary(&stop, zone)?,
+        )),
+        (None, None) => None,
+        _ => {
+            return Error::bad_request("Bound the search by both start and stop, or neither.")
+                .apply(Err)
+        }
+    };
+    Event::search_events(user_uuid, &query.q, range, SEARCH_LIMIT, &conn)?
+        .into_iter()
+        .map(|result| SearchResult {
+            event: ZonedEvent::new(result.event, zone),
+            rank: result.rank,
+            snippet: result.snippet,
+        })
+        .collect::<Vec<_>>()
+        .apply(Ok)
+}
+
+/// Gets the events in the user's calendar from one day to another, grouped by day.
+///
+/// # Arguments
+/// * user_uuid - The user's uuid.
+/// * first - The first day.
+/// * last - The last day, which is included.
+/// * conn - The connection to the database.
+fn calendar_view(
+    user_uuid: Uuid,
+    first: NaiveDate,
+    last: NaiveDate,
+    conn: &PooledConn,
+) -> Result<CalendarView, Error> {
+    let zone = user_zone(user_uuid, conn)?;
+    let days = Event::events_by_day(user_uuid, first, last, zone, conn)?;
+    // Occurrences of a recurring event share its uuid, so they are told apart by their start.
+    let count = days
+        .iter()
+        .flat_map(|day| day.events.iter())
+        .map(|event| (event.uuid, event.recurrence_id))
+        .collect::<HashSet<_>>()
+        .len();
+    let days = days
+        .into_iter()
+        .map(|day| DayEvents {
+            date: day.date,
+            count: day.events.len(),
+            events: day
+                .events
+                .into_iter()
+                .map(|event| ZonedEvent::new(event, zone))
+                .collect(),
+        })
+        .collect();
+    Ok(CalendarView {
+        first,
+        last,
+        count,
+        days,
+    })
+}
+
+/// The ETag of an event, which changes whenever the event does.
+fn event_etag(event: &Event) -> String {
+    format!("\"{}\"", event.updated_at.format("%Y-%m-%dT%H:%M:%S%.6f"))
+}
+
+/// Replies with the event as json, along with its ETag.
+fn event_reply(event: ZonedEvent) -> Result<Response<String>, Rejection> {
+    let json = serde_json::to_string(&event)
+        .map_err(|e| Error::internal_server_error(e.to_string()).reject())?;
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .header(ETAG, event_etag(&event.event))
+        .body(json)
+        .map_err(|e| Error::internal_server_error(e.to_string()).reject())
+}
+
+/// Checks the `If-Match` header of a request that changes an event.
+///
+/// # Arguments
+/// * if_match - The header, if the request has one.
+/// * event - The event as it is now.
+///
+/// # Return
+/// When the event was last updated, if the request should only change it as it is now.
+/// Requests without the header, or with `*`, can change the event however it is.
+/// It fails with `PreconditionFailed` if none of the ETags in the header are the event's.
+fn check_if_match(if_match: Option<String>, event: &Event) -> Result<Option<NaiveDateTime>, Error> {
+    match if_match {
+        None => Ok(None),
+        Some(ref if_match) if if_match.trim() == "*" => Ok(None),
+        Some(if_match) => {
+            let etag = event_etag(event);
+            if if_match.split(',').any(|tag| tag.trim() == etag) {
+                Ok(Some(event.updated_at))
+            } else {
+                Err(changed_since_fetched(DieselError::NotFound))
+            }
+        }
+    }
+}
+
+/// The error for a change to an event that was changed by someone else in the meantime.
+/// Changes that only apply to the event as it was fail with `NotFound` when it has changed.
+fn changed_since_fetched(error: DieselError) -> Error {
+    match error {
+        DieselError::NotFound => {
+            Error::precondition_failed("The event has changed since it was fetched.")
+        }
+        error => Error::from(error),
+    }
+}
+
+/// Gets an event, after checking that it belongs to the user.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn get_owned_event(event_uuid: Uuid, user_uuid: Uuid, conn: &PooledConn) -> Result<Event, Error> {
+    let event = Event::get_event(event_uuid, conn)?;
+    if event.user_uuid != user_uuid {
+        Err(Error::not_authorized("User UUIDs do not match"))
+    } else {
+        Ok(event)
+    }
+}
+
+/// Gets an event, after checking that it is shown in the user's calendar.
+/// It is if the event is theirs, its calendar is shared with them, or they are invited to it.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn get_visible_event(event_uuid: Uuid, user_uuid: Uuid, conn: &PooledConn) -> Result<Event, Error> {
+    Event::get_event(event_uuid, conn)?;
+    Event::get_calendar_event(event_uuid, user_uuid, conn)
+        .optional()?
+        .ok_or_else(|| Error::not_authorized("User can't see events in this calendar"))
+}
+
+/// Gets an event, after checking that the user can change it.
+/// They can if the event is theirs, or its calendar is shared with them with write permission.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn get_writable_event(
+    event_uuid: Uuid,
+    user_uuid: Uuid,
+    conn: &PooledConn,
+) -> Result<Event, Error> {
+    let event = Event::get_event(event_uuid, conn)?;
+    match CalendarShare::permission(event.user_uuid, user_uuid, conn)? {
+        Some(SharePermission::Write) => Ok(event),
+        _ => Err(Error::not_authorized("User can't change events in this calendar")),
+    }
+}
+
+/// Shares the user's calendar with another user, or changes what they can do with it.
+///
+/// # Arguments
+/// * request - Who to share the calendar with, and what they can do.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn share_calendar(
+    request: ShareRequest,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<CalendarShare, Error> {
+    if request.user_uuid == user_uuid {
+        return Error::bad_request("You can't share your calendar with yourself.").apply(Err);
+    }
+    let share = CalendarShare {
+        owner_uuid: user_uuid,
+        member_uuid: request.user_uuid,
+        permission: request.permission,
+        created_at: Utc::now().naive_utc(),
+    };
+    CalendarShare::share(share, &conn).map_err(|e| match e {
+        DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+            Error::not_found("User")
+        }
+        e => Error::from(e),
+    })
+}
+
+/// Invites another user to an event, after checking that the user can change the event.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event.
+/// * request - Who to invite.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn invite(
+    event_uuid: Uuid,
+    request: InviteRequest,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<EventInvitation, Error> {
+    let event = get_writable_event(event_uuid, user_uuid, &conn)?;
+    if request.user_uuid == event.user_uuid {
+        return Error::bad_request("The event's owner can't be invited to it.").apply(Err);
+    }
+    let now = Utc::now().naive_utc();
+    EventInvitation::invite(event_uuid, request.user_uuid, now, &conn).map_err(|e| match e {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Error::bad_request("The user is already invited to the event.")
+        }
+        DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+            Error::not_found("User")
+        }
+        e => Error::from(e),
+    })
+}
+
+/// Adds a reminder to an event, after checking that the event belongs to the user.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event.
+/// * request - How long before the event the reminder fires.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn create_reminder(
+    event_uuid: Uuid,
+    request: ReminderRequest,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<EventReminder, Error> {
+    if request.minutes_before < 0 || request.minutes_before > MAX_MINUTES_BEFORE {
+        return Error::bad_request(format!(
+            "Reminders must be between 0 and {} minutes before the event.",
+            MAX_MINUTES_BEFORE
+        ))
+        .apply(Err);
+    }
+    get_owned_event(event_uuid, user_uuid, &conn)?;
+    let new_reminder = NewEventReminder {
+        event_uuid,
+        minutes_before: request.minutes_before,
+    };
+    EventReminder::create_reminder(new_reminder, &conn).map_err(|e| match e {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Error::bad_request(format!(
+                "The event already has a reminder {} minutes before it.",
+                request.minutes_before
+            ))
+        }
+        e => Error::from(e),
+    })
+}
+
+/// Removes a reminder from an event, after checking that the event belongs to the user.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event.
+/// * reminder_uuid - The uuid of the reminder.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn delete_reminder(
+    event_uuid: Uuid,
+    reminder_uuid: Uuid,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<EventReminder, Error> {
+    get_owned_event(event_uuid, user_uuid, &conn)?;
+    let reminder = EventReminder::get_reminder(reminder_uuid, &conn)?;
+    if reminder.event_uuid != event_uuid {
+        return Err(Error::not_found("EventReminder"));
+    }
+    EventReminder::delete_reminder(reminder_uuid, &conn).map_err(Error::from)
+}
+
+/// Changes one or more occurrences of a recurring event, after checking the user can change it.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the recurring event.
+/// * request - The occurrence to change, and its new details.
+/// * user_uuid - The user's uuid.
+/// Used to validate that the user can change the event being modified.
+/// * conn - The connection to the database.
+fn change_occurrences(
+    event_uuid: Uuid,
+    mut request: OccurrenceChangeRequest,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<impl Reply, Rejection> {
+    if request.change.start_at > request.change.stop_at {
+        return Error::bad_request("Request can't start after it has ended.").reject_result();
+    }
+    if request.scope == RecurrenceScope::This && request.change.rrule.is_some() {
+        return Error::bad_request("A single occurrence can't repeat.").reject_result();
+    }
+    normalize_rrule(request.change.rrule.take())
+        .and_then(|rrule| {
+            request.change.rrule = rrule;
+            let event = get_writable_event(event_uuid, user_uuid, &conn)?;
+            if event.all_day {
+                let change = &mut request.change;
+                let (start_at, stop_at) = whole_days(change.start_at, change.stop_at);
+                change.start_at = start_at;
+                change.stop_at = stop_at;
+            }
+            Event::change_occurrences(
+                &event,
+                request.occurrence,
+                request.scope,
+                request.change,
+                Utc::now().naive_utc(),
+                &conn,
+            )
+            .map_err(Error::from)
+        })
+        .and_then(|event| Ok(ZonedEvent::new(event, user_zone(user_uuid, &conn)?)))
+        .map_err(Error::reject)
+        .map(util::json)
+}
+
+/// Removes one or more occurrences of a recurring event, after checking the user can change it.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the recurring event.
+/// * query - The occurrence to remove.
+/// * user_uuid - The user's uuid.
+/// Used to validate that the user can change the event being modified.
+/// * conn - The connection to the database.
+fn delete_occurrences(
+    event_uuid: Uuid,
+    query: OccurrenceQuery,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<impl Reply, Rejection> {
+    get_writable_event(event_uuid, user_uuid, &conn)
+        .and_then(|event| {
+            let now = Utc::now().naive_utc();
+            Event::delete_occurrences(&event, query.occurrence, query.scope, now, &conn)
+                .map_err(Error::from)
+        })
+        .map_err(Error::reject)
+        .map(util::json)
+}
+
+/// Moves the event to the trash after checking that the user can change it.
+/// First, it gets the event from the database, then it checks if the event belongs to the user,
+/// or to a calendar shared with them for writing, then it moves the event to the trash.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event to be deleted.
+/// * if_match - The `If-Match` header, which the event's ETag must match if it is given.
+/// * user_uuid - The user's uuid.
+/// Used to validate that the user can change the event being modified.
+/// * conn - The connection to the database.
+fn delete_event(
+    event_uuid: Uuid,
+    if_match: Option<String>,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<impl Reply, Rejection> {
+    let now = Utc::now().naive_utc();
+    get_writable_event(event_uuid, user_uuid, &conn)
+        .and_then(|event| match check_if_match(if_match, &event)? {
+            Some(updated_at) => Event::delete_unmodified_event(event_uuid, updated_at, now, &conn)
+                .map_err(changed_since_fetched),
+            None => Event::delete_event(event_uuid, now, &conn).map_err(Error::from),
+        })
+        .map_err(Error::reject)
+        .map(util::json)
+}
+
+/// Takes an event out of the trash, after checking that the user can change it.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event in the trash.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn restore_event(
+    event_uuid: Uuid,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<ZonedEvent, Error> {
+    let event = Event::get_trashed_event(event_uuid, &conn)?;
+    match CalendarShare::permission(event.user_uuid, user_uuid, &conn)? {
+        Some(SharePermission::Write) => {
+            let zone = user_zone(user_uuid, &conn)?;
+            let clashes: Vec<ZonedEvent> = Event::restore_clashes(&event, &conn)?
+                .into_iter()
+                .map(|event| ZonedEvent::new(event, zone))
+                .collect();
+            if !clashes.is_empty() {
+                let reason =
+                    "Another event has the same external id, or replaces the same occurrence.";
+                return Error::conflict(reason, &clashes).apply(Err);
+            }
+            let event = Event::restore_event(event_uuid, &conn)?;
+            Ok(ZonedEvent::new(event, zone))
+        }
+        _ => Err(Error::not_authorized("User can't change events in this calendar")),
+    }
+}
+
+/// Modifies an existing event.
+///
+/// # Arguments
+/// * changeset - The changeset used to modify the event.
+/// * query - Whether to reject changes that make the event conflict with others.
+/// * if_match - The `If-Match` header, which the event's ETag must match if it is given.
+/// * user_uuid - The user's uuid.
+/// Used to validate that the user can change the event being modified.
+/// * conn - The connection to the database.
+fn modify_event(
+    mut changeset: EventChangeset,
+    query: ConflictQuery,
+    if_match: Option<String>,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<impl Reply, Rejection> {
+    // check logical ordering of start and stop times
+    if changeset.start_at > changeset.stop_at {
+        Error::bad_request("Request can't start after it has ended.").reject_result()
+    } else {
+        // Check if the user has authority to change the event.
+        get_writable_event(changeset.uuid, user_uuid, &conn)
+            .and_then(|event: Event| {
+                let updated_at = check_if_match(if_match, &event)?;
+                if event.recurrence_parent_uuid.is_some() && changeset.rrule.is_some() {
+                    Err(Error::bad_request("A single occurrence can't repeat."))
+                } else {
+                    changeset.rrule = normalize_rrule(changeset.rrule.take())?;
+                    changeset.time_zone = normalize_zone(changeset.time_zone.take())?;
+                    if changeset.all_day.unwrap_or(event.all_day) {
+                        let (start_at, stop_at) = whole_days(changeset.start_at, changeset.stop_at);
+                        changeset.start_at = start_at;
+                        changeset.stop_at = stop_at;
+                    }
+                    let event = conn.build_transaction().run(|| -> Result<Event, Error> {
+                        if query.reject_conflicts {
+                            let all_day = changeset.all_day.unwrap_or(event.all_day);
+                            let times = (changeset.start_at, changeset.stop_at, all_day);
+                            reject_conflicts(event.user_uuid, Some(event.uuid), times, &conn)?;
+                        }
+                        match updated_at {
+                            Some(updated_at) => {
+                                Event::change_unmodified_event(changeset, updated_at, &conn)
+                                    .map_err(changed_since_fetched)
+                            }
+                            None => Event::change_event(changeset, &conn).map_err(Error::from),
+                        }
+                    })?;
+                    Ok(ZonedEvent::new(event, user_zone(user_uuid, &conn)?))
+                }
+            })
+            .map_err(Error::reject)
+            .and_then(event_reply)
+    }
+}
+
+/// Alters the parts of an existing event that are present in the patch.
+///
+/// # Arguments
+/// * event_uuid - The uuid of the event being patched.
+/// * patch - The fields to alter.
+/// * query - Whether to reject changes that make the event conflict with others.
+/// * if_match - The `If-Match` header, which the event's ETag must match if it is given.
+/// * user_uuid - The user's uuid.
+/// Used to validate that the user can change the event being patched.
+/// * conn - The connection to the database.
+fn patch_event(
+    event_uuid: Uuid,
+    mut patch: EventPatch,
+    query: ConflictQuery,
+    if_match: Option<String>,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<impl Reply, Rejection> {
+    get_writable_event(event_uuid, user_uuid, &conn)
+        .and_then(|event: Event| {
+            let updated_at = check_if_match(if_match, &event)?;
+            if let (Some(_), Some(Some(_))) = (event.recurrence_parent_uuid, &patch.rrule) {
+                return Err(Error::bad_request("A single occurrence can't repeat."));
+            }
+            if let Some(rrule) = patch.rrule.take() {
+                patch.rrule = Some(normalize_rrule(rrule)?);
+            }
+            if let Some(time_zone) = patch.time_zone.take() {
+                patch.time_zone = Some(normalize_zone(time_zone)?);
+            }
+            // The times that the event will have, if the patch changes them.
+            let mut times = None;
+            if patch.start_at.is_some() || patch.stop_at.is_some() || patch.all_day.is_some() {
+                let all_day = patch.all_day.unwrap_or(event.all_day);
+                let mut start_at = patch.start_at.unwrap_or(event.start_at);
+                let mut stop_at = patch.stop_at.unwrap_or(event.stop_at);
+                if all_day {
+                    let (whole_start, whole_stop) = whole_days(start_at, stop_at);
+                    start_at = whole_start;
+                    stop_at = whole_stop;
+                }
+                // check logical ordering of start and stop times
+                if start_at > stop_at {
+                    return Err(Error::bad_request("Request can't start after it has ended."));
+                }
+                patch.start_at = Some(start_at);
+                patch.stop_at = Some(stop_at);
+                times = Some((start_at, stop_at, all_day));
+            }
+            let zone = user_zone(user_uuid, &conn)?;
+            if patch == EventPatch::default() {
+                // There is nothing to change.
+                return Ok(ZonedEvent::new(event, zone));
+            }
+
+            let event = conn.build_transaction().run(|| -> Result<Event, Error> {
+                match times {
+                    Some(times) if query.reject_conflicts => {
+                        reject_conflicts(event.user_uuid, Some(event.uuid), times, &conn)?;
+                    }
+                    _ => {}
+                }
+                match updated_at {
+                    Some(updated_at) => {
+                        Event::patch_unmodified_event(event_uuid, patch, updated_at, &conn)
+                            .map_err(changed_since_fetched)
+                    }
+                    None => Event::patch_event(event_uuid, patch, &conn).map_err(Error::from),
+                }
+            })?;
+            Ok(ZonedEvent::new(event, zone))
+        })
+        .map_err(Error::reject)
+        .and_then(event_reply)
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::{api::auth::get_jwt, testing_fixtures::user::UserFixture};
+    use authorization::{Secret, AUTHORIZATION_HEADER_KEY, BEARER};
+    use pool::Pool;
+    use testing_common::setup::setup_warp;
+
+    #[test]
+    fn busy_intervals_are_merged() {
+        let at = |day, hour| NaiveDate::from_ymd(2019, 5, day).and_hms(hour, 0, 0);
+        let event = |start_at, stop_at, all_day| Event {
+            uuid: Uuid::new_v4(),
+            user_uuid: Uuid::new_v4(),
+            title: "".to_string(),
+            text: "".to_string(),
+            start_at,
+            stop_at,
+            rrule: None,
+            exdates: vec![],
+            recurrence_parent_uuid: None,
+            recurrence_id: None,
+            external_id: None,
+            time_zone: None,
+            all_day,
+            updated_at: start_at,
+            deleted_at: None,
+        };
+        let events = vec![
+            event(at(6, 10), at(6, 12), false),
+            event(at(6, 8), at(6, 11), false),
+            event(at(6, 12), at(6, 13), false),
+            event(at(6, 15), at(6, 15), false),
+            // Midnight to midnight in New York.
+            event(at(7, 0), at(8, 0), true),
+            event(at(8, 2), at(8, 6), false),
+        ];
+        let zone: Tz = "America/New_York".parse().unwrap();
+
+        let busy = busy_intervals(&events, zone, at(6, 9), at(8, 5));
+
+        assert_eq!(
+            busy,
+            vec![
+                BusyInterval {
+                    start_at: at(6, 9),
+                    stop_at: at(6, 13),
+                },
+                BusyInterval {
+                    start_at: at(7, 4),
+                    stop_at: at(8, 5),
+                },
+            ]
+        );
+    }
 
     #[test]
     fn date_time_query_param_matches() {
@@ -1,31 +1,64 @@
 //! Responsible for hosting routes that deal with stock market data.
 use crate::state::State;
-use warp::{path, Filter, Reply};
+use warp::{
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        Response,
+    },
+    path, Filter, Reply,
+};
 
 use crate::{
+    api::calendar::TimeBoundaries,
     error::Error,
     server_auth::user_filter,
     util::{self, json_body_filter},
 };
-use db::stock::{NewStock, Stock};
-use diesel::result::QueryResult;
+use db::{
+    order::{NewOrder, Order, OrderKind, OrderStatus, UserOrderResponse},
+    stock::{
+        SortOrder, Stock, TransactionCursor, TransactionFilter, TransactionSide,
+        TransactionWithStock,
+    },
+    watchlist::{NewWatchlist, Watchlist, WatchlistEntry, WatchlistResponse},
+};
+use diesel::{
+    pg::PgConnection,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
 use pool::PooledConn;
 use uuid::Uuid;
 use warp::Rejection;
 
-use crate::{state::HttpsClient, util::json_or_reject};
+use crate::{
+    leaderboard::{self, Competitor, LeaderboardWindow},
+    portfolio::{percent_return, LotSummary, PortfolioHistory},
+    stock_quote::QuoteProvider,
+    trade_export::ExportFormat,
+    trading,
+    util::json_or_reject,
+};
 use apply::Apply;
-use chrono::Utc;
-use db::stock::{NewStockTransaction, StockTransaction, UserStockResponse};
-use diesel::result::Error as DieselError;
-use futures::{
-    future::{self, Either, Future},
-    stream::Stream,
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use db::{
+    cash::CashTransaction,
+    stock::{StockTransaction, UserStockResponse},
+    stock_listing::StockListing,
+    stock_price::StockPrice,
 };
-use hyper::{Chunk, Uri};
+use futures::future::{self, Future};
 use log::info;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
+
+/// The most listings a search returns.
+const SEARCH_RESULT_LIMIT: i64 = 20;
+/// The number of transactions on a page of history, if the request doesn't say.
+const DEFAULT_TRANSACTION_PAGE_SIZE: i64 = 50;
+/// How many times a trade is tried when it conflicts with concurrent trades, before giving up.
+const TRADE_ATTEMPTS: u32 = 3;
+/// The most transactions that can be on a page of history.
+const MAX_TRANSACTION_PAGE_SIZE: i64 = 200;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct StockTransactionRequest {
@@ -40,9 +73,188 @@ pub struct StockAndPerfResponse {
     /// The stock.
     pub stock: UserStockResponse,
     /// Net loss or gain.
+    /// This is the sum of the realized and unrealized gains.
     pub performance: f64,
     /// Current price
-    pub price: f64
+    pub price: f64,
+    /// The number of stocks still held.
+    pub quantity: i32,
+    /// What was paid for the stocks still held, with sales matched against the oldest purchases first.
+    pub cost_basis: f64,
+    /// The average price paid for the stocks still held.
+    pub average_price: f64,
+    /// Gains or losses locked in by sales.
+    pub realized: f64,
+    /// Gains or losses on the stocks still held, at the current price.
+    pub unrealized: f64,
+    /// The net gain as a percentage of everything that was paid for the stock.
+    pub percent_return: f64,
+}
+
+/// The performance of the whole portfolio, summed across every priced stock.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PortfolioTotals {
+    /// What was paid for the stocks still held.
+    pub cost_basis: f64,
+    /// The current value of the stocks still held.
+    pub market_value: f64,
+    /// Gains or losses locked in by sales.
+    pub realized: f64,
+    /// Gains or losses on the stocks still held, at their current prices.
+    pub unrealized: f64,
+    /// The net gain as a percentage of everything that was paid for stocks.
+    pub percent_return: f64,
+}
+
+/// Query parameters for /search
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchQuery {
+    /// The start of a symbol, or part of a company name.
+    pub q: String,
+}
+
+/// Query parameters for /transactions
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TransactionHistoryQuery {
+    /// Only include transactions recorded at or after this time.
+    pub start: Option<DateTime<Utc>>,
+    /// Only include transactions recorded at or before this time.
+    pub stop: Option<DateTime<Utc>>,
+    /// Only include purchases, or only include sales.
+    pub side: Option<TransactionSide>,
+    /// Only include transactions for the stock with this symbol.
+    pub symbol: Option<String>,
+    /// The order to return transactions in, by the time they were recorded. Newest first by default.
+    #[serde(default)]
+    pub sort: SortOrder,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    /// The most transactions to return.
+    pub limit: Option<i64>,
+}
+
+/// Query parameters for /leaderboard
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct LeaderboardQuery {
+    /// The period that returns are measured over. All time by default.
+    #[serde(default)]
+    pub window: LeaderboardWindow,
+}
+
+/// Query parameters for /transactions/export
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ExportQuery {
+    /// The file format to export the transactions in.
+    pub format: ExportFormat,
+}
+
+/// A page of the user's transaction history.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TransactionPage {
+    /// The transactions on this page.
+    pub transactions: Vec<TransactionWithStock>,
+    /// Pass this as the `cursor` to get the next page.
+    /// This is absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// A request to place a limit or stop order.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewOrderRequest {
+    /// The stock symbol.
+    pub symbol: String,
+    /// The sign bit indicates if it is a sale or a purchase.
+    pub quantity: i32,
+    /// The conditions under which the order is filled.
+    pub kind: OrderKind,
+    /// The price that triggers the order.
+    pub trigger_price: f64,
+}
+
+/// The spacing between points in a portfolio's history.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryInterval {
+    /// One point per day.
+    Day,
+    /// One point per week.
+    Week,
+}
+
+impl HistoryInterval {
+    /// The time between points.
+    fn step(self) -> Duration {
+        match self {
+            HistoryInterval::Day => Duration::days(1),
+            HistoryInterval::Week => Duration::weeks(1),
+        }
+    }
+}
+
+/// Query parameters for /performance/history
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PerformanceHistoryQuery {
+    /// The spacing between points.
+    pub interval: HistoryInterval,
+}
+
+/// The value of a user's account.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AccountResponse {
+    /// Cash available for buying stocks.
+    pub cash: f64,
+    /// The current value of every stock the user holds.
+    pub holdings_value: f64,
+    /// Cash plus holdings.
+    pub total_equity: f64,
+    /// Symbols of held stocks that the quote provider did not have a price for.
+    /// These are not included in `holdings_value`.
+    pub unpriced_symbols: Vec<String>,
+}
+
+/// The performance of every stock the user has traded.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PortfolioPerformanceResponse {
+    /// The performance of each stock that a current price could be found for.
+    pub stocks: Vec<StockAndPerfResponse>,
+    /// The performance of the priced stocks, taken together.
+    pub totals: PortfolioTotals,
+    /// Symbols of the user's stocks that the quote provider did not have a price for.
+    pub unpriced_symbols: Vec<String>,
+}
+
+/// A request to create or rename a watchlist.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WatchlistNameRequest {
+    /// The name of the watchlist.
+    pub name: String,
+}
+
+/// A request to add a stock to a watchlist.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WatchlistStockRequest {
+    /// The stock symbol.
+    pub symbol: String,
+}
+
+/// A stock on a watchlist, along with its current price.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StockQuote {
+    /// The stock.
+    pub stock: Stock,
+    /// Current price
+    pub price: f64,
+}
+
+/// The current prices of every stock on a watchlist.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WatchlistQuotesResponse {
+    /// The watchlist.
+    pub watchlist: Watchlist,
+    /// The stocks on the watchlist that a current price could be found for.
+    pub quotes: Vec<StockQuote>,
+    /// Symbols of stocks on the watchlist that the quote provider did not have a price for.
+    pub unpriced_symbols: Vec<String>,
 }
 
 /// The Filter for the market API.
@@ -54,14 +266,20 @@ pub fn market_api(s: &State) -> impl Filter<Extract = (impl Reply,), Error = Rej
     info!("Attaching Market Api");
     let transact = path!("transact")
         .and(warp::post2())
-        .and(s.https_client())
+        .and(s.quote_provider())
         .and(json_body_filter(10))
-        .and_then(|client: HttpsClient, request: StockTransactionRequest| {
-            // Get the current price from a remote source
-            get_current_price(&request.symbol, &client)
-                .join(future::ok::<_, Error>(request)) // Join in the request, so it isn't lost.
-                .map_err(Error::reject) // Handle errors.
-        })
+        .and_then(
+            |quotes: Arc<QuoteProvider>, request: StockTransactionRequest| {
+                // Get the current price and the company from the quote provider
+                trading::normalize_symbol(&request.symbol)
+                    .apply(future::result)
+                    .and_then(move |symbol| {
+                        quotes.current_price(&symbol).join(quotes.listing(&symbol))
+                    })
+                    .map(move |(price, listing)| (price, listing, request)) // Join in the request, so it isn't lost.
+                    .map_err(Error::reject) // Handle errors.
+            },
+        )
         .untuple_one()
         .and(user_filter(s))
         .and(s.db())
@@ -76,25 +294,135 @@ pub fn market_api(s: &State) -> impl Filter<Extract = (impl Reply,), Error = Rej
             Stock::get_stocks_belonging_to_user(user_uuid, &conn)
                 .map_err(Error::from_reject)
                 .map(util::json)
-        },
-    );
+        });
+
+    let transaction_history = warp::get2()
+        .and(path!("transactions"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(
+            |query: TransactionHistoryQuery, user_uuid: Uuid, conn: PooledConn| {
+                transaction_history(query, user_uuid, &conn)
+            },
+        )
+        .and_then(json_or_reject);
+
+    // Write every transaction the user has made to a file that can be downloaded.
+    let export_transactions = warp::get2()
+        .and(path!("transactions" / "export"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(user_filter(s))
+        .and(s.db())
+        .and_then(|query: ExportQuery, user_uuid: Uuid, conn: PooledConn| {
+            info!("Exporting transactions for user: {}", user_uuid);
+            let transactions =
+                Stock::export_transactions(user_uuid, &conn).map_err(Error::from_reject)?;
+            let file = query
+                .format
+                .write(&transactions, user_uuid, Utc::now().naive_utc());
+            Response::builder()
+                .header(CONTENT_TYPE, query.format.content_type())
+                .header(
+                    CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", query.format.file_name()),
+                )
+                .body(file)
+                .map_err(|e| Error::internal_server_error(e.to_string()).reject())
+        });
 
     let user_transactions_for_stock = warp::get2()
         .and(path!("transactions" / String)) // The string is a symbol
         .and(user_filter(s))
         .and(s.db())
         .and_then(|symbol: String, user_uuid: Uuid, conn: PooledConn| {
+            let symbol = trading::normalize_symbol(&symbol).map_err(Error::reject)?;
             let stock = Stock::get_stock_by_symbol(symbol, &conn).map_err(Error::from_reject)?;
             Stock::get_user_transactions_for_stock(user_uuid, stock.uuid, &conn)
                 .map_err(Error::from_reject)
                 .map(util::json)
         });
 
+    // Find stocks by symbol or company name in the cached listings.
+    let search = warp::get2()
+        .and(path!("search"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(|query: SearchQuery, _user_uuid: Uuid, conn: PooledConn| {
+            search_listings(&query.q, &conn)
+        })
+        .and_then(json_or_reject);
+
+    // Get the prices recorded for a stock, so it can be charted without asking the quote provider.
+    let price_history = warp::get2()
+        .and(path!(String / "history")) // The string is a symbol
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(
+            |symbol: String, tb: TimeBoundaries, _user_uuid: Uuid, conn: PooledConn| {
+                price_history(&symbol, tb, &conn)
+            },
+        )
+        .and_then(json_or_reject);
+
+    // Get the User's stock transactions, cash, and the prices recorded since their first trade
+    // Get the current prices for their stocks
+    // Replay the transactions to value the account at the end of each interval.
+    let performance_history = warp::get2()
+        .and(path!("performance" / "history"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(user_filter(s))
+        .and(s.db())
+        .and_then(
+            |query: PerformanceHistoryQuery,
+             user_uuid: Uuid,
+             conn: PooledConn|
+             -> Result<(PerformanceHistoryQuery, PortfolioHistory), Rejection> {
+                PortfolioHistory::load(user_uuid, &conn)
+                    .map(|history| (query, history))
+                    .map_err(Error::from_reject)
+            },
+        )
+        .untuple_one()
+        .and(s.quote_provider())
+        .and_then(
+            |query: PerformanceHistoryQuery,
+             history: PortfolioHistory,
+             quotes: Arc<QuoteProvider>| {
+                let symbols = history.symbols();
+                quotes
+                    .current_prices(&symbols)
+                    .map_err(Error::reject)
+                    .join(future::ok((query, history)))
+            },
+        )
+        .map(
+            |(prices, (query, history)): (
+                HashMap<String, f64>,
+                (PerformanceHistoryQuery, PortfolioHistory),
+            )| {
+                history.equity_curve(
+                    &prices,
+                    query.interval.step(),
+                    Utc::now().naive_utc().date(),
+                )
+            },
+        )
+        .map(util::json);
+
     // Get the User's stock transactions
     // Get the current prices for those transactions
-    // Zip them together, and calculate the net profit/loss for each.
+    // Join them on their symbols, and calculate the net profit/loss for each.
     let portfolio_performance = warp::get2()
         .and(path!("performance"))
+        .and(warp::path::end())
         .and(user_filter(s))
         .and(s.db())
         .and_then(
@@ -102,230 +430,853 @@ pub fn market_api(s: &State) -> impl Filter<Extract = (impl Reply,), Error = Rej
                 Stock::get_stocks_belonging_to_user(user_uuid, &conn).map_err(Error::from_reject)
             },
         )
-        .and(s.https_client())
-        .and_then(|stocks: Vec<UserStockResponse>, client: HttpsClient| {
-            let symbols: Vec<&str> = stocks.iter().map(|s| s.stock.symbol.as_str()).collect();
-            get_current_prices(&symbols, &client)
+        .and(s.quote_provider())
+        .and_then(
+            |stocks: Vec<UserStockResponse>, quotes: Arc<QuoteProvider>| {
+                let symbols: Vec<&str> = stocks.iter().map(|s| s.stock.symbol.as_str()).collect();
+                quotes
+                    .current_prices(&symbols)
+                    .map_err(Error::reject)
+                    .join(future::ok(stocks))
+            },
+        )
+        .untuple_one()
+        .map(portfolio_performance)
+        .map(util::json);
+
+    // Get the user's cash and stocks
+    // Get the current prices for the stocks they hold
+    // Sum them together to find the value of the account.
+    let account = warp::get2()
+        .and(path!("account"))
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .and_then(
+            |user_uuid: Uuid,
+             conn: PooledConn|
+             -> Result<(f64, Vec<UserStockResponse>), Rejection> {
+                let cash =
+                    CashTransaction::get_balance(user_uuid, &conn).map_err(Error::from_reject)?;
+                let stocks = Stock::get_stocks_belonging_to_user(user_uuid, &conn)
+                    .map_err(Error::from_reject)?
+                    .into_iter()
+                    .filter(|stock| stock.quantity_stocks_owned() > 0)
+                    .collect();
+                Ok((cash, stocks))
+            },
+        )
+        .untuple_one()
+        .and(s.quote_provider())
+        .and_then(
+            |cash: f64, stocks: Vec<UserStockResponse>, quotes: Arc<QuoteProvider>| {
+                let symbols: Vec<&str> = stocks.iter().map(|s| s.stock.symbol.as_str()).collect();
+                quotes
+                    .current_prices(&symbols)
+                    .map_err(Error::reject)
+                    .join(future::ok((cash, stocks)))
+            },
+        )
+        .map(|(prices, (cash, stocks))| account_summary(cash, prices, stocks))
+        .map(util::json);
+
+    // Get the trades and cash of every user who has opted in
+    // Get the current prices for all of their stocks in one batch
+    // Rank them by the change in their equity over the window.
+    let leaderboard = warp::get2()
+        .and(path!("leaderboard"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(user_filter(s))
+        .and(s.db())
+        .and_then(
+            |query: LeaderboardQuery,
+             _user_uuid: Uuid,
+             conn: PooledConn|
+             -> Result<(LeaderboardQuery, Vec<Competitor>), Rejection> {
+                Competitor::load_all(&conn)
+                    .map(|competitors| (query, competitors))
+                    .map_err(Error::from_reject)
+            },
+        )
+        .untuple_one()
+        .and(s.quote_provider())
+        .and_then(
+            |query: LeaderboardQuery, competitors: Vec<Competitor>, quotes: Arc<QuoteProvider>| {
+                let symbols = leaderboard::symbols(&competitors);
+                quotes
+                    .current_prices(&symbols)
+                    .map_err(Error::reject)
+                    .join(future::ok((query, competitors)))
+            },
+        )
+        .map(
+            |(prices, (query, competitors)): (
+                HashMap<String, f64>,
+                (LeaderboardQuery, Vec<Competitor>),
+            )| {
+                leaderboard::rank(competitors, &prices, query.window, Utc::now().naive_utc())
+            },
+        )
+        .map(util::json);
+
+    let place_order = warp::post2()
+        .and(warp::path::end())
+        .and(json_body_filter(10))
+        .and(s.quote_provider())
+        .and_then(|request: NewOrderRequest, quotes: Arc<QuoteProvider>| {
+            // Make sure the stock is listed before an order is placed for it
+            trading::normalize_symbol(&request.symbol)
+                .apply(future::result)
+                .and_then(move |symbol| quotes.listing(&symbol))
+                .map(move |listing| (listing, request))
                 .map_err(Error::reject)
-                .join(future::ok(stocks))
         })
         .untuple_one()
-        .map(
-            |prices: Vec<f64>, stocks: Vec<UserStockResponse>| -> Vec<StockAndPerfResponse> {
-                prices
-                    .into_iter()
-                    .zip(stocks)
-                    .map(|(price, stock): (f64, UserStockResponse)| {
-                        let net: f64 = stock.transactions.iter().fold(0.0, |acc, transaction| {
-                            acc + ((price - transaction.price_of_stock_at_time_of_trading)
-                                * f64::from(transaction.quantity))
-                        });
-                        StockAndPerfResponse {
-                            stock,
-                            performance: net,
-                            price
-                        }
-                    })
-                    .collect::<Vec<StockAndPerfResponse>>()
+        .and(user_filter(s))
+        .and(s.db())
+        .map(place_order)
+        .and_then(json_or_reject);
+
+    let open_orders = warp::get2()
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| Order::get_open_orders_for_user(user_uuid, &conn))
+        .and_then(json_or_reject);
+
+    let order = warp::get2()
+        .and(path!(Uuid))
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(|order_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            get_order(order_uuid, user_uuid, &conn)
+        })
+        .and_then(json_or_reject);
+
+    let cancel_order = warp::delete2()
+        .and(path!(Uuid))
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(cancel_order)
+        .and_then(json_or_reject);
+
+    let watchlists = warp::get2()
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(|user_uuid: Uuid, conn: PooledConn| {
+            Watchlist::get_watchlists_for_user(user_uuid, &conn)
+        })
+        .and_then(json_or_reject);
+
+    let create_watchlist = warp::post2()
+        .and(warp::path::end())
+        .and(json_body_filter(10))
+        .and(user_filter(s))
+        .and(s.db())
+        .map(create_watchlist)
+        .and_then(json_or_reject);
+
+    let watchlist = warp::get2()
+        .and(path!(Uuid))
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(|watchlist_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            get_owned_watchlist(watchlist_uuid, user_uuid, &conn)?;
+            Watchlist::get_watchlist_with_stocks(watchlist_uuid, &conn).map_err(Error::from)
+        })
+        .and_then(json_or_reject);
+
+    let rename_watchlist = warp::put2()
+        .and(path!(Uuid))
+        .and(warp::path::end())
+        .and(json_body_filter(10))
+        .and(user_filter(s))
+        .and(s.db())
+        .map(rename_watchlist)
+        .and_then(json_or_reject);
+
+    let delete_watchlist = warp::delete2()
+        .and(path!(Uuid))
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(|watchlist_uuid: Uuid, user_uuid: Uuid, conn: PooledConn| {
+            get_owned_watchlist(watchlist_uuid, user_uuid, &conn)?;
+            Watchlist::delete_watchlist(watchlist_uuid, &conn).map_err(Error::from)
+        })
+        .and_then(json_or_reject);
+
+    let add_watchlist_stock = warp::post2()
+        .and(path!(Uuid / "stock"))
+        .and(warp::path::end())
+        .and(json_body_filter(10))
+        .and(s.quote_provider())
+        .and_then(
+            |watchlist_uuid: Uuid, request: WatchlistStockRequest, quotes: Arc<QuoteProvider>| {
+                // Make sure the stock is listed before it is watched
+                trading::normalize_symbol(&request.symbol)
+                    .apply(future::result)
+                    .and_then(move |symbol| quotes.listing(&symbol))
+                    .map(move |listing| (watchlist_uuid, listing))
+                    .map_err(Error::reject)
             },
         )
+        .untuple_one()
+        .and(user_filter(s))
+        .and(s.db())
+        .map(add_watchlist_stock)
+        .and_then(json_or_reject);
+
+    let remove_watchlist_stock = warp::delete2()
+        .and(path!(Uuid / "stock" / String)) // The string is a symbol
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .map(remove_watchlist_stock)
+        .and_then(json_or_reject);
+
+    // Get the stocks on the watchlist
+    // Get the current prices for all of them in one batch
+    // Join them on their symbols.
+    let watchlist_quotes = warp::get2()
+        .and(path!(Uuid / "quotes"))
+        .and(warp::path::end())
+        .and(user_filter(s))
+        .and(s.db())
+        .and_then(
+            |watchlist_uuid: Uuid,
+             user_uuid: Uuid,
+             conn: PooledConn|
+             -> Result<WatchlistResponse, Rejection> {
+                get_owned_watchlist(watchlist_uuid, user_uuid, &conn).map_err(Error::reject)?;
+                Watchlist::get_watchlist_with_stocks(watchlist_uuid, &conn)
+                    .map_err(Error::from_reject)
+            },
+        )
+        .and(s.quote_provider())
+        .and_then(|watchlist: WatchlistResponse, quotes: Arc<QuoteProvider>| {
+            let symbols: Vec<&str> = watchlist.stocks.iter().map(|s| s.symbol.as_str()).collect();
+            quotes
+                .current_prices(&symbols)
+                .map_err(Error::reject)
+                .join(future::ok(watchlist))
+        })
+        .map(|(prices, watchlist)| watchlist_quotes(prices, watchlist))
         .map(util::json);
 
     let stock_api = path!("stock").and(
         owned_stocks
             .or(transact)
+            .or(transaction_history)
+            .or(export_transactions) // Must come before the symbol in the path is matched.
             .or(user_transactions_for_stock)
+            .or(search)
+            .or(performance_history)
+            .or(price_history)
             .or(portfolio_performance),
     );
 
-    path!("market").and(stock_api)
+    let order_api = path!("order").and(open_orders.or(place_order).or(order).or(cancel_order));
+
+    let watchlist_api = path!("watchlist").and(
+        watchlists
+            .or(create_watchlist)
+            .or(watchlist)
+            .or(rename_watchlist)
+            .or(delete_watchlist)
+            .or(add_watchlist_stock)
+            .or(remove_watchlist_stock)
+            .or(watchlist_quotes),
+    );
+
+    path!("market").and(
+        stock_api
+            .or(account)
+            .or(leaderboard)
+            .or(order_api)
+            .or(watchlist_api),
+    )
+}
+
+/// Pairs each stock with its current price by symbol, and calculates the realized and unrealized profit/loss for each.
+///
+/// Stocks without a price are reported in `unpriced_symbols` instead of being left out silently.
+///
+/// # Arguments
+/// * prices - The current prices, keyed by symbol.
+/// * stocks - The user's stocks and their transactions.
+fn portfolio_performance(
+    prices: HashMap<String, f64>,
+    stocks: Vec<UserStockResponse>,
+) -> PortfolioPerformanceResponse {
+    let mut performance = PortfolioPerformanceResponse {
+        stocks: vec![],
+        totals: PortfolioTotals::default(),
+        unpriced_symbols: vec![],
+    };
+    let mut invested = 0.0;
+
+    for stock in stocks {
+        match prices.get(&stock.stock.symbol) {
+            Some(&price) => {
+                let lots = LotSummary::fifo(&stock.transactions);
+                let unrealized = lots.unrealized(price);
+
+                invested += lots.invested;
+                performance.totals.cost_basis += lots.cost_basis;
+                performance.totals.market_value += lots.market_value(price);
+                performance.totals.realized += lots.realized;
+                performance.totals.unrealized += unrealized;
+
+                performance.stocks.push(StockAndPerfResponse {
+                    stock,
+                    performance: lots.realized + unrealized,
+                    price,
+                    quantity: lots.quantity,
+                    cost_basis: lots.cost_basis,
+                    average_price: lots.average_price(),
+                    realized: lots.realized,
+                    unrealized,
+                    percent_return: percent_return(lots.realized, unrealized, lots.invested),
+                });
+            }
+            None => {
+                info!("No current price for {}", stock.stock.symbol);
+                performance.unpriced_symbols.push(stock.stock.symbol);
+            }
+        }
+    }
+
+    performance.totals.percent_return = percent_return(
+        performance.totals.realized,
+        performance.totals.unrealized,
+        invested,
+    );
+    performance
+}
+
+/// Values the user's holdings at their current prices, and adds their cash to find their equity.
+///
+/// # Arguments
+/// * cash - The cash available to the user.
+/// * prices - The current prices, keyed by symbol.
+/// * stocks - The stocks the user currently holds.
+fn account_summary(
+    cash: f64,
+    prices: HashMap<String, f64>,
+    stocks: Vec<UserStockResponse>,
+) -> AccountResponse {
+    let mut holdings_value = 0.0;
+    let mut unpriced_symbols = vec![];
+    for stock in stocks {
+        match prices.get(&stock.stock.symbol) {
+            Some(price) => holdings_value += price * f64::from(stock.quantity_stocks_owned()),
+            None => unpriced_symbols.push(stock.stock.symbol),
+        }
+    }
+
+    AccountResponse {
+        cash,
+        holdings_value,
+        total_equity: cash + holdings_value,
+        unpriced_symbols,
+    }
 }
 
-/// Get the stock or create it if needed.
-/// Get the current transactions for the user for this stock.
-/// Check if the transactions would cause them to own negative number of stocks.
-/// Check if the user has the funds to make the transaction.
-/// Subtract the funds from the user.
-/// Record the transaction.
+/// Records a trade at the current price for the user.
+///
+/// The lookup or creation of the stock, the checks on the user's holdings and cash,
+/// and the recording of the trade all happen within a single serializable database transaction.
+/// If that transaction conflicts with a concurrent one, it is tried again a few times
+/// before the conflict is returned.
 ///
 /// # Arguments
-/// * current_price - The current price of the stock, retrieved from an async https call.
+/// * current_price - The current price of the stock, retrieved from the quote provider.
+/// * listing - The company behind the stock, retrieved from the quote provider.
 /// * request - The request struct representing a transaction.
 /// * user_uuid - The unique id of the user whose funds are being modified
 /// * conn - the connection to the database.
 fn transact(
     current_price: f64,
+    listing: StockListing,
     request: StockTransactionRequest,
     user_uuid: Uuid,
     conn: PooledConn,
 ) -> Result<StockTransaction, Error> {
-    info!("Transacting stock {:?}, at current price: {}, for user: {}", request, current_price, user_uuid);
-    let stock: QueryResult<Stock> = Stock::get_stock_by_symbol(request.symbol.clone(), &conn);
-
-    let stock = stock.or_else(|e| match e {
-        DieselError::NotFound => {
-            let new_stock = NewStock {
-                symbol: request.symbol.clone(),
-                stock_name: "VOID - This field is slated for removal".to_string(),
-            };
-            Stock::create_stock(new_stock, &conn).map_err(Error::from)
+    info!(
+        "Transacting stock {:?}, at current price: {}, for user: {}",
+        request, current_price, user_uuid
+    );
+    let trade = || {
+        conn.build_transaction()
+            .serializable()
+            .run(|| -> Result<StockTransaction, Error> {
+                let stock = trading::get_or_create_stock(&listing, &conn)?;
+                trading::execute_trade(user_uuid, &stock, request.quantity, current_price, &conn)
+            })
+    };
+    let mut attempts = 1;
+    loop {
+        match trade() {
+            Err(Error::Conflict { .. }) if attempts < TRADE_ATTEMPTS => attempts += 1,
+            result => return result,
         }
-        e => Error::from(e).apply(Err),
-    })?;
-
-    let transactions = Stock::get_user_transactions_for_stock(user_uuid, stock.uuid, &conn)
-        .map_err(Error::from)?;
-    let quantity = transactions.into_iter().fold(0, |acc, t| acc + t.quantity);
-
-    // Users can't sell more than they have.
-    if -request.quantity > quantity {
-        let err = format!(
-            "Can't sell more stocks than you have. Owned: {}, Transaction: {}",
-            quantity, request.quantity
-        );
-        Error::bad_request(err).apply(Err)?;
     }
+}
+
+/// Gets the prices that were recorded for a stock within the time bounds.
+///
+/// # Arguments
+/// * symbol - The ticker symbol of the stock.
+/// * tb - The time range to get prices for.
+/// * conn - The connection to the database.
+fn price_history(
+    symbol: &str,
+    tb: TimeBoundaries,
+    conn: &PgConnection,
+) -> Result<Vec<StockPrice>, Error> {
+    if tb.stop < tb.start {
+        return Error::bad_request("The start of the range must not be after its stop.").apply(Err);
+    }
+    StockPrice::get_price_history(
+        &trading::normalize_symbol(symbol)?,
+        tb.start.naive_utc(),
+        tb.stop.naive_utc(),
+        conn,
+    )
+    .map_err(Error::from)
+}
 
-    let new_stock_transaction = NewStockTransaction {
+/// Gets a page of the user's transactions across all of their stocks.
+///
+/// # Arguments
+/// * query - The filters, sort order and position of the page.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn transaction_history(
+    query: TransactionHistoryQuery,
+    user_uuid: Uuid,
+    conn: &PgConnection,
+) -> Result<TransactionPage, Error> {
+    let limit = query.limit.unwrap_or(DEFAULT_TRANSACTION_PAGE_SIZE);
+    if limit < 1 || limit > MAX_TRANSACTION_PAGE_SIZE {
+        return Error::bad_request(format!(
+            "The limit must be between 1 and {}.",
+            MAX_TRANSACTION_PAGE_SIZE
+        ))
+        .apply(Err);
+    }
+    if let (Some(start), Some(stop)) = (query.start, query.stop) {
+        if stop < start {
+            return Error::bad_request("The start of the range must not be after its stop.")
+                .apply(Err);
+        }
+    }
+
+    let filter = TransactionFilter {
+        start: query.start.map(|start| start.naive_utc()),
+        stop: query.stop.map(|stop| stop.naive_utc()),
+        side: query.side,
+        symbol: query
+            .symbol
+            .as_ref()
+            .map(|symbol| trading::normalize_symbol(symbol))
+            .transpose()?,
+        sort: query.sort,
+        after: query
+            .cursor
+            .as_ref()
+            .map(|cursor| decode_cursor(cursor))
+            .transpose()?,
+    };
+
+    // Ask for one more than the page holds, to find out if there is another page.
+    let mut transactions = Stock::get_user_transactions(user_uuid, &filter, limit + 1, conn)?;
+    let next_cursor = if transactions.len() as i64 > limit {
+        transactions.truncate(limit as usize);
+        transactions.last().map(|last| {
+            encode_cursor(TransactionCursor {
+                record_time: last.transaction.record_time,
+                uuid: last.transaction.uuid,
+            })
+        })
+    } else {
+        None
+    };
+
+    Ok(TransactionPage {
+        transactions,
+        next_cursor,
+    })
+}
+
+/// Writes a cursor as the microseconds since the epoch that the transaction was recorded at, and the transaction's uuid.
+fn encode_cursor(cursor: TransactionCursor) -> String {
+    let micros = cursor.record_time.timestamp() * 1_000_000
+        + i64::from(cursor.record_time.timestamp_subsec_micros());
+    format!("{}_{}", micros, cursor.uuid)
+}
+
+/// Reads a cursor written by `encode_cursor`.
+fn decode_cursor(cursor: &str) -> Result<TransactionCursor, Error> {
+    let invalid = || Error::bad_request("The cursor is not valid.");
+    let mut parts = cursor.splitn(2, '_');
+    let micros: i64 = parts
+        .next()
+        .and_then(|micros| micros.parse().ok())
+        .filter(|micros| *micros >= 0)
+        .ok_or_else(invalid)?;
+    let uuid = parts
+        .next()
+        .and_then(|uuid| Uuid::parse_str(uuid).ok())
+        .ok_or_else(invalid)?;
+    let record_time =
+        NaiveDateTime::from_timestamp_opt(micros / 1_000_000, (micros % 1_000_000) as u32 * 1_000)
+            .ok_or_else(invalid)?;
+    Ok(TransactionCursor { record_time, uuid })
+}
+
+/// Finds listed stocks whose symbol starts with, or whose company name contains, the query.
+///
+/// # Arguments
+/// * query - The text to search for.
+/// * conn - The connection to the database.
+fn search_listings(query: &str, conn: &PgConnection) -> Result<Vec<StockListing>, Error> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+    StockListing::search(query, SEARCH_RESULT_LIMIT, conn).map_err(Error::from)
+}
+
+/// Places a limit or stop order for the user.
+///
+/// # Arguments
+/// * listing - The company behind the stock, retrieved from the quote provider.
+/// * request - The request struct representing the order.
+/// * user_uuid - The unique id of the user placing the order.
+/// * conn - the connection to the database.
+fn place_order(
+    listing: StockListing,
+    request: NewOrderRequest,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<UserOrderResponse, Error> {
+    info!("Placing order {:?} for user: {}", request, user_uuid);
+    if request.quantity == 0 {
+        return Error::bad_request("Orders must exchange at least one stock.").apply(Err);
+    }
+    if request.trigger_price <= 0.0 {
+        return Error::bad_request("Orders must have a positive trigger price.").apply(Err);
+    }
+
+    let stock = trading::get_or_create_stock(&listing, &conn)?;
+    let new_order = NewOrder {
         user_uuid,
         stock_uuid: stock.uuid,
+        kind: request.kind,
         quantity: request.quantity,
-        price_of_stock_at_time_of_trading: current_price,
-        record_time: Utc::now().naive_utc(),
+        trigger_price: request.trigger_price,
+        created_at: Utc::now().naive_utc(),
     };
+    let order = Order::create_order(new_order, &conn)?;
+    Ok(UserOrderResponse { order, stock })
+}
 
-    // Record that the stock was purchased for the user
-    Stock::create_transaction(new_stock_transaction, &conn).map_err(Error::from)
+/// Gets an order after checking that it belongs to the user.
+///
+/// # Arguments
+/// * order_uuid - The uuid of the order.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn get_order(
+    order_uuid: Uuid,
+    user_uuid: Uuid,
+    conn: &PgConnection,
+) -> Result<UserOrderResponse, Error> {
+    let order = Order::get_order(order_uuid, conn)?;
+    if order.order.user_uuid != user_uuid {
+        Err(Error::not_authorized("User UUIDs do not match"))
+    } else {
+        Ok(order)
+    }
 }
 
-fn get_current_price(
-    stock_symbol: &str,
-    client: &HttpsClient,
-) -> impl Future<Item = f64, Error = Error> {
-    info!("Getting current price for stock: {}", stock_symbol);
-    let stock_symbol_copy = stock_symbol.to_string();
+/// Cancels an open order after checking that it belongs to the user.
+///
+/// # Arguments
+/// * order_uuid - The uuid of the order to be cancelled.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn cancel_order(
+    order_uuid: Uuid,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<UserOrderResponse, Error> {
+    let order = get_order(order_uuid, user_uuid, &conn)?;
+    if order.order.status != OrderStatus::Open {
+        return Error::bad_request("Only open orders can be cancelled.").apply(Err);
+    }
+    let cancelled = Order::close_order(order_uuid, OrderStatus::Cancelled, None, &conn)?;
+    Ok(UserOrderResponse {
+        order: cancelled,
+        stock: order.stock,
+    })
+}
 
-    let uri = format!(
-        "https://api.iextrading.com/1.0/stock/{}/price",
-        stock_symbol
-    )
-    .parse::<Uri>()
-    .map_err(|e| Error::bad_request(format!("{:?}", e)));
-
-    match uri {
-        Ok(uri) => {
-            let uri_string = uri.to_string(); // create this here, so it can be moved into the closure.
-            client
-                .get(uri.clone())
-                .and_then(|res| {
-                    res.into_body().concat2() // Await the whole body
-                })
-                .map_err(move |_| {
-                    Error::dependent_connection_failed(
-                        uri.to_string(),
-                        format!("Could not get current price for {}.", stock_symbol_copy),
-                    )
-                })
-                .and_then(move |chunk: Chunk| -> Result<f64, Error> {
-                    let v = chunk.to_vec();
-                    let body = String::from_utf8_lossy(&v).to_string();
-                    body.parse::<f64>().map_err(move |_| -> Error {
-                        crate::error::Error::internal_server_error(format!(
-                            "Could not parse body of dependent connection: {}, body: {}",
-                            uri_string, body
-                        ))
-                    })
-                })
-                .apply(Either::A)
+/// Pairs each stock on a watchlist with its current price by symbol.
+///
+/// # Arguments
+/// * prices - The current prices, keyed by symbol.
+/// * watchlist - The watchlist and its stocks.
+fn watchlist_quotes(
+    prices: HashMap<String, f64>,
+    watchlist: WatchlistResponse,
+) -> WatchlistQuotesResponse {
+    let mut quotes = vec![];
+    let mut unpriced_symbols = vec![];
+    for stock in watchlist.stocks {
+        match prices.get(&stock.symbol) {
+            Some(&price) => quotes.push(StockQuote { stock, price }),
+            None => unpriced_symbols.push(stock.symbol),
         }
-        Err(e) => e.apply(futures::future::err).apply(Either::B),
+    }
+
+    WatchlistQuotesResponse {
+        watchlist: watchlist.watchlist,
+        quotes,
+        unpriced_symbols,
     }
 }
 
-/// Get the current prices for a set of stocks.
-fn get_current_prices(
-    stock_symbols: &[&str],
-    client: &HttpsClient,
-) -> Either<
-    impl Future<Item = Vec<f64>, Error = Error>,
-    impl Future<Item = Vec<f64>, Error = Error>
-    >
-{
-    if stock_symbols.len() == 0 {
-        info!("Can't get stock prices, because no stocks were provided");
-        future::ok::<Vec<f64>, Error>(vec![]).apply(future::Either::A)
+/// Trims a watchlist name, making sure something is left.
+fn watchlist_name(name: &str) -> Result<String, Error> {
+    let name = name.trim();
+    if name.is_empty() {
+        Error::bad_request("Watchlists must have a name.").apply(Err)
     } else {
-        info!("Getting current prices for multiple stocks: {:?}", stock_symbols);
-        let uri: Uri = format!(
-            "https://api.iextrading.com/1.0/stock/market/batch?symbols={}&types=price",
-            stock_symbols.join(",")
-        )
-            .parse()
-            .unwrap();
-        info!("Getting current prices for: {}", uri);
-
-        // handle json in the form: {"AAPL":{"price":170.67},"FB":{"price":165.465}}
-        #[derive(Serialize, Deserialize, Debug)]
-        struct Price {
-            price: f64,
+        Ok(name.to_string())
+    }
+}
+
+/// Users can't have two watchlists with the same name.
+fn duplicate_watchlist_name(name: &str, error: DieselError) -> Error {
+    match error {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Error::bad_request(format!("You already have a watchlist named '{}'.", name))
         }
-        client
-            .get(uri.clone())
-            .and_then(|res| {
-                res.into_body().concat2() // Await the whole body
-            })
-            .map_err(move |_| {
-                Error::dependent_connection_failed(uri.to_string(), "Could not get current stocks.")
-            })
-            .and_then(|chunk: Chunk| {
-                let v = chunk.to_vec();
-                let body = String::from_utf8_lossy(&v).to_string();
-                serde_json::from_str::<HashMap<String, Price>>(&body)
-                    .map(|r| {
-                        info!("Got current prices: {:#?}", r);
-                        r.values().map(|v| v.price).collect()
-                    })
-                    .map_err(|_| {
-                        crate::error::Error::internal_server_error(
-                            "Could not get current prices"
-                        )
-                    })
-            })
-            .apply(Either::B)
+        e => Error::from(e),
+    }
+}
+
+/// Creates a watchlist for the user.
+///
+/// # Arguments
+/// * request - The request struct containing the watchlist's name.
+/// * user_uuid - The unique id of the user creating the watchlist.
+/// * conn - The connection to the database.
+fn create_watchlist(
+    request: WatchlistNameRequest,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<Watchlist, Error> {
+    let name = watchlist_name(&request.name)?;
+    let new_watchlist = NewWatchlist {
+        user_uuid,
+        name: name.clone(),
+        created_at: Utc::now().naive_utc(),
+    };
+    Watchlist::create_watchlist(new_watchlist, &conn)
+        .map_err(|e| duplicate_watchlist_name(&name, e))
+}
+
+/// Renames a watchlist after checking that it belongs to the user.
+///
+/// # Arguments
+/// * watchlist_uuid - The uuid of the watchlist to be renamed.
+/// * request - The request struct containing the watchlist's new name.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn rename_watchlist(
+    watchlist_uuid: Uuid,
+    request: WatchlistNameRequest,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<Watchlist, Error> {
+    let name = watchlist_name(&request.name)?;
+    get_owned_watchlist(watchlist_uuid, user_uuid, &conn)?;
+    Watchlist::rename_watchlist(watchlist_uuid, name.clone(), &conn)
+        .map_err(|e| duplicate_watchlist_name(&name, e))
+}
+
+/// Adds a stock to a watchlist after checking that it belongs to the user.
+///
+/// # Arguments
+/// * watchlist_uuid - The uuid of the watchlist.
+/// * listing - The company behind the stock, retrieved from the quote provider.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn add_watchlist_stock(
+    watchlist_uuid: Uuid,
+    listing: StockListing,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<WatchlistResponse, Error> {
+    get_owned_watchlist(watchlist_uuid, user_uuid, &conn)?;
+    let stock = trading::get_or_create_stock(&listing, &conn)?;
+    let entry = WatchlistEntry {
+        watchlist_uuid,
+        stock_uuid: stock.uuid,
+        added_at: Utc::now().naive_utc(),
+    };
+    Watchlist::add_stock(entry, &conn)?;
+    Watchlist::get_watchlist_with_stocks(watchlist_uuid, &conn).map_err(Error::from)
+}
+
+/// Removes a stock from a watchlist after checking that it belongs to the user.
+///
+/// # Arguments
+/// * watchlist_uuid - The uuid of the watchlist.
+/// * symbol - The ticker symbol of the stock to remove.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn remove_watchlist_stock(
+    watchlist_uuid: Uuid,
+    symbol: String,
+    user_uuid: Uuid,
+    conn: PooledConn,
+) -> Result<WatchlistResponse, Error> {
+    get_owned_watchlist(watchlist_uuid, user_uuid, &conn)?;
+    let stock = Stock::get_stock_by_symbol(trading::normalize_symbol(&symbol)?, &conn)?;
+    if Watchlist::remove_stock(watchlist_uuid, stock.uuid, &conn)? == 0 {
+        return Err(Error::not_found("WatchlistEntry"));
+    }
+    Watchlist::get_watchlist_with_stocks(watchlist_uuid, &conn).map_err(Error::from)
+}
+
+/// Gets a watchlist after checking that it belongs to the user.
+///
+/// # Arguments
+/// * watchlist_uuid - The uuid of the watchlist.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn get_owned_watchlist(
+    watchlist_uuid: Uuid,
+    user_uuid: Uuid,
+    conn: &PgConnection,
+) -> Result<Watchlist, Error> {
+    let watchlist = Watchlist::get_watchlist(watchlist_uuid, conn)?;
+    if watchlist.user_uuid != user_uuid {
+        Err(Error::not_authorized("User UUIDs do not match"))
+    } else {
+        Ok(watchlist)
     }
 }
 
 #[cfg(test)]
-mod integration {
+mod test {
     use super::*;
-    use futures::future;
-    use hyper::Client;
-    use hyper_tls::HttpsConnector;
-    use tokio;
+
+    fn user_stock(symbol: &str, bought_at: f64) -> UserStockResponse {
+        let stock_uuid = Uuid::new_v4();
+        UserStockResponse {
+            stock: Stock {
+                uuid: stock_uuid,
+                symbol: symbol.to_string(),
+                stock_name: symbol.to_string(),
+                exchange: None,
+            },
+            transactions: vec![StockTransaction {
+                uuid: Uuid::new_v4(),
+                user_uuid: Uuid::new_v4(),
+                stock_uuid,
+                quantity: 1,
+                price_of_stock_at_time_of_trading: bought_at,
+                record_time: Utc::now().naive_utc(),
+            }],
+        }
+    }
+
+    #[test]
+    fn cursors_survive_a_round_trip() {
+        let cursor = TransactionCursor {
+            record_time: NaiveDateTime::from_timestamp(1_556_000_000, 123_456_000),
+            uuid: Uuid::new_v4(),
+        };
+        assert_eq!(decode_cursor(&encode_cursor(cursor)), Ok(cursor));
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        assert!(decode_cursor("").is_err());
+        assert!(decode_cursor("1556000000123456").is_err());
+        assert!(decode_cursor("-1_936DA01F-9ABD-4D9D-80C7-02AF85C822A8").is_err());
+        assert!(decode_cursor("1556000000123456_not-a-uuid").is_err());
+    }
 
     #[test]
-    fn can_get_current_price() {
-        // This test assumes that apple's stock price is above 1 dollar per share.
-        // A fair assumption, but it may not always be true :/.
-        tokio::run(future::lazy(|| {
-            let https = HttpsConnector::new(4).unwrap();
-            let client = Client::builder().build::<_, hyper::Body>(https);
-            get_current_price("aapl", &client)
-                .map(|price| assert!(price > 0.0, "Aapl should have a positive share price."))
-                .map_err(|_| panic!("Could not get current price"))
-        }));
+    fn performance_pairs_prices_by_symbol() {
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), 200.0);
+        prices.insert("FB".to_string(), 100.0);
+        let stocks = vec![user_stock("FB", 50.0), user_stock("AAPL", 150.0)];
+
+        let performance = portfolio_performance(prices, stocks);
+
+        assert!(performance.unpriced_symbols.is_empty());
+        for perf in performance.stocks {
+            match perf.stock.stock.symbol.as_str() {
+                "AAPL" => assert_eq!(perf.price, 200.0),
+                "FB" => assert_eq!(perf.price, 100.0),
+                _ => panic!("Unexpected stock"),
+            }
+            assert_eq!(perf.performance, 50.0);
+        }
     }
 
     #[test]
-    fn can_get_multiple_current_price() {
-        // This test assumes that apple's stock price is above 1 dollar per share.
-        // A fair assumption, but it may not always be true :/.
-        tokio::run(future::lazy(|| {
-            let https = HttpsConnector::new(4).unwrap();
-            let client = Client::builder().build::<_, hyper::Body>(https);
-            get_current_prices(&["aapl", "fb"], &client)
-                .map(|prices| assert_eq!(prices.len(), 2))
-                .map_err(|_| panic!("Could not get current prices"))
-        }));
+    fn performance_reports_missing_prices() {
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), 200.0);
+        let stocks = vec![user_stock("AAPL", 150.0), user_stock("DELISTED", 10.0)];
+
+        let performance = portfolio_performance(prices, stocks);
+
+        assert_eq!(performance.stocks.len(), 1);
+        assert_eq!(performance.unpriced_symbols, vec!["DELISTED".to_string()]);
+    }
+
+    #[test]
+    fn performance_rolls_up_holdings() {
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), 200.0);
+        prices.insert("FB".to_string(), 100.0);
+        let stocks = vec![user_stock("FB", 50.0), user_stock("AAPL", 150.0)];
+
+        let performance = portfolio_performance(prices, stocks);
+
+        assert_eq!(performance.totals.cost_basis, 200.0);
+        assert_eq!(performance.totals.market_value, 300.0);
+        assert_eq!(performance.totals.realized, 0.0);
+        assert_eq!(performance.totals.unrealized, 100.0);
+        assert_eq!(performance.totals.percent_return, 50.0);
+    }
+
+    #[test]
+    fn account_adds_cash_to_holdings() {
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), 200.0);
+        let stocks = vec![user_stock("AAPL", 150.0), user_stock("DELISTED", 10.0)];
+
+        let account = account_summary(1_000.0, prices, stocks);
+
+        assert_eq!(account.holdings_value, 200.0);
+        assert_eq!(account.total_equity, 1_200.0);
+        assert_eq!(account.unpriced_symbols, vec!["DELISTED".to_string()]);
     }
 }
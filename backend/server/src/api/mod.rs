@@ -59,6 +59,7 @@ pub fn routes(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rej
             "Access-Control-Allow-Origin",
             "content-type",
             "Authorization",
+            "If-Match",
         ])
         .allow_any_origin()
         .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
@@ -112,7 +113,30 @@ mod integration_test {
 
     mod events {
         use super::*;
-        use crate::api::calendar::TimeBoundaries;
+        use crate::{
+            api::{
+                auth::get_jwt_for,
+                calendar::{
+                    BusyInterval, CalendarFeedResponse, CalendarView, InvitationReply,
+                    InviteRequest, InvitedEvent, OccurrenceChangeRequest, OccurrenceQuery,
+                    ReminderRequest, SearchResult, ShareRequest, TimeBoundaries, ZonedEvent,
+                },
+            },
+            notifier::LogNotifier,
+            reminder_scheduler::send_reminders,
+            testing_fixtures::util::deserialize_string,
+            trash_purger::purge_trash,
+        };
+        use db::{
+            calendar_share::{CalendarShare, SharePermission},
+            event::{
+                EventPatch, ImportExportEvent, ImportSummary, OccurrenceChange, RecurrenceScope,
+            },
+            invitation::InvitationStatus,
+            reminder::{EventReminder, FiredReminder},
+        };
+        use futures::Future;
+        use std::sync::Arc;
 
         #[test]
         fn create_event() {
@@ -128,6 +152,9 @@ mod integration_test {
                     text: "".to_string(),
                     start_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
                     stop_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(2),
+                    rrule: None,
+                    time_zone: None,
+                    all_day: false,
                 };
 
                 let resp = warp::test::request()
@@ -160,6 +187,9 @@ mod integration_test {
                     text: "".to_string(),
                     start_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
                     stop_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(2),
+                    rrule: None,
+                    time_zone: None,
+                    all_day: false,
                 };
 
                 let start = chrono::Utc::now();
@@ -210,6 +240,9 @@ mod integration_test {
                     text: "".to_string(),
                     start_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
                     stop_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(2),
+                    rrule: None,
+                    time_zone: None,
+                    all_day: false,
                 };
 
                 let resp = warp::test::request()
@@ -230,6 +263,9 @@ mod integration_test {
                     text: "lol".to_string(),
                     start_at: event.start_at,
                     stop_at: event.stop_at,
+                    rrule: None,
+                    time_zone: None,
+                    all_day: None,
                 };
 
                 let resp = warp::test::request()
@@ -263,6 +299,9 @@ mod integration_test {
                     text: "".to_string(),
                     start_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(1),
                     stop_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(2),
+                    rrule: None,
+                    time_zone: None,
+                    all_day: false,
                 };
 
                 let start = chrono::Utc::now();
@@ -309,15 +348,1713 @@ mod integration_test {
                 assert_eq!(events.len(), 0);
             });
         }
+
+        #[test]
+        fn recurring_events_expand_and_can_be_edited() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let first = chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(9, 0, 0);
+                let week = chrono::Duration::weeks(1);
+                let request = NewEventRequest {
+                    title: "Standup".to_string(),
+                    text: "".to_string(),
+                    start_at: first,
+                    stop_at: first + chrono::Duration::minutes(15),
+                    rrule: Some("freq=weekly;count=4".to_string()),
+                    time_zone: None,
+                    all_day: false,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200);
+                let series: Event = deserialize(resp);
+                assert_eq!(series.rrule, Some("FREQ=WEEKLY;COUNT=4".to_string()));
+
+                let utc =
+                    |time: chrono::NaiveDateTime| chrono::DateTime::from_utc(time, chrono::Utc);
+                let tb = TimeBoundaries {
+                    start: utc(first - chrono::Duration::days(1)),
+                    stop: utc(first + week * 8),
+                };
+                let get_events = || {
+                    let resp = warp::test::request()
+                        .method("GET")
+                        .path(&format!(
+                            "/api/calendar/event/events?{}",
+                            serde_urlencoded::to_string(&tb).unwrap()
+                        ))
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    deserialize::<Vec<Event>>(resp)
+                };
+
+                let events = get_events();
+                assert_eq!(events.len(), 4);
+                assert_eq!(events[3].start_at, first + week * 3);
+                assert_eq!(events[3].recurrence_id, Some(first + week * 3));
+
+                // Move the second occurrence by an hour.
+                let request = OccurrenceChangeRequest {
+                    occurrence: first + week,
+                    scope: RecurrenceScope::This,
+                    change: OccurrenceChange {
+                        title: "Late standup".to_string(),
+                        text: "".to_string(),
+                        start_at: first + week + chrono::Duration::hours(1),
+                        stop_at: first + week + chrono::Duration::minutes(75),
+                        rrule: None,
+                    },
+                };
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/calendar/event/{}/occurrence", series.uuid))
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+
+                let events = get_events();
+                assert_eq!(events.len(), 4);
+                assert_eq!(&events[1].title, "Late standup");
+                assert_eq!(events[1].recurrence_parent_uuid, Some(series.uuid));
+
+                // Cancel the third occurrence and every one after it.
+                let query = OccurrenceQuery {
+                    occurrence: first + week * 2,
+                    scope: RecurrenceScope::Following,
+                };
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!(
+                        "/api/calendar/event/{}/occurrence?{}",
+                        series.uuid,
+                        serde_urlencoded::to_string(&query).unwrap()
+                    ))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+
+                let titles: Vec<String> = get_events().into_iter().map(|e| e.title).collect();
+                assert_eq!(titles, vec!["Standup", "Late standup"]);
+
+                // Times that aren't occurrences can't be changed.
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!(
+                        "/api/calendar/event/{}/occurrence?{}",
+                        series.uuid,
+                        serde_urlencoded::to_string(&query).unwrap()
+                    ))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 404);
+            });
+        }
+
+        #[test]
+        fn replaced_occurrences_can_be_replaced_again() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let first = chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(9, 0, 0);
+                let week = chrono::Duration::weeks(1);
+                let request = NewEventRequest {
+                    title: "Standup".to_string(),
+                    text: "".to_string(),
+                    start_at: first,
+                    stop_at: first + chrono::Duration::minutes(15),
+                    rrule: Some("FREQ=WEEKLY;COUNT=4".to_string()),
+                    time_zone: None,
+                    all_day: false,
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let series: Event = deserialize(resp);
+
+                let change = |title: &str, occurrence: chrono::NaiveDateTime, scope| {
+                    let start_at = if scope == RecurrenceScope::This {
+                        occurrence + chrono::Duration::hours(1)
+                    } else {
+                        occurrence
+                    };
+                    let request = OccurrenceChangeRequest {
+                        occurrence,
+                        scope,
+                        change: OccurrenceChange {
+                            title: title.to_string(),
+                            text: "".to_string(),
+                            start_at,
+                            stop_at: start_at + chrono::Duration::minutes(15),
+                            rrule: None,
+                        },
+                    };
+                    warp::test::request()
+                        .method("PUT")
+                        .path(&format!("/api/calendar/event/{}/occurrence", series.uuid))
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+
+                let resp = change("Late standup", first + week, RecurrenceScope::This);
+                assert_eq!(resp.status(), 200);
+                let replacement: Event = deserialize(resp);
+
+                // Changing the whole series moves the replacement to the trash,
+                // so the second occurrence can be replaced again.
+                let resp = change("Daily standup", first, RecurrenceScope::Following);
+                assert_eq!(resp.status(), 200);
+                let resp = change("Later standup", first + week, RecurrenceScope::This);
+                assert_eq!(resp.status(), 200);
+
+                // The replacement in the trash can't come back while another replaces its occurrence.
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/calendar/event/{}/restore", replacement.uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 409);
+            });
+        }
+
+        #[test]
+        fn merging_imports_keeps_existing_events() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let start_at = chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(9, 0, 0);
+                let event = |title: &str, external_id: Option<&str>| ImportExportEvent {
+                    title: title.to_string(),
+                    text: "".to_string(),
+                    start_at,
+                    stop_at: start_at + chrono::Duration::hours(1),
+                    rrule: None,
+                    exdates: vec![],
+                    external_id: external_id.map(str::to_string),
+                    time_zone: None,
+                    all_day: false,
+                };
+                let import = |events: &Vec<ImportExportEvent>, query: &str| {
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path(&format!("/api/calendar/event/events/import?{}", query))
+                        .json(events)
+                        .header("content-length", "5000")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    deserialize::<ImportSummary>(resp)
+                };
+
+                let events = vec![event("Standup", Some("standup")), event("Lunch", None)];
+                let summary = import(&events, "");
+                assert_eq!(summary.created, 2);
+
+                // A partial file only touches the events in it.
+                let partial = vec![event("Standup, moved", Some("standup"))];
+                let summary = import(&partial, "mode=merge&dry_run=true");
+                assert_eq!(summary.updated, 1);
+                assert_eq!(summary.deleted, 0);
+                let summary = import(&partial, "mode=merge");
+                assert_eq!(summary.updated, 1);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/event/events/export")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let mut titles: Vec<String> = deserialize::<Vec<ImportExportEvent>>(resp)
+                    .into_iter()
+                    .map(|e| e.title)
+                    .collect();
+                titles.sort();
+                assert_eq!(titles, vec!["Lunch", "Standup, moved"]);
+
+                // Same title and start, but different contents and no id to tell them apart.
+                let mut changed_lunch = event("Lunch", None);
+                changed_lunch.text = "Somewhere else".to_string();
+                let summary = import(&vec![changed_lunch, event("Lunch", None)], "mode=merge");
+                assert_eq!(summary.conflicting, 1);
+                assert_eq!(summary.unchanged, 1);
+                assert_eq!(summary.created, 0);
+
+                // Imported events are checked like the ones created one at a time.
+                let mut backwards = event("Backwards", None);
+                backwards.stop_at = start_at - chrono::Duration::hours(1);
+                let mut lost = event("Lost", None);
+                lost.time_zone = Some("Mars/Olympus".to_string());
+                for events in &[vec![backwards], vec![lost]] {
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/calendar/event/events/import")
+                        .json(events)
+                        .header("content-length", "5000")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 400);
+                }
+            });
+        }
+
+        #[test]
+        fn events_import_export_and_subscribe_as_ics() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let ics = "BEGIN:VCALENDAR\r\n\
+                           VERSION:2.0\r\n\
+                           BEGIN:VEVENT\r\n\
+                           UID:lunch@example.com\r\n\
+                           SUMMARY:Lunch\\, with Sam\r\n\
+                           DESCRIPTION:Bring the report\r\n\
+                           DTSTART;TZID=Europe/Paris:20190506T120000\r\n\
+                           DTEND;TZID=Europe/Paris:20190506T130000\r\n\
+                           END:VEVENT\r\n\
+                           END:VCALENDAR\r\n";
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event/events/import/ics")
+                    .body(ics)
+                    .header("content-length", ics.len().to_string())
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/event/events/export/ics")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                assert_eq!(resp.headers()["content-type"], "text/calendar; charset=utf-8");
+                let exported = deserialize_string(resp);
+                // Paris is two hours ahead of UTC in May.
+                assert!(exported.contains("DTSTART:20190506T100000Z\r\n"));
+                assert!(exported.contains("SUMMARY:Lunch\\, with Sam\r\n"));
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/feed")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let feed: CalendarFeedResponse = deserialize(resp);
+
+                // Calendar apps read the feed without logging in.
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(&feed.path)
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                assert!(deserialize_string(resp).contains("SUMMARY:Lunch"));
+
+                // Issuing a new token stops the old one from working.
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/feed")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(&feed.path)
+                    .reply(&filter);
+                assert_eq!(resp.status(), 404);
+            });
+        }
+
+        #[test]
+        fn importing_the_same_ics_again_replaces_it() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let ics = "BEGIN:VCALENDAR\r\n\
+                           VERSION:2.0\r\n\
+                           BEGIN:VEVENT\r\n\
+                           UID:lunch@example.com\r\n\
+                           SUMMARY:Lunch\r\n\
+                           DTSTART:20190506T120000Z\r\n\
+                           DTEND:20190506T130000Z\r\n\
+                           END:VEVENT\r\n\
+                           END:VCALENDAR\r\n";
+                let import = || {
+                    warp::test::request()
+                        .method("POST")
+                        .path("/api/calendar/event/events/import/ics")
+                        .body(ics)
+                        .header("content-length", ics.len().to_string())
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+                let get = |path: &str| -> Vec<Event> {
+                    let resp = warp::test::request()
+                        .method("GET")
+                        .path(path)
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    deserialize(resp)
+                };
+
+                // The events moved to the trash by the second import keep their external ids.
+                assert_eq!(import().status(), 200);
+                let resp = import();
+                assert_eq!(resp.status(), 200);
+                let summary: ImportSummary = deserialize(resp);
+                assert_eq!(summary.deleted, 1);
+                assert_eq!(summary.created, 1);
+                let calendar = get("/api/calendar/event/events?start=2019-05-06&stop=2019-05-07");
+                assert_eq!(calendar.len(), 1);
+                let trash = get("/api/calendar/event/trash");
+                assert_eq!(trash.len(), 1);
+                assert_eq!(trash[0].external_id, calendar[0].external_id);
+
+                // So the trashed copy can't be restored next to the live one.
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/calendar/event/{}/restore", trash[0].uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 409);
+            });
+        }
+
+        #[test]
+        fn events_overlapping_the_range_are_included() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path("/api/user/time_zone")
+                    .json(&"America/New_York")
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+
+                let at = |day: u32, hour: u32| {
+                    chrono::NaiveDate::from_ymd(2019, 5, day).and_hms(hour, 0, 0)
+                };
+                let requests = vec![
+                    // Still running when the range starts.
+                    ("Conference", at(5, 14), at(7, 20), false),
+                    // Starts exactly when the range starts.
+                    ("Breakfast", at(6, 4), at(6, 5), false),
+                    // Covers the 6th in New York, though it starts at 4am UTC.
+                    ("Holiday", at(6, 9), at(6, 10), true),
+                    // Ends exactly when the range starts.
+                    ("Late night", at(6, 2), at(6, 4), false),
+                    ("Next day", at(7, 4), at(7, 5), false),
+                ];
+                for (title, start_at, stop_at, all_day) in requests {
+                    let request = NewEventRequest {
+                        title: title.to_string(),
+                        text: "".to_string(),
+                        start_at,
+                        stop_at,
+                        rrule: None,
+                        time_zone: None,
+                        all_day,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/calendar/event")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    let event: ZonedEvent = deserialize(resp);
+                    if all_day {
+                        assert_eq!(event.event.start_at, at(6, 0));
+                        assert_eq!(event.event.stop_at, at(7, 0));
+                        assert_eq!(event.local_start_at.to_rfc3339(), "2019-05-06T00:00:00-04:00");
+                    }
+                }
+
+                // The 6th of May, in New York.
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/event/events?start=2019-05-06&stop=2019-05-07")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let events: Vec<ZonedEvent> = deserialize(resp);
+                let titles: Vec<&str> = events.iter().map(|e| e.event.title.as_str()).collect();
+                assert_eq!(titles, vec!["Conference", "Holiday", "Breakfast"]);
+            });
+        }
+
+        #[test]
+        fn events_are_shown_in_the_users_time_zone() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path("/api/user/time_zone")
+                    .json(&"Mars/Olympus")
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 400);
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path("/api/user/time_zone")
+                    .json(&"America/New_York")
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let user: User = deserialize(resp);
+                assert_eq!(user.time_zone, "America/New_York");
+
+                // 9am in New York, weekly across the start of daylight saving time.
+                let first = chrono::NaiveDate::from_ymd(2019, 3, 4).and_hms(14, 0, 0);
+                let request = NewEventRequest {
+                    title: "Standup".to_string(),
+                    text: "".to_string(),
+                    start_at: first,
+                    stop_at: first + chrono::Duration::minutes(15),
+                    rrule: Some("FREQ=WEEKLY;COUNT=3".to_string()),
+                    time_zone: None,
+                    all_day: false,
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let series: ZonedEvent = deserialize(resp);
+                assert_eq!(series.event.time_zone, Some("America/New_York".to_string()));
+
+                // Dates without an offset are midnight in the user's zone.
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/event/events?start=2019-03-01&stop=2019-03-31")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let events: Vec<ZonedEvent> = deserialize(resp);
+                let local_starts: Vec<String> = events
+                    .iter()
+                    .map(|e| e.local_start_at.to_rfc3339())
+                    .collect();
+                assert_eq!(
+                    local_starts,
+                    vec![
+                        "2019-03-04T09:00:00-05:00",
+                        "2019-03-11T09:00:00-04:00",
+                        "2019-03-18T09:00:00-04:00",
+                    ]
+                );
+                assert_eq!(events[1].event.start_at, first + chrono::Duration::hours(167));
+            });
+        }
+
+        #[test]
+        fn shared_calendars_and_invitations() {
+            setup_warp(|fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+                let other_jwt = get_jwt_for(&s, "someone else");
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/user")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, other_jwt))
+                    .reply(&filter);
+                let other: User = deserialize(resp);
+
+                let create = |title: &str, jwt: &str| -> ZonedEvent {
+                    let start_at = chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(9, 0, 0);
+                    let request = NewEventRequest {
+                        title: title.to_string(),
+                        text: "".to_string(),
+                        start_at,
+                        stop_at: start_at + chrono::Duration::hours(1),
+                        rrule: None,
+                        time_zone: None,
+                        all_day: false,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/calendar/event")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    deserialize(resp)
+                };
+                let titles = |jwt: &str| -> Vec<String> {
+                    let resp = warp::test::request()
+                        .method("GET")
+                        .path("/api/calendar/event/events?start=2019-05-01&stop=2019-06-01")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    let events: Vec<ZonedEvent> = deserialize(resp);
+                    events.into_iter().map(|e| e.event.title).collect()
+                };
+                let share = |permission: SharePermission| {
+                    let request = ShareRequest {
+                        user_uuid: other.uuid,
+                        permission,
+                    };
+                    let resp = warp::test::request()
+                        .method("PUT")
+                        .path("/api/calendar/share")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                };
+                let delete = |event: &ZonedEvent, jwt: &str| {
+                    warp::test::request()
+                        .method("DELETE")
+                        .path(&format!("/api/calendar/event/{}", event.event.uuid))
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                        .status()
+                };
+
+                let planning = create("Planning", &jwt);
+                assert!(titles(&other_jwt).is_empty());
+
+                // A calendar shared for reading can be seen, but not changed.
+                share(SharePermission::Read);
+                assert_eq!(titles(&other_jwt), vec!["Planning".to_string()]);
+                assert_eq!(delete(&planning, &other_jwt), 403);
+
+                share(SharePermission::Write);
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/share/received")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, other_jwt))
+                    .reply(&filter);
+                let shares: Vec<CalendarShare> = deserialize(resp);
+                assert_eq!(shares.len(), 1);
+                assert_eq!(shares[0].permission, SharePermission::Write);
+                assert_eq!(delete(&planning, &other_jwt), 200);
+                assert!(titles(&jwt).is_empty());
+
+                // Invitees see the event in their calendar until they decline it.
+                let lunch = create("Lunch", &other_jwt);
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path(&format!("/api/calendar/event/{}/invitee", lunch.event.uuid))
+                    .json(&InviteRequest { user_uuid: fixture.user.uuid })
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, other_jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                assert_eq!(titles(&jwt), vec!["Lunch".to_string()]);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/invitation")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                let invitations: Vec<InvitedEvent> = deserialize(resp);
+                assert_eq!(invitations.len(), 1);
+                assert_eq!(invitations[0].invitation.status, InvitationStatus::Pending);
+
+                // Invitees can't change the event.
+                assert_eq!(delete(&lunch, &jwt), 403);
+
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path(&format!("/api/calendar/event/{}/invitation", lunch.event.uuid))
+                    .json(&InvitationReply { status: InvitationStatus::Declined })
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                assert!(titles(&jwt).is_empty());
+                assert_eq!(titles(&other_jwt), vec!["Lunch".to_string()]);
+            });
+        }
+
+        #[test]
+        fn conflicts_can_be_rejected_and_busy_times_are_merged() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let at = |hour, minute| {
+                    chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(hour, minute, 0)
+                };
+                let create = |title: &str, start_at, stop_at, path: &str| {
+                    let request = NewEventRequest {
+                        title: title.to_string(),
+                        text: "".to_string(),
+                        start_at,
+                        stop_at,
+                        rrule: None,
+                        time_zone: None,
+                        all_day: false,
+                    };
+                    warp::test::request()
+                        .method("POST")
+                        .path(path)
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+                let checked = "/api/calendar/event?reject_conflicts=true";
+
+                let resp = create("Standup", at(9, 0), at(10, 0), checked);
+                assert_eq!(resp.status(), 200);
+                let standup: ZonedEvent = deserialize(resp);
+
+                // Touching events don't conflict.
+                let resp = create("Review", at(10, 0), at(10, 30), checked);
+                assert_eq!(resp.status(), 200);
+
+                let resp = create("Interview", at(9, 30), at(10, 15), checked);
+                assert_eq!(resp.status(), 409);
+                let error: serde_json::Value = deserialize(resp);
+                let conflicts = error["details"].as_array().unwrap();
+                assert_eq!(conflicts.len(), 2);
+                assert_eq!(conflicts[0]["uuid"], standup.event.uuid.to_string());
+
+                // Without the flag, conflicting events are saved.
+                let resp = create("Interview", at(9, 30), at(10, 15), "/api/calendar/event");
+                assert_eq!(resp.status(), 200);
+                let resp = create("Lunch", at(12, 0), at(13, 0), "/api/calendar/event");
+                assert_eq!(resp.status(), 200);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/freebusy?start=2019-05-06&stop=2019-05-06T12:30:00")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let busy: Vec<BusyInterval> = deserialize(resp);
+                assert_eq!(
+                    busy,
+                    vec![
+                        BusyInterval {
+                            start_at: at(9, 0),
+                            stop_at: at(10, 30),
+                        },
+                        BusyInterval {
+                            start_at: at(12, 0),
+                            stop_at: at(12, 30),
+                        },
+                    ]
+                );
+
+                // An event doesn't conflict with itself when it is modified.
+                let changeset = EventChangeset {
+                    uuid: standup.event.uuid,
+                    title: "Standup".to_string(),
+                    text: "".to_string(),
+                    start_at: at(8, 30),
+                    stop_at: at(9, 15),
+                    rrule: None,
+                    time_zone: None,
+                    all_day: None,
+                };
+                let modify = |changeset: &EventChangeset| {
+                    warp::test::request()
+                        .method("PUT")
+                        .path(checked)
+                        .json(changeset)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                        .status()
+                };
+                assert_eq!(modify(&changeset), 200);
+                let changeset = EventChangeset {
+                    stop_at: at(9, 45),
+                    ..changeset
+                };
+                assert_eq!(modify(&changeset), 409);
+            });
+        }
+
+        #[test]
+        fn events_can_be_searched() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let events = vec![
+                    ("Dentist appointment", "Bring the insurance card", 6),
+                    ("Lunch", "Talk about the dentist's bill", 7),
+                    ("Planning", "", 8),
+                ];
+                for (title, text, day) in events {
+                    let start_at = chrono::NaiveDate::from_ymd(2019, 5, day).and_hms(12, 0, 0);
+                    let request = NewEventRequest {
+                        title: title.to_string(),
+                        text: text.to_string(),
+                        start_at,
+                        stop_at: start_at + chrono::Duration::hours(1),
+                        rrule: None,
+                        time_zone: None,
+                        all_day: false,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/calendar/event")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                }
+                let search = |query: &str| {
+                    warp::test::request()
+                        .method("GET")
+                        .path(&format!("/api/calendar/event/search?{}", query))
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+
+                // Matches in the title rank above matches in the text.
+                let resp = search("q=dentists");
+                assert_eq!(resp.status(), 200);
+                let results: Vec<SearchResult> = deserialize(resp);
+                let titles: Vec<&str> = results
+                    .iter()
+                    .map(|result| result.event.event.title.as_str())
+                    .collect();
+                assert_eq!(titles, vec!["Dentist appointment", "Lunch"]);
+                assert!(results[0].rank > results[1].rank);
+                assert!(results[0].snippet.contains("<b>Dentist</b>"));
+
+                let resp = search("q=dentist&start=2019-05-07&stop=2019-05-08");
+                assert_eq!(resp.status(), 200);
+                let results: Vec<SearchResult> = deserialize(resp);
+                assert_eq!(results.len(), 1);
+                assert_eq!(results[0].event.event.title, "Lunch");
+
+                let resp = search("q=%22insurance%20card%22%20-lunch");
+                let results: Vec<SearchResult> = deserialize(resp);
+                assert_eq!(results.len(), 1);
+
+                assert_eq!(search("q=%20").status(), 400);
+                assert_eq!(search("q=dentist&start=2019-05-07").status(), 400);
+            });
+        }
+
+        #[test]
+        fn stale_changes_are_rejected() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let start_at = chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(9, 0, 0);
+                let request = NewEventRequest {
+                    title: "Standup".to_string(),
+                    text: "".to_string(),
+                    start_at,
+                    stop_at: start_at + chrono::Duration::minutes(15),
+                    rrule: None,
+                    time_zone: None,
+                    all_day: false,
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let etag = resp.headers()["etag"].to_str().unwrap().to_string();
+                let event: Event = deserialize(resp);
+
+                let modify = |title: &str, if_match: &str| {
+                    let changeset = EventChangeset {
+                        uuid: event.uuid,
+                        title: title.to_string(),
+                        text: "".to_string(),
+                        start_at: event.start_at,
+                        stop_at: event.stop_at,
+                        rrule: None,
+                        time_zone: None,
+                        all_day: None,
+                    };
+                    warp::test::request()
+                        .method("PUT")
+                        .path("/api/calendar/event")
+                        .json(&changeset)
+                        .header("content-length", "500")
+                        .header("if-match", if_match)
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+                let delete = |if_match: &str| {
+                    warp::test::request()
+                        .method("DELETE")
+                        .path(&format!("/api/calendar/event/{}", event.uuid))
+                        .header("if-match", if_match)
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+
+                // The first tab's change goes through, and changes the ETag.
+                let resp = modify("Standup with the team", &etag);
+                assert_eq!(resp.status(), 200);
+                let new_etag = resp.headers()["etag"].to_str().unwrap().to_string();
+                assert_ne!(new_etag, etag);
+                let changed: Event = deserialize(resp);
+                assert!(changed.updated_at > event.updated_at);
+
+                // The second tab still has the old ETag, so it can't overwrite the change.
+                assert_eq!(modify("Daily standup", &etag).status(), 412);
+                assert_eq!(delete(&etag).status(), 412);
+                assert_eq!(modify("Daily standup", "\"garbage\"").status(), 412);
+                let current = Event::get_event(event.uuid, &s.db_pool().get().unwrap()).unwrap();
+                assert_eq!(current.title, "Standup with the team");
+
+                // Any of several ETags can match, and `*` matches whatever the event is now.
+                let resp = modify("Daily standup", &format!("{}, {}", etag, new_etag));
+                assert_eq!(resp.status(), 200);
+                let resp = modify("Standup", "*");
+                assert_eq!(resp.status(), 200);
+                let latest_etag = resp.headers()["etag"].to_str().unwrap().to_string();
+
+                assert_eq!(delete(&latest_etag).status(), 200);
+                assert_eq!(delete(&latest_etag).status(), 404);
+            });
+        }
+
+        #[test]
+        fn events_can_be_fetched_and_patched() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+                let other_jwt = get_jwt_for(&s, "other user");
+
+                let start_at = chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(9, 0, 0);
+                let request = NewEventRequest {
+                    title: "Standup".to_string(),
+                    text: "".to_string(),
+                    start_at,
+                    stop_at: start_at + chrono::Duration::minutes(15),
+                    rrule: Some("FREQ=DAILY".to_string()),
+                    time_zone: None,
+                    all_day: false,
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let event: Event = deserialize(resp);
+
+                let get = |jwt: &str| {
+                    warp::test::request()
+                        .method("GET")
+                        .path(&format!("/api/calendar/event/{}", event.uuid))
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+                let patch = |body: &str, if_match: &str| {
+                    warp::test::request()
+                        .method("PATCH")
+                        .path(&format!("/api/calendar/event/{}", event.uuid))
+                        .body(body)
+                        .header("content-type", "application/json")
+                        .header("content-length", "500")
+                        .header("if-match", if_match)
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+
+                // Fetching the event gives its ETag, which other users can't get.
+                let resp = get(&jwt);
+                assert_eq!(resp.status(), 200);
+                let etag = resp.headers()["etag"].to_str().unwrap().to_string();
+                let fetched: Event = deserialize(resp);
+                assert_eq!(fetched.uuid, event.uuid);
+                assert_eq!(get(&other_jwt).status(), 403);
+
+                // Only the fields that are present are changed, and `null` stops the event repeating.
+                let resp = patch(r#"{"title": "Daily standup", "rrule": null}"#, &etag);
+                assert_eq!(resp.status(), 200);
+                let new_etag = resp.headers()["etag"].to_str().unwrap().to_string();
+                assert_ne!(new_etag, etag);
+                let patched: Event = deserialize(resp);
+                assert_eq!(patched.title, "Daily standup");
+                assert_eq!(patched.rrule, None);
+                assert_eq!(patched.start_at, event.start_at);
+                assert_eq!(patched.stop_at, event.stop_at);
+
+                // A missing rule is left as it is.
+                let change = EventPatch {
+                    text: Some("In the small room".to_string()),
+                    ..EventPatch::default()
+                };
+                let resp = patch(&serde_json::to_string(&change).unwrap(), &new_etag);
+                assert_eq!(resp.status(), 200);
+                let patched: Event = deserialize(resp);
+                assert_eq!(patched.title, "Daily standup");
+                assert_eq!(patched.text, "In the small room");
+
+                assert_eq!(patch(r#"{"title": "Standup"}"#, &etag).status(), 412);
+                assert_eq!(patch(r#"{"stop_at": "2019-05-06T08:00:00"}"#, "*").status(), 400);
+
+                // An empty patch leaves the event, and its ETag, as they are.
+                let current_etag = get(&jwt).headers()["etag"].to_str().unwrap().to_string();
+                let resp = patch("{}", "*");
+                assert_eq!(resp.status(), 200);
+                assert_eq!(resp.headers()["etag"].to_str().unwrap(), current_etag);
+            });
+        }
+
+        #[test]
+        fn deleted_events_can_be_restored_and_purged() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+                let other_jwt = get_jwt_for(&s, "other user");
+
+                let start_at = chrono::NaiveDate::from_ymd(2019, 5, 6).and_hms(9, 0, 0);
+                let mut events = vec![];
+                for title in &["Standup", "Lunch"] {
+                    let request = NewEventRequest {
+                        title: title.to_string(),
+                        text: "".to_string(),
+                        start_at,
+                        stop_at: start_at + chrono::Duration::hours(1),
+                        rrule: None,
+                        time_zone: None,
+                        all_day: false,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/calendar/event")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    events.push(deserialize::<Event>(resp));
+                }
+                let titles = |path: &str| {
+                    let resp = warp::test::request()
+                        .method("GET")
+                        .path(path)
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                    let mut titles: Vec<String> = deserialize::<Vec<Event>>(resp)
+                        .into_iter()
+                        .map(|event| event.title)
+                        .collect();
+                    titles.sort();
+                    titles
+                };
+                let calendar = "/api/calendar/event/events?start=2019-05-06&stop=2019-05-07";
+                let trash = "/api/calendar/event/trash";
+                let restore = |event: &Event, jwt: &str| {
+                    warp::test::request()
+                        .method("POST")
+                        .path(&format!("/api/calendar/event/{}/restore", event.uuid))
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                        .status()
+                };
+
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!("/api/calendar/event/{}", events[0].uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let deleted: Event = deserialize(resp);
+                assert!(deleted.deleted_at.is_some());
+                assert_eq!(titles(calendar), vec!["Lunch"]);
+                assert_eq!(titles(trash), vec!["Standup"]);
+
+                // Only users who can change the calendar can restore its events.
+                assert_eq!(restore(&events[0], &other_jwt), 403);
+
+                // Members who can change the calendar see its trash, and can restore from it.
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/user")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, other_jwt))
+                    .reply(&filter);
+                let other: User = deserialize(resp);
+                let request = ShareRequest {
+                    user_uuid: other.uuid,
+                    permission: SharePermission::Write,
+                };
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path("/api/calendar/share")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(trash)
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, other_jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let shared_trash: Vec<Event> = deserialize(resp);
+                assert_eq!(shared_trash.len(), 1);
+                assert_eq!(shared_trash[0].uuid, events[0].uuid);
+                assert_eq!(restore(&events[0], &other_jwt), 200);
+                assert_eq!(restore(&events[0], &jwt), 404);
+                assert_eq!(titles(calendar), vec!["Lunch", "Standup"]);
+                assert!(titles(trash).is_empty());
+
+                // Importing over the calendar moves the events it replaces to the trash.
+                let imported = vec![ImportExportEvent {
+                    title: "Planning".to_string(),
+                    text: "".to_string(),
+                    start_at,
+                    stop_at: start_at + chrono::Duration::hours(1),
+                    rrule: None,
+                    exdates: vec![],
+                    external_id: None,
+                    time_zone: None,
+                    all_day: false,
+                }];
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event/events/import")
+                    .json(&imported)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let summary: ImportSummary = deserialize(resp);
+                assert_eq!(summary.deleted, 2);
+                assert_eq!(titles(calendar), vec!["Planning"]);
+                assert_eq!(titles(trash).len(), 2);
+
+                // Events are only purged once they have been in the trash for the retention.
+                let day = std::time::Duration::from_secs(24 * 60 * 60);
+                assert_eq!(purge_trash(&s.db_pool(), day).unwrap(), 0);
+                let now = std::time::Duration::from_secs(0);
+                assert_eq!(purge_trash(&s.db_pool(), now).unwrap(), 2);
+                assert!(titles(trash).is_empty());
+                assert_eq!(restore(&events[1], &jwt), 404);
+            });
+        }
+
+        #[test]
+        fn events_are_grouped_by_day() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let at = |day, hour| chrono::NaiveDate::from_ymd(2019, 5, day).and_hms(hour, 0, 0);
+                let events = vec![
+                    ("Trip", at(30, 20), at(30, 0) + chrono::Duration::days(3)),
+                    ("Standup", at(31, 9), at(31, 10)),
+                ];
+                for (title, start_at, stop_at) in events {
+                    let request = NewEventRequest {
+                        title: title.to_string(),
+                        text: "".to_string(),
+                        start_at,
+                        stop_at,
+                        rrule: None,
+                        time_zone: None,
+                        all_day: false,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/calendar/event")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200);
+                }
+                let view = |path: &str| {
+                    warp::test::request()
+                        .method("GET")
+                        .path(&format!("/api/calendar/{}", path))
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter)
+                };
+
+                let resp = view("month/2019/5");
+                assert_eq!(resp.status(), 200);
+                let month: CalendarView = deserialize(resp);
+                assert_eq!(month.first, chrono::NaiveDate::from_ymd(2019, 5, 1));
+                assert_eq!(month.last, chrono::NaiveDate::from_ymd(2019, 5, 31));
+                assert_eq!(month.days.len(), 31);
+                assert_eq!(month.count, 2);
+                let counts: Vec<usize> = month.days.iter().map(|day| day.count).collect();
+                assert_eq!(&counts[28..], &[0, 1, 2]);
+                assert_eq!(month.days[30].events[1].event.title, "Standup");
+
+                // The trip stops at midnight, so it isn't shown on the 2nd of June.
+                let resp = view("month/2019/6");
+                let month: CalendarView = deserialize(resp);
+                assert_eq!(month.days.len(), 30);
+                assert_eq!(month.days[0].count, 1);
+                assert_eq!(month.days[1].count, 0);
+
+                // Week 22 of 2019 is from Monday the 27th of May to Sunday the 2nd of June.
+                let resp = view("week/2019/22");
+                assert_eq!(resp.status(), 200);
+                let week: CalendarView = deserialize(resp);
+                assert_eq!(week.first, chrono::NaiveDate::from_ymd(2019, 5, 27));
+                assert_eq!(week.days.len(), 7);
+                assert_eq!(week.count, 2);
+
+                let resp = view("day/2019/5/31");
+                assert_eq!(resp.status(), 200);
+                let day: CalendarView = deserialize(resp);
+                assert_eq!(day.days.len(), 1);
+                assert_eq!(day.count, 2);
+
+                assert_eq!(view("month/2019/13").status(), 400);
+                assert_eq!(view("week/2019/53").status(), 400);
+                assert_eq!(view("day/2019/2/29").status(), 400);
+            });
+        }
+
+        #[test]
+        fn reminders_are_fired_delivered_and_dismissed() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let start_at = chrono::Utc::now().naive_utc() + chrono::Duration::minutes(10);
+                let request = NewEventRequest {
+                    title: "Dentist".to_string(),
+                    text: "".to_string(),
+                    start_at,
+                    stop_at: start_at + chrono::Duration::hours(1),
+                    rrule: None,
+                    time_zone: None,
+                    all_day: false,
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/calendar/event")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let event: ZonedEvent = deserialize(resp);
+
+                // Reminders can't be set too far ahead, or twice for the same time.
+                let path = format!("/api/calendar/event/{}/reminder", event.event.uuid);
+                for (minutes_before, status) in &[(15, 200), (15, 400), (-1, 400), (50_000, 400)] {
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path(&path)
+                        .json(&ReminderRequest { minutes_before: *minutes_before })
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), *status);
+                }
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(&path)
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let reminders: Vec<EventReminder> = deserialize(resp);
+                assert_eq!(reminders.len(), 1);
+                assert_eq!(reminders[0].minutes_before, 15);
+
+                let sent = send_reminders(s.db_pool(), Arc::new(LogNotifier)).wait().unwrap();
+                assert_eq!(sent, (1, 1));
+                // The reminder only fires once for the event.
+                let sent = send_reminders(s.db_pool(), Arc::new(LogNotifier)).wait().unwrap();
+                assert_eq!(sent, (0, 0));
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/reminder/fired")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+                let fired: Vec<FiredReminder> = deserialize(resp);
+                assert_eq!(fired.len(), 1);
+                assert_eq!(fired[0].event_uuid, event.event.uuid);
+                assert_eq!(fired[0].title, "Dentist");
+                assert!(fired[0].delivered_at.is_some());
+
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!("/api/calendar/reminder/fired/{}", fired[0].uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/calendar/reminder/fired")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                let fired: Vec<FiredReminder> = deserialize(resp);
+                assert!(fired.is_empty());
+
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!("{}/{}", path, reminders[0].uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200);
+            });
+        }
     }
 
-    mod market {
-        use super::*;
-        use crate::api::market::StockTransactionRequest;
-        use db::stock::UserStockResponse;
+    mod market {
+        use super::*;
+        use crate::{
+            api::{
+                calendar::TimeBoundaries,
+                market::{
+                    AccountResponse, NewOrderRequest, StockTransactionRequest, TransactionPage,
+                    WatchlistNameRequest, WatchlistQuotesResponse, WatchlistStockRequest,
+                },
+                user::LeaderboardPreferences,
+            },
+            leaderboard::LeaderboardEntry,
+            listing_refresher::refresh_listings,
+            order_matcher::fill_triggered_orders,
+            portfolio::EquityPoint,
+        };
+        use db::{
+            cash::STARTING_BALANCE,
+            order::{Order, OrderKind, OrderStatus, UserOrderResponse},
+            stock::{StockTransaction, UserStockResponse},
+            stock_listing::StockListing,
+            stock_price::StockPrice,
+            watchlist::{Watchlist, WatchlistResponse},
+        };
+        use futures::Future;
+        use std::collections::HashMap;
+
+        #[test]
+        fn buy_stock() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 1,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/stock/transact")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                dbg!(&resp.body());
+                assert_eq!(resp.status(), 200);
+            });
+        }
+
+        #[test]
+        fn owned_stocks() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 1,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/stock/transact")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not buy stocks");
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "Could not find stocks for user");
+                let r: Vec<UserStockResponse> = deserialize(resp);
+                assert_eq!(1, r.len());
+                assert_eq!(1, r[0].transactions.len());
+                assert_eq!(1, r[0].transactions[0].quantity)
+            });
+        }
+
+        #[test]
+        fn buying_twice_uses_one_stock() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 1,
+                };
+
+                for _ in 0..2 {
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/market/stock/transact")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200, "could not buy stocks");
+                }
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "Could not find stocks for user");
+                let r: Vec<UserStockResponse> = deserialize(resp);
+                assert_eq!(1, r.len());
+                assert_eq!(2, r[0].transactions.len());
+            });
+        }
+
+        #[test]
+        fn symbols_are_normalized_and_named() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                for symbol in &[" aapl ", "AAPL"] {
+                    let request = StockTransactionRequest {
+                        symbol: symbol.to_string(),
+                        quantity: 1,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/market/stock/transact")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200, "could not buy stocks");
+                }
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let r: Vec<UserStockResponse> = deserialize(resp);
+                assert_eq!(1, r.len());
+                assert_eq!(r[0].stock.symbol, "AAPL");
+                assert_eq!(r[0].stock.stock_name, "Apple Inc.");
+                assert_eq!(r[0].stock.exchange, Some("Nasdaq Global Select".to_string()));
+            });
+        }
+
+        #[test]
+        fn unlisted_symbols_are_rejected() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                for symbol in &["NOTREAL", "not a symbol"] {
+                    let request = NewOrderRequest {
+                        symbol: symbol.to_string(),
+                        quantity: 1,
+                        kind: OrderKind::Limit,
+                        trigger_price: 100.0,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/market/order")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 400, "{} should be rejected", symbol);
+                }
+            });
+        }
+
+        #[test]
+        fn search_finds_cached_listings() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let cached = refresh_listings(s.db_pool(), s.quotes()).wait().unwrap();
+                assert_eq!(cached, 4);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/search?q=micro")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200);
+                let listings: Vec<StockListing> = deserialize(resp);
+                assert_eq!(listings.len(), 1);
+                assert_eq!(listings[0].symbol, "MSFT");
+            });
+        }
+
+        #[test]
+        fn cant_sell_more_than_owned() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: -1,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/stock/transact")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 400);
+            });
+        }
+
+        #[test]
+        fn cant_buy_more_than_cash() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 1_000_000,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/stock/transact")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 400);
+            });
+        }
+
+        #[test]
+        fn account_reflects_purchase() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 2,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/stock/transact")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not buy stocks");
+                let transaction: StockTransaction = deserialize(resp);
+                let cost = 2.0 * transaction.price_of_stock_at_time_of_trading;
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/account")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200);
+                let account: AccountResponse = deserialize(resp);
+                assert!((account.cash - (STARTING_BALANCE - cost)).abs() < 0.001);
+                assert!((account.total_equity - STARTING_BALANCE).abs() < 0.001);
+            });
+        }
+
+        #[test]
+        fn fetched_prices_are_recorded_once_per_ttl() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 1,
+                };
+
+                // The second purchase should reuse the cached quote.
+                for _ in 0..2 {
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/market/stock/transact")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200, "could not buy stocks");
+                }
+
+                let tb = TimeBoundaries {
+                    start: chrono::Utc::now() - chrono::Duration::hours(1),
+                    stop: chrono::Utc::now() + chrono::Duration::hours(1),
+                };
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(&format!(
+                        "/api/market/stock/aapl/history?{}",
+                        serde_urlencoded::to_string(tb).unwrap()
+                    ))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200);
+                let history: Vec<StockPrice> = deserialize(resp);
+                assert_eq!(history.len(), 1);
+                assert_eq!(history[0].symbol, "AAPL");
+                assert_eq!(history[0].price, 170.67);
+            });
+        }
 
         #[test]
-        fn buy_stock() {
+        fn performance_history_starts_at_first_trade() {
             setup_warp(|_fixture: &UserFixture, pool: Pool| {
                 let secret = Secret::new("test");
                 let s = State::testing_init(pool, secret);
@@ -327,7 +2064,7 @@ mod integration_test {
 
                 let request = StockTransactionRequest {
                     symbol: "AAPL".to_string(),
-                    quantity: 1,
+                    quantity: 2,
                 };
 
                 let resp = warp::test::request()
@@ -337,14 +2074,25 @@ mod integration_test {
                     .header("content-length", "500")
                     .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
                     .reply(&filter);
+                assert_eq!(resp.status(), 200, "could not buy stocks");
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/performance/history?interval=day")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
 
-                dbg!(&resp.body());
                 assert_eq!(resp.status(), 200);
+                let curve: Vec<EquityPoint> = deserialize(resp);
+                assert_eq!(curve.len(), 1);
+                assert_eq!(curve[0].date, chrono::Utc::now().naive_utc().date());
+                assert!((curve[0].holdings_value - 2.0 * 170.67).abs() < 0.001);
+                assert!((curve[0].equity - STARTING_BALANCE).abs() < 0.001);
             });
         }
 
         #[test]
-        fn owned_stocks() {
+        fn limit_order_is_filled_when_triggered() {
             setup_warp(|_fixture: &UserFixture, pool: Pool| {
                 let secret = Secret::new("test");
                 let s = State::testing_init(pool, secret);
@@ -352,11 +2100,268 @@ mod integration_test {
 
                 let jwt = get_jwt(&s);
 
-                let request = StockTransactionRequest {
+                let request = NewOrderRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 2,
+                    kind: OrderKind::Limit,
+                    trigger_price: 150.0,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/order")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not place order");
+                let placed: UserOrderResponse = deserialize(resp);
+                assert_eq!(placed.order.status, OrderStatus::Open);
+
+                let conn = s.db_pool().get().unwrap();
+
+                // The price hasn't fallen to the limit yet.
+                let mut prices = HashMap::new();
+                prices.insert("AAPL".to_string(), 170.0);
+                let orders = Order::get_open_orders(&conn).unwrap();
+                assert_eq!(fill_triggered_orders(&prices, orders, &conn), 0);
+
+                prices.insert("AAPL".to_string(), 140.0);
+                let orders = Order::get_open_orders(&conn).unwrap();
+                assert_eq!(fill_triggered_orders(&prices, orders, &conn), 1);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(&format!("/api/market/order/{}", placed.order.uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200);
+                let filled: UserOrderResponse = deserialize(resp);
+                assert_eq!(filled.order.status, OrderStatus::Filled);
+                assert!(filled.order.stock_transaction_uuid.is_some());
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/account")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let account: AccountResponse = deserialize(resp);
+                assert!((account.cash - (STARTING_BALANCE - 280.0)).abs() < 0.001);
+            });
+        }
+
+        #[test]
+        fn cancelled_order_is_not_filled() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = NewOrderRequest {
                     symbol: "AAPL".to_string(),
                     quantity: 1,
+                    kind: OrderKind::Stop,
+                    trigger_price: 180.0,
+                };
+
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/order")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not place order");
+                let placed: UserOrderResponse = deserialize(resp);
+
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!("/api/market/order/{}", placed.order.uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not cancel order");
+                let cancelled: UserOrderResponse = deserialize(resp);
+                assert_eq!(cancelled.order.status, OrderStatus::Cancelled);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/order")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let open: Vec<UserOrderResponse> = deserialize(resp);
+                assert!(open.is_empty());
+
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!("/api/market/order/{}", placed.order.uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 400, "closed orders can't be cancelled");
+            });
+        }
+
+        #[test]
+        fn transaction_history_is_paginated() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                for &(symbol, quantity) in &[("AAPL", 2), ("FB", 1), ("AAPL", -1)] {
+                    let request = StockTransactionRequest {
+                        symbol: symbol.to_string(),
+                        quantity,
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path("/api/market/stock/transact")
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+                    assert_eq!(resp.status(), 200, "could not trade {}", symbol);
+                }
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/transactions?limit=2")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not get history");
+                let first: TransactionPage = deserialize(resp);
+                assert_eq!(first.transactions.len(), 2);
+                assert_eq!(first.transactions[0].transaction.quantity, -1, "newest should be first");
+                let cursor = first.next_cursor.expect("there should be another page");
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(&format!("/api/market/stock/transactions?limit=2&cursor={}", cursor))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let second: TransactionPage = deserialize(resp);
+                assert_eq!(second.transactions.len(), 1);
+                assert_eq!(second.transactions[0].transaction.quantity, 2, "oldest should be last");
+                assert!(second.next_cursor.is_none());
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/transactions?side=buy&symbol=aapl&sort=asc")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let aapl_buys: TransactionPage = deserialize(resp);
+                assert_eq!(aapl_buys.transactions.len(), 1);
+                assert_eq!(aapl_buys.transactions[0].stock.symbol, "AAPL");
+                assert_eq!(aapl_buys.transactions[0].transaction.quantity, 2);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/transactions?cursor=garbage")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 400, "bad cursors should be rejected");
+            });
+        }
+
+        #[test]
+        fn transactions_export_as_csv() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 2,
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/stock/transact")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200, "could not buy stock");
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/transactions/export?format=csv")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not export transactions");
+                assert_eq!(resp.headers()["content-type"], "text/csv; charset=utf-8");
+                let csv = String::from_utf8(resp.body().to_vec()).unwrap();
+                let rows: Vec<&str> = csv.lines().collect();
+                assert_eq!(rows.len(), 2);
+                assert!(
+                    rows[1].contains(",AAPL,Apple Inc.,Nasdaq Global Select,Buy,2,170.67,-341.34,")
+                );
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/stock/transactions/export?format=ofx")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not export transactions");
+                let ofx = String::from_utf8(resp.body().to_vec()).unwrap();
+                assert_eq!(ofx.matches("<BUYSTOCK>").count(), 1);
+            });
+        }
+
+        #[test]
+        fn leaderboard_only_shows_users_who_opt_in() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/leaderboard?window=week")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not get leaderboard");
+                let entries: Vec<LeaderboardEntry> = deserialize(resp);
+                assert!(entries.is_empty(), "users should be left off until they opt in");
+
+                let preferences = LeaderboardPreferences {
+                    display_name: " Trader Joe ".to_string(),
+                    opt_in: true,
                 };
+                let resp = warp::test::request()
+                    .method("PUT")
+                    .path("/api/user/leaderboard")
+                    .json(&preferences)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200, "could not opt in");
 
+                let request = StockTransactionRequest {
+                    symbol: "AAPL".to_string(),
+                    quantity: 1,
+                };
                 let resp = warp::test::request()
                     .method("POST")
                     .path("/api/market/stock/transact")
@@ -364,20 +2369,140 @@ mod integration_test {
                     .header("content-length", "500")
                     .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
                     .reply(&filter);
+                assert_eq!(resp.status(), 200, "could not buy stock");
 
-                assert_eq!(resp.status(), 200, "could not buy stocks");
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/leaderboard")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let entries: Vec<LeaderboardEntry> = deserialize(resp);
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].rank, 1);
+                assert_eq!(entries[0].display_name, "Trader Joe");
+                // Bought at the current price, so nothing has been gained yet.
+                assert!((entries[0].equity - STARTING_BALANCE).abs() < 0.001);
+                assert!(entries[0].percent_return.abs() < 0.001);
+            });
+        }
+
+        #[test]
+        fn watchlist_quotes_every_stock() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = WatchlistNameRequest {
+                    name: "Tech".to_string(),
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/watchlist")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not create watchlist");
+                let watchlist: Watchlist = deserialize(resp);
+
+                for symbol in &["aapl", "FB", "AAPL"] {
+                    let request = WatchlistStockRequest {
+                        symbol: symbol.to_string(),
+                    };
+                    let resp = warp::test::request()
+                        .method("POST")
+                        .path(&format!("/api/market/watchlist/{}/stock", watchlist.uuid))
+                        .json(&request)
+                        .header("content-length", "500")
+                        .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                        .reply(&filter);
+
+                    assert_eq!(resp.status(), 200, "could not add {}", symbol);
+                }
 
                 let resp = warp::test::request()
                     .method("GET")
-                    .path("/api/market/stock/")
+                    .path(&format!("/api/market/watchlist/{}", watchlist.uuid))
                     .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
                     .reply(&filter);
 
-                assert_eq!(resp.status(), 200, "Could not find stocks for user");
-                let r: Vec<UserStockResponse> = deserialize(resp);
-                assert_eq!(1, r.len());
-                assert_eq!(1, r[0].transactions.len());
-                assert_eq!(1, r[0].transactions[0].quantity)
+                let watched: WatchlistResponse = deserialize(resp);
+                let symbols: Vec<&str> = watched.stocks.iter().map(|s| s.symbol.as_str()).collect();
+                assert_eq!(symbols, vec!["AAPL", "FB"], "stocks should only be added once");
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path(&format!("/api/market/watchlist/{}/quotes", watchlist.uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                assert_eq!(resp.status(), 200, "could not get quotes");
+                let quotes: WatchlistQuotesResponse = deserialize(resp);
+                assert!(quotes.unpriced_symbols.is_empty());
+                let aapl = quotes
+                    .quotes
+                    .iter()
+                    .find(|quote| quote.stock.symbol == "AAPL")
+                    .expect("AAPL should be quoted");
+                assert_eq!(aapl.price, 170.67);
+
+                let resp = warp::test::request()
+                    .method("DELETE")
+                    .path(&format!("/api/market/watchlist/{}/stock/fb", watchlist.uuid))
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let watched: WatchlistResponse = deserialize(resp);
+                assert_eq!(watched.stocks.len(), 1);
+            });
+        }
+
+        #[test]
+        fn watchlist_names_are_unique() {
+            setup_warp(|_fixture: &UserFixture, pool: Pool| {
+                let secret = Secret::new("test");
+                let s = State::testing_init(pool, secret);
+                let filter = routes(&s);
+
+                let jwt = get_jwt(&s);
+
+                let request = WatchlistNameRequest {
+                    name: "Tech".to_string(),
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/watchlist")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 200, "could not create watchlist");
+
+                let request = WatchlistNameRequest {
+                    name: " Tech ".to_string(),
+                };
+                let resp = warp::test::request()
+                    .method("POST")
+                    .path("/api/market/watchlist")
+                    .json(&request)
+                    .header("content-length", "500")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+                assert_eq!(resp.status(), 400, "watchlist names should be unique");
+
+                let resp = warp::test::request()
+                    .method("GET")
+                    .path("/api/market/watchlist")
+                    .header(AUTHORIZATION_HEADER_KEY, format!("{} {}", BEARER, jwt))
+                    .reply(&filter);
+
+                let watchlists: Vec<WatchlistResponse> = deserialize(resp);
+                assert_eq!(watchlists.len(), 1);
             });
         }
 
@@ -1,15 +1,33 @@
 use crate::{
+    error::Error,
     server_auth::user_filter,
     state::State,
     util::{self, json_body_filter},
 };
-use db::user::User;
-use diesel::result::QueryResult;
+use apply::Apply;
+use db::{time_zone, user::User};
+use diesel::{
+    pg::PgConnection,
+    result::{DatabaseErrorKind, Error as DieselError, QueryResult},
+};
 use log::info;
 use pool::PooledConn;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use warp::{path, Filter, Rejection, Reply};
 
+/// The longest display name a user can pick.
+const MAX_DISPLAY_NAME_LENGTH: usize = 32;
+
+/// A request to pick a display name and join or leave the market leaderboard.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeaderboardPreferences {
+    /// The name the user is shown as to other users.
+    pub display_name: String,
+    /// Whether the user wants to be ranked on the leaderboard.
+    pub opt_in: bool,
+}
+
 /// The user api.
 ///
 /// # Arguments
@@ -41,6 +59,17 @@ pub fn user_api(state: &State) -> impl Filter<Extract = (impl Reply,), Error = R
         })
         .and_then(util::json_or_reject);
 
+    let set_time_zone = path!("time_zone")
+        .and(warp::put2())
+        .and(json_body_filter(1))
+        .and(user_filter(state))
+        .and(state.db())
+        .map(|time_zone: String, user_uuid: Uuid, conn: PooledConn| {
+            info!("Setting time zone to {} for user: {}", time_zone, user_uuid);
+            set_time_zone(time_zone, user_uuid, &conn)
+        })
+        .and_then(util::json_or_reject);
+
     let get_user = warp::get2()
         .and(warp::path::end())
         .and(user_filter(state))
@@ -50,5 +79,81 @@ pub fn user_api(state: &State) -> impl Filter<Extract = (impl Reply,), Error = R
         })
         .and_then(util::json_or_reject);
 
-    path!("user").and(get_user.or(set_zip_code).or(get_zip_code))
+    let set_leaderboard_preferences = path!("leaderboard")
+        .and(warp::put2())
+        .and(json_body_filter(1))
+        .and(user_filter(state))
+        .and(state.db())
+        .map(
+            |preferences: LeaderboardPreferences, user_uuid: Uuid, conn: PooledConn| {
+                info!(
+                    "Setting leaderboard preferences to {:?} for user: {}",
+                    preferences, user_uuid
+                );
+                set_leaderboard_preferences(preferences, user_uuid, &conn)
+            },
+        )
+        .and_then(util::json_or_reject);
+
+    path!("user").and(
+        get_user
+            .or(set_zip_code)
+            .or(get_zip_code)
+            .or(set_leaderboard_preferences)
+            .or(set_time_zone),
+    )
+}
+
+/// Sets the time zone the user's calendar is shown in.
+///
+/// # Arguments
+/// * time_zone - The IANA name of the zone, like `America/New_York`.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn set_time_zone(time_zone: String, user_uuid: Uuid, conn: &PgConnection) -> Result<User, Error> {
+    match time_zone::parse_zone(time_zone.trim()) {
+        Some(zone) => {
+            User::set_time_zone(user_uuid, zone.name().to_string(), conn).map_err(Error::from)
+        }
+        None => {
+            Error::bad_request(format!("The time zone '{}' is not known.", time_zone)).apply(Err)
+        }
+    }
+}
+
+/// Sets the name the user is shown as, and whether they are ranked on the leaderboard.
+///
+/// # Arguments
+/// * preferences - The request struct containing the display name and whether the user is opting in.
+/// * user_uuid - The user's uuid.
+/// * conn - The connection to the database.
+fn set_leaderboard_preferences(
+    preferences: LeaderboardPreferences,
+    user_uuid: Uuid,
+    conn: &PgConnection,
+) -> Result<User, Error> {
+    let display_name = preferences.display_name.trim();
+    if display_name.is_empty() {
+        return Error::bad_request("Display names can't be empty.").apply(Err);
+    }
+    if display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+        return Error::bad_request(format!(
+            "Display names can't be longer than {} characters.",
+            MAX_DISPLAY_NAME_LENGTH
+        ))
+        .apply(Err);
+    }
+
+    User::set_leaderboard_preferences(
+        user_uuid,
+        display_name.to_string(),
+        preferences.opt_in,
+        conn,
+    )
+    .map_err(|e| match e {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Error::bad_request(format!("The display name '{}' is taken.", display_name))
+        }
+        e => Error::from(e),
+    })
 }
@@ -2,10 +2,14 @@
 use apply::Apply;
 use clap::{App, Arg};
 
+use crate::{notifier::NotifierConfig, stock_quote::QuoteProviderConfig};
 use authorization::Secret;
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_ORDER_MATCH_INTERVAL_SECONDS: u64 = 60;
+const DEFAULT_REMINDER_INTERVAL_SECONDS: u64 = 60;
+const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
 
 /// Configuration options for initializing the server.
 #[derive(Debug)]
@@ -25,6 +29,19 @@ pub struct Config {
     pub server_lib_root: Option<PathBuf>,
     /// Is the server running in production.
     pub is_production: bool,
+    /// Where stock prices are sourced from.
+    pub quote_provider: QuoteProviderConfig,
+    /// How often open orders are checked against current prices.
+    pub order_match_interval: Duration,
+    /// How long fetched stock quotes are reused for.
+    /// If left unspecified, the state's default is used.
+    pub quote_cache_ttl: Option<Duration>,
+    /// How fired event reminders are delivered.
+    pub notifier: NotifierConfig,
+    /// How often event reminders are checked.
+    pub reminder_interval: Duration,
+    /// How long deleted events are kept in the trash before they are purged.
+    pub trash_retention: Duration,
 }
 
 impl Config {
@@ -73,6 +90,74 @@ impl Config {
                     .long("production")
                     .help("Run with configurations made for a production environment.")
             )
+            .arg(
+                Arg::with_name("quote_provider")
+                    .long("quote-provider")
+                    .value_name("PROVIDER")
+                    .possible_values(&["iex", "fixture"])
+                    .help("Where stock prices come from. Defaults to 'iex'. 'fixture' serves prices from a local JSON file, so the market works offline.")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("quote_fixture")
+                    .long("quote-fixture")
+                    .value_name("PATH")
+                    .help("The JSON file used by the 'fixture' quote provider. Defaults to 'fixtures/quotes.json' under the server lib root.")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("order_match_interval")
+                    .long("order-match-interval")
+                    .value_name("SECONDS")
+                    .help("How often open limit and stop orders are checked against current prices. Defaults to 60 seconds.")
+                    .takes_value(true)
+                    .validator(at_least_one_second)
+            )
+            .arg(
+                Arg::with_name("quote_cache_ttl")
+                    .long("quote-cache-ttl")
+                    .value_name("SECONDS")
+                    .help("How long fetched stock quotes are reused before the provider is asked again. Defaults to 60 seconds.")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("notifier")
+                    .long("notifier")
+                    .value_name("NOTIFIER")
+                    .possible_values(&["log", "webhook", "mail"])
+                    .help("How event reminders are delivered. Defaults to 'log'. 'webhook' POSTs them as JSON to --notifier-webhook-url. 'mail' writes them as email messages into --notifier-mail-dir.")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("notifier_webhook_url")
+                    .long("notifier-webhook-url")
+                    .value_name("URL")
+                    .help("Where the 'webhook' notifier sends reminders.")
+                    .required_if("notifier", "webhook")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("notifier_mail_dir")
+                    .long("notifier-mail-dir")
+                    .value_name("PATH")
+                    .help("The directory the 'mail' notifier writes messages into. Defaults to 'mail' under the server lib root.")
+                    .takes_value(true)
+            )
+            .arg(
+                Arg::with_name("reminder_interval")
+                    .long("reminder-interval")
+                    .value_name("SECONDS")
+                    .help("How often event reminders are checked for. Defaults to 60 seconds.")
+                    .takes_value(true)
+                    .validator(at_least_one_second)
+            )
+            .arg(
+                Arg::with_name("trash_retention")
+                    .long("trash-retention")
+                    .value_name("DAYS")
+                    .help("How long deleted events are kept in the trash before they are purged. Defaults to 30 days.")
+                    .takes_value(true)
+            )
             .get_matches();
 
         let port: u16 = if let Some(port) = matches.value_of("port") {
@@ -96,6 +181,55 @@ impl Config {
 
         let is_production = matches.is_present("production");
 
+        let quote_provider = match matches.value_of("quote_provider") {
+            Some("fixture") => matches
+                .value_of("quote_fixture")
+                .map(PathBuf::from)
+                .apply(QuoteProviderConfig::Fixture),
+            _ => QuoteProviderConfig::Iex,
+        };
+
+        let order_match_interval: u64 = if let Some(seconds) = matches.value_of("order_match_interval") {
+            seconds.parse().expect("Order match interval must be an integer.")
+        } else {
+            DEFAULT_ORDER_MATCH_INTERVAL_SECONDS
+        };
+        let order_match_interval = Duration::from_secs(order_match_interval);
+
+        let quote_cache_ttl = matches.value_of("quote_cache_ttl").map(|seconds| {
+            seconds
+                .parse()
+                .map(Duration::from_secs)
+                .expect("Quote cache TTL must be an integer.")
+        });
+
+        let notifier = match matches.value_of("notifier") {
+            Some("webhook") => matches
+                .value_of("notifier_webhook_url")
+                .expect("The webhook notifier needs a url.")
+                .to_string()
+                .apply(NotifierConfig::Webhook),
+            Some("mail") => matches
+                .value_of("notifier_mail_dir")
+                .map(PathBuf::from)
+                .apply(NotifierConfig::MailDrop),
+            _ => NotifierConfig::Log,
+        };
+
+        let reminder_interval: u64 = if let Some(seconds) = matches.value_of("reminder_interval") {
+            seconds.parse().expect("Reminder interval must be an integer.")
+        } else {
+            DEFAULT_REMINDER_INTERVAL_SECONDS
+        };
+        let reminder_interval = Duration::from_secs(reminder_interval);
+
+        let trash_retention: u64 = if let Some(days) = matches.value_of("trash_retention") {
+            days.parse().expect("Trash retention must be an integer.")
+        } else {
+            DEFAULT_TRASH_RETENTION_DAYS
+        };
+        let trash_retention = Duration::from_secs(trash_retention * 24 * 60 * 60);
+
         Config {
             port,
             tls_enabled,
@@ -103,6 +237,22 @@ impl Config {
             max_pool_size,
             server_lib_root,
             is_production,
+            quote_provider,
+            order_match_interval,
+            quote_cache_ttl,
+            notifier,
+            reminder_interval,
+            trash_retention,
         }
     }
 }
+
+/// Checks that an interval in seconds is a whole number of seconds, and isn't zero.
+/// Timers can't tick every zero seconds.
+fn at_least_one_second(seconds: String) -> Result<(), String> {
+    match seconds.parse::<u64>() {
+        Ok(0) => Err("The interval must be at least one second.".to_string()),
+        Ok(_) => Ok(()),
+        Err(_) => Err("The interval must be a whole number of seconds.".to_string()),
+    }
+}
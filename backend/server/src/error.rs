@@ -38,6 +38,14 @@ pub enum Error {
     /// Authorization - user may be authenticated, but still should not access the resource.
     /// This is synonymous with HTTP - Forbidden code.
     NotAuthorized { reason: String },
+    /// The request conflicts with data the server already has.
+    /// The details describe the conflicting data, so the user can resolve the conflict.
+    Conflict {
+        reason: String,
+        details: serde_json::Value,
+    },
+    /// A precondition of the request, like `If-Match`, doesn't hold for the resource as it is now.
+    PreconditionFailed { reason: String },
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
@@ -82,6 +90,12 @@ impl Display for Error {
             Error::NotAuthorized { reason } => {
                 format!("You are forbidden from accessing this resource. ({})", reason)
             }
+            Error::Conflict { reason, .. } => {
+                format!("The request conflicts with existing data. ({})", reason)
+            }
+            Error::PreconditionFailed { reason } => {
+                format!("The resource doesn't match the request's precondition. ({})", reason)
+            }
         };
         write!(f, "{}", description)
     }
@@ -134,6 +148,7 @@ pub fn customize_error(err: Rejection) -> Result<impl Reply, Rejection> {
         message: s,
         canonical_reason: code.canonical_reason().unwrap_or_default(),
         error_code: code.as_u16(),
+        details: cause.details(),
     };
     let json = warp::reply::json(&error_response);
 
@@ -163,6 +178,16 @@ impl Error {
                 }
             }
             Error::NotAuthorized { .. } => StatusCode::FORBIDDEN,
+            Error::Conflict { .. } => StatusCode::CONFLICT,
+            Error::PreconditionFailed { .. } => StatusCode::PRECONDITION_FAILED,
+        }
+    }
+
+    /// Get the data that describes the error in more detail, if it has any.
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::Conflict { details, .. } => Some(details.clone()),
+            _ => None,
         }
     }
 
@@ -242,6 +267,21 @@ impl Error {
             reason: reason.to_string(),
         }
     }
+
+    /// Construct a conflict error with a reason, and the data that the request conflicts with.
+    pub fn conflict<T: ToString, D: Serialize>(reason: T, details: &D) -> Self {
+        Error::Conflict {
+            reason: reason.to_string(),
+            details: serde_json::to_value(details).unwrap_or(serde_json::Value::Null),
+        }
+    }
+
+    /// Construct a precondition failed error with a reason.
+    pub fn precondition_failed<T: ToString>(reason: T) -> Self {
+        Error::PreconditionFailed {
+            reason: reason.to_string(),
+        }
+    }
 }
 
 impl From<diesel::result::Error> for Error {
@@ -249,13 +289,13 @@ impl From<diesel::result::Error> for Error {
         use self::Error::*;
         use diesel::result::Error as DieselError;
         match error {
-            DieselError::DatabaseError(e, _) => {
-                let e = match e {
+            DieselError::DatabaseError(kind, _) => {
+                let e = match kind {
                     DatabaseErrorKind::ForeignKeyViolation => {
                         "A foreign key constraint was violated in the database"
                     }
                     DatabaseErrorKind::SerializationFailure => {
-                        "Value failed to serialize in the database"
+                        "The request conflicted with a concurrent request, and should be retried"
                     }
                     DatabaseErrorKind::UnableToSendCommand => {
                         "Database Protocol violation, possibly too many bound parameters"
@@ -266,7 +306,12 @@ impl From<diesel::result::Error> for Error {
                     DatabaseErrorKind::__Unknown => "An unknown error occurred in the database",
                 }
                 .to_string();
-                DatabaseError(e)
+                match kind {
+                    // Only one of two concurrent serializable transactions can succeed,
+                    // the other can be tried again.
+                    DatabaseErrorKind::SerializationFailure => Error::conflict(e, &()),
+                    _ => DatabaseError(e),
+                }
             }
             DieselError::NotFound => NotFound {
                 type_name: "Not implemented".to_string(),
@@ -290,4 +335,6 @@ struct ErrorResponse {
     message: String,
     canonical_reason: &'static str,
     error_code: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
 }
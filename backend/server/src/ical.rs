@@ -0,0 +1,586 @@
+//! Reads and writes calendar events as iCalendar (RFC 5545) files,
+//! so events can be moved to and from other calendar apps.
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use db::{
+    event::{Event, ImportExportEvent},
+    time_zone,
+};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The media type of iCalendar files.
+pub const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Identifies the app as the producer of exported calendars.
+const PRODUCT_ID: &str = "-//SWEN344 web project//Calendar//EN";
+
+/// The format of UTC date-times.
+const UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The format of date-times in a named time zone.
+const LOCAL_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// The format of dates.
+const DATE_FORMAT: &str = "%Y%m%d";
+
+/// Lines longer than this many bytes are folded onto the next line.
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Writes the events as a calendar.
+///
+/// Recurring events keep their rules and removed occurrences,
+/// and events that replace an occurrence share the recurring event's UID.
+/// Imported events keep the UID they were imported with.
+/// Events with a time zone are written in local time, so recurrences follow daylight saving time.
+/// All-day events are written as dates.
+///
+/// # Arguments
+/// * events - The events to write, as stored.
+/// * generated_at - The time the calendar was made.
+pub fn write_calendar(events: &[Event], generated_at: NaiveDateTime) -> String {
+    let mut ics = String::new();
+    write_line(&mut ics, "BEGIN:VCALENDAR");
+    write_line(&mut ics, "VERSION:2.0");
+    write_line(&mut ics, &format!("PRODID:{}", PRODUCT_ID));
+    write_line(&mut ics, "CALSCALE:GREGORIAN");
+    let uids: HashMap<Uuid, String> = events
+        .iter()
+        .map(|e| (e.uuid, e.external_id.clone().unwrap_or_else(|| e.uuid.to_string())))
+        .collect();
+    for event in events {
+        let uid = match event.recurrence_parent_uuid {
+            Some(parent) => uids.get(&parent).cloned().unwrap_or_else(|| parent.to_string()),
+            None => uids[&event.uuid].clone(),
+        };
+        write_line(&mut ics, "BEGIN:VEVENT");
+        write_line(&mut ics, &format!("UID:{}", uid));
+        write_line(&mut ics, &format!("DTSTAMP:{}", generated_at.format(UTC_FORMAT)));
+        write_line(&mut ics, &time_property("DTSTART", &[event.start_at], event));
+        write_line(&mut ics, &time_property("DTEND", &[event.stop_at], event));
+        write_line(&mut ics, &format!("SUMMARY:{}", escape_text(&event.title)));
+        if !event.text.is_empty() {
+            write_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(&event.text)));
+        }
+        if let Some(rrule) = &event.rrule {
+            write_line(&mut ics, &format!("RRULE:{}", rrule));
+        }
+        if !event.exdates.is_empty() {
+            write_line(&mut ics, &time_property("EXDATE", &event.exdates, event));
+        }
+        if let (Some(_), Some(recurrence_id)) = (event.recurrence_parent_uuid, event.recurrence_id)
+        {
+            write_line(&mut ics, &time_property("RECURRENCE-ID", &[recurrence_id], event));
+        }
+        write_line(&mut ics, "END:VEVENT");
+    }
+    write_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Writes a DATE-TIME property, in local time if the event has a time zone,
+/// or a DATE property if the event lasts whole days.
+fn time_property(name: &str, times: &[NaiveDateTime], event: &Event) -> String {
+    let zone = event.zone();
+    let values: Vec<String> = times
+        .iter()
+        .map(|time| match zone {
+            _ if event.all_day => time.format(DATE_FORMAT).to_string(),
+            Some(zone) => time_zone::to_local(*time, zone).format(LOCAL_FORMAT).to_string(),
+            None => time.format(UTC_FORMAT).to_string(),
+        })
+        .collect();
+    match zone {
+        _ if event.all_day => format!("{};VALUE=DATE:{}", name, values.join(",")),
+        Some(zone) => format!("{};TZID={}:{}", name, zone.name(), values.join(",")),
+        None => format!("{}:{}", name, values.join(",")),
+    }
+}
+
+/// Writes a content line, folding it so no line is longer than 75 bytes.
+fn write_line(ics: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            ics.push_str("\r\n ");
+            // The leading space counts towards the length of the continuation.
+            length = 1;
+        }
+        ics.push(c);
+        length += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// Escapes the characters that have meaning in TEXT values.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Reverses `escape_text`.
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(other) => unescaped.push(other),
+                None => unescaped.push('\\'),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+/// A property of a component, like `DTSTART;TZID=Europe/Paris:20190506T090000`.
+#[derive(Debug)]
+struct Property<'a> {
+    name: String,
+    params: HashMap<String, &'a str>,
+    value: &'a str,
+}
+
+impl<'a> Property<'a> {
+    /// Splits a content line into its name, parameters and value.
+    fn parse(line: &'a str) -> Result<Property<'a>, String> {
+        let mut in_quotes = false;
+        let mut separators = vec![];
+        let mut value_start = None;
+        for (i, c) in line.char_indices() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                ';' if !in_quotes => separators.push(i),
+                ':' if !in_quotes => {
+                    value_start = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let value_start =
+            value_start.ok_or_else(|| format!("The line '{}' has no value.", line))?;
+        separators.push(value_start);
+
+        let name = line[..separators[0]].to_uppercase();
+        let params = separators
+            .windows(2)
+            .filter_map(|pair| {
+                let mut param = line[pair[0] + 1..pair[1]].splitn(2, '=');
+                let key = param.next()?.to_uppercase();
+                let value = param.next()?.trim_matches('"');
+                Some((key, value))
+            })
+            .collect();
+        Ok(Property {
+            name,
+            params,
+            value: &line[value_start + 1..],
+        })
+    }
+
+    /// Reads a DATE or DATE-TIME value as UTC.
+    ///
+    /// Times in a named zone are converted to UTC, and times without a zone are taken to be UTC.
+    ///
+    /// # Return
+    /// The time, and whether it was only a date.
+    fn time(&self) -> Result<(NaiveDateTime, bool), String> {
+        parse_time(self.value, self.params.get("TZID").cloned())
+    }
+}
+
+/// Reads a DATE or DATE-TIME value as UTC.
+fn parse_time(value: &str, tzid: Option<&str>) -> Result<(NaiveDateTime, bool), String> {
+    let invalid = || format!("'{}' is not a date or time.", value);
+    let value = value.trim();
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|_| invalid())?;
+        return Ok((date.and_hms(0, 0, 0), true));
+    }
+    let time = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), LOCAL_FORMAT)
+        .map_err(|_| invalid())?;
+    match tzid {
+        Some(tzid) if !value.ends_with('Z') => {
+            Ok((time_zone::to_utc(time, parse_zone(tzid)?), false))
+        }
+        _ => Ok((time, false)),
+    }
+}
+
+/// Looks up the zone named by a TZID parameter.
+fn parse_zone(tzid: &str) -> Result<Tz, String> {
+    time_zone::parse_zone(tzid).ok_or_else(|| format!("The time zone '{}' is not known.", tzid))
+}
+
+/// Reads a DURATION value, like `PT1H30M` or `P1W`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("'{}' is not a duration.", value);
+    let (negative, rest) = match value.trim().chars().next() {
+        Some('-') => (true, &value.trim()[1..]),
+        Some('+') => (false, &value.trim()[1..]),
+        _ => (false, value.trim()),
+    };
+    if !rest.starts_with('P') {
+        return Err(invalid());
+    }
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for c in rest[1..].chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                let part = match c {
+                    'W' => Duration::try_weeks(n),
+                    'D' => Duration::try_days(n),
+                    'H' => Duration::try_hours(n),
+                    'M' => Duration::try_minutes(n),
+                    _ => Duration::try_seconds(n),
+                };
+                duration = part
+                    .and_then(|part| duration.checked_add(&part))
+                    .ok_or_else(invalid)?;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(if negative { -duration } else { duration })
+}
+
+/// The properties of a VEVENT that are kept when importing.
+#[derive(Debug, Default)]
+struct VEvent {
+    uid: Option<String>,
+    summary: String,
+    description: String,
+    start: Option<(NaiveDateTime, bool)>,
+    end: Option<NaiveDateTime>,
+    duration: Option<Duration>,
+    rrule: Option<String>,
+    exdates: Vec<NaiveDateTime>,
+    recurrence_id: Option<NaiveDateTime>,
+    time_zone: Option<String>,
+}
+
+impl VEvent {
+    fn set(&mut self, property: &Property) -> Result<(), String> {
+        match property.name.as_str() {
+            "UID" => self.uid = Some(property.value.to_string()),
+            "SUMMARY" => self.summary = unescape_text(property.value),
+            "DESCRIPTION" => self.description = unescape_text(property.value),
+            "DTSTART" => {
+                self.start = Some(property.time()?);
+                // Local times keep their zone, so the event recurs in it.
+                if let Some(tzid) = property.params.get("TZID") {
+                    if !property.value.trim().ends_with('Z') {
+                        self.time_zone = Some(parse_zone(tzid)?.name().to_string());
+                    }
+                }
+            }
+            "DTEND" => self.end = Some(property.time()?.0),
+            "DURATION" => self.duration = Some(parse_duration(property.value)?),
+            "RRULE" => self.rrule = Some(property.value.to_string()),
+            "EXDATE" => {
+                let tzid = property.params.get("TZID").cloned();
+                for exdate in property.value.split(',') {
+                    self.exdates.push(parse_time(exdate, tzid)?.0);
+                }
+            }
+            "RECURRENCE-ID" => self.recurrence_id = Some(property.time()?.0),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The event's UID, made unique for events that replace an occurrence.
+    fn external_id(&self) -> Option<String> {
+        let uid = self.uid.clone()?;
+        Some(match self.recurrence_id {
+            Some(recurrence_id) => format!("{}#{}", uid, recurrence_id.format(UTC_FORMAT)),
+            None => uid,
+        })
+    }
+
+    fn into_event(self) -> Result<ImportExportEvent, String> {
+        let external_id = self.external_id();
+        let (start_at, is_date) = self.start.ok_or_else(|| {
+            format!("The event '{}' has no start (DTSTART).", self.summary)
+        })?;
+        // Without an end, events last for their whole day, or end as soon as they start.
+        let stop_at = match (self.end, self.duration) {
+            (Some(end), _) => end,
+            (None, Some(duration)) => start_at
+                .checked_add_signed(duration)
+                .ok_or_else(|| format!("The event '{}' lasts too long.", self.summary))?,
+            (None, None) if is_date => start_at + Duration::days(1),
+            (None, None) => start_at,
+        };
+        Ok(ImportExportEvent {
+            title: self.summary,
+            text: self.description,
+            start_at,
+            stop_at,
+            rrule: self.rrule,
+            exdates: self.exdates,
+            external_id,
+            time_zone: self.time_zone,
+            all_day: is_date,
+        })
+    }
+}
+
+/// Reads every VEVENT in a calendar.
+///
+/// Events that replace an occurrence of a recurring event are imported as separate events,
+/// and the occurrence they replace is removed from the recurring event.
+///
+/// # Arguments
+/// * ics - The contents of the iCalendar file.
+///
+/// # Return
+/// The events, or the reason the calendar could not be read.
+pub fn read_calendar(ics: &str) -> Result<Vec<ImportExportEvent>, String> {
+    // Undo line folding: lines starting with whitespace continue the previous line.
+    let mut lines: Vec<String> = vec![];
+    for line in ics.split('\n') {
+        let line = line.trim_end_matches('\r');
+        match (line.chars().next(), lines.last_mut()) {
+            (Some(' '), Some(last)) | (Some('\t'), Some(last)) => last.push_str(&line[1..]),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    if !lines
+        .first()
+        .map_or(false, |line| line.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err("The file is not an iCalendar file.".to_string());
+    }
+
+    let mut components: Vec<String> = vec![];
+    let mut current: Option<VEvent> = None;
+    let mut vevents: Vec<VEvent> = vec![];
+    for line in &lines {
+        let property = Property::parse(line)?;
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.to_uppercase();
+                if component == "VEVENT" {
+                    current = Some(VEvent::default());
+                }
+                components.push(component);
+            }
+            "END" => {
+                if components.pop().as_ref().map(String::as_str) == Some("VEVENT") {
+                    vevents.extend(current.take());
+                }
+            }
+            _ => {
+                // Properties of alarms and other components nested in events are ignored.
+                if components.last().map(String::as_str) == Some("VEVENT") {
+                    if let Some(vevent) = current.as_mut() {
+                        vevent.set(&property)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let replaced: Vec<(String, NaiveDateTime)> = vevents
+        .iter()
+        .filter_map(|vevent| Some((vevent.uid.clone()?, vevent.recurrence_id?)))
+        .collect();
+    for (uid, recurrence_id) in replaced {
+        let parent = vevents
+            .iter_mut()
+            .find(|vevent| vevent.recurrence_id.is_none() && vevent.uid.as_ref() == Some(&uid));
+        if let Some(parent) = parent {
+            parent.exdates.push(recurrence_id);
+        }
+    }
+
+    vevents.into_iter().map(VEvent::into_event).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2019, 5, day).and_hms(hour, 0, 0)
+    }
+
+    fn event(title: &str, text: &str) -> Event {
+        Event {
+            uuid: Uuid::new_v4(),
+            user_uuid: Uuid::new_v4(),
+            title: title.to_string(),
+            text: text.to_string(),
+            start_at: at(6, 9),
+            stop_at: at(6, 10),
+            rrule: None,
+            exdates: vec![],
+            recurrence_parent_uuid: None,
+            recurrence_id: None,
+            external_id: None,
+            time_zone: None,
+            all_day: false,
+            updated_at: at(6, 8),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn events_survive_a_round_trip() {
+        let mut standup = event("Standup; daily", "Line one\nLine two, with a comma");
+        standup.rrule = Some("FREQ=DAILY;COUNT=5".to_string());
+        standup.exdates = vec![at(7, 9)];
+        let long = event(&"Long title ".repeat(20), "");
+
+        let ics = write_calendar(&[standup, long.clone()], at(1, 0));
+        assert!(ics.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(ics.contains("SUMMARY:Standup\\; daily\r\n"));
+
+        let events = read_calendar(&ics).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].title, "Standup; daily");
+        assert_eq!(events[0].text, "Line one\nLine two, with a comma");
+        assert_eq!(events[0].start_at, at(6, 9));
+        assert_eq!(events[0].stop_at, at(6, 10));
+        assert_eq!(events[0].rrule, Some("FREQ=DAILY;COUNT=5".to_string()));
+        assert_eq!(events[0].exdates, vec![at(7, 9)]);
+        assert_eq!(events[1].title, long.title);
+    }
+
+    #[test]
+    fn zoned_times_are_converted_to_utc() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   BEGIN:VEVENT\r\n\
+                   SUMMARY:Lunch\r\n\
+                   DTSTART;TZID=America/New_York:20190506T120000\r\n\
+                   DURATION:PT1H30M\r\n\
+                   BEGIN:VALARM\r\n\
+                   DESCRIPTION:Not the event's description\r\n\
+                   END:VALARM\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   SUMMARY:Holiday\r\n\
+                   DTSTART;VALUE=DATE:20190527\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+
+        let events = read_calendar(ics).unwrap();
+
+        // New York is four hours behind UTC in May.
+        assert_eq!(events[0].start_at, at(6, 16));
+        assert_eq!(events[0].stop_at, at(6, 16) + Duration::minutes(90));
+        assert_eq!(events[0].text, "");
+        assert_eq!(events[1].start_at, at(27, 0));
+        assert_eq!(events[1].stop_at, at(28, 0));
+        assert!(events[1].all_day);
+        assert!(!events[0].all_day);
+    }
+
+    #[test]
+    fn all_day_events_are_written_as_dates() {
+        let mut holiday = event("Holiday", "");
+        holiday.start_at = at(27, 0);
+        holiday.stop_at = at(29, 0);
+        holiday.all_day = true;
+        holiday.time_zone = Some("America/New_York".to_string());
+
+        let ics = write_calendar(&[holiday], at(1, 0));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20190527\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20190529\r\n"));
+
+        let events = read_calendar(&ics).unwrap();
+        assert_eq!(events[0].start_at, at(27, 0));
+        assert_eq!(events[0].stop_at, at(29, 0));
+        assert!(events[0].all_day);
+    }
+
+    #[test]
+    fn replaced_occurrences_are_removed_from_their_event() {
+        let ics = "BEGIN:VCALENDAR\n\
+                   BEGIN:VEVENT\n\
+                   UID:standup\n\
+                   SUMMARY:Standup\n\
+                   DTSTART:20190506T090000Z\n\
+                   RRULE:FREQ=DAILY;COUNT=3\n\
+                   END:VEVENT\n\
+                   BEGIN:VEVENT\n\
+                   UID:standup\n\
+                   RECURRENCE-ID:20190507T090000Z\n\
+                   SUMMARY:Late standup\n\
+                   DTSTART:20190507T100000Z\n\
+                   END:VEVENT\n\
+                   END:VCALENDAR\n";
+
+        let events = read_calendar(ics).unwrap();
+
+        assert_eq!(events[0].exdates, vec![at(7, 9)]);
+        assert_eq!(events[0].external_id, Some("standup".to_string()));
+        assert_eq!(events[1].title, "Late standup");
+        assert_eq!(events[1].external_id, Some("standup#20190507T090000Z".to_string()));
+        assert_eq!(events[1].rrule, None);
+    }
+
+    #[test]
+    fn zoned_events_are_written_in_local_time() {
+        // 9am in New York, every week across the start of daylight saving time.
+        let mut meeting = event("Meeting", "");
+        meeting.start_at = NaiveDate::from_ymd(2019, 3, 4).and_hms(14, 0, 0);
+        meeting.stop_at = NaiveDate::from_ymd(2019, 3, 4).and_hms(15, 0, 0);
+        meeting.rrule = Some("FREQ=WEEKLY".to_string());
+        meeting.exdates = vec![NaiveDate::from_ymd(2019, 3, 11).and_hms(13, 0, 0)];
+        meeting.time_zone = Some("America/New_York".to_string());
+
+        let ics = write_calendar(&[meeting.clone()], at(1, 0));
+        assert!(ics.contains("DTSTART;TZID=America/New_York:20190304T090000\r\n"));
+        assert!(ics.contains("EXDATE;TZID=America/New_York:20190311T090000\r\n"));
+
+        let events = read_calendar(&ics).unwrap();
+        assert_eq!(events[0].start_at, meeting.start_at);
+        assert_eq!(events[0].exdates, meeting.exdates);
+        assert_eq!(events[0].time_zone, meeting.time_zone);
+    }
+
+    #[test]
+    fn unknown_time_zones_are_rejected() {
+        let ics = "BEGIN:VCALENDAR\n\
+                   BEGIN:VEVENT\n\
+                   DTSTART;TZID=Mars/Olympus:20190506T090000\n\
+                   END:VEVENT\n\
+                   END:VCALENDAR\n";
+        assert!(read_calendar(ics).is_err());
+        assert!(read_calendar("not a calendar").is_err());
+    }
+
+    #[test]
+    fn overlong_durations_are_rejected() {
+        assert!(parse_duration("P99999999999999W").is_err());
+        assert!(parse_duration("P9223372036854775807D").is_err());
+        assert!(parse_duration("P10000000000DT9223372036854775S").is_err());
+        assert_eq!(parse_duration("-P1W2D").unwrap(), -Duration::days(9));
+
+        let ics = "BEGIN:VCALENDAR\n\
+                   BEGIN:VEVENT\n\
+                   DTSTART:20190506T090000Z\n\
+                   DURATION:P100000000D\n\
+                   END:VEVENT\n\
+                   END:VCALENDAR\n";
+        assert!(read_calendar(ics).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Ranks the users who have opted in to the leaderboard by the return on their portfolios.
+use crate::portfolio::PortfolioHistory;
+use chrono::{Duration, NaiveDateTime};
+use db::user::User;
+use diesel::{pg::PgConnection, result::QueryResult};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap};
+
+/// The period that returns are measured over.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardWindow {
+    /// The last seven days.
+    Week,
+    /// The last thirty days.
+    Month,
+    /// Since each user opened their account.
+    AllTime,
+}
+
+impl Default for LeaderboardWindow {
+    fn default() -> Self {
+        LeaderboardWindow::AllTime
+    }
+}
+
+impl LeaderboardWindow {
+    /// The start of the window, if it doesn't reach back to the start of every account.
+    fn start(self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            LeaderboardWindow::Week => Some(now - Duration::weeks(1)),
+            LeaderboardWindow::Month => Some(now - Duration::days(30)),
+            LeaderboardWindow::AllTime => None,
+        }
+    }
+}
+
+/// A user's place on the leaderboard.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+    /// The user's place, starting at 1. Users with the same return share a place.
+    pub rank: usize,
+    /// The name the user is shown as.
+    pub display_name: String,
+    /// The current value of the user's cash and stocks.
+    pub equity: f64,
+    /// The change in the user's equity over the window, as a percentage.
+    pub percent_return: f64,
+}
+
+/// A user on the leaderboard, along with everything needed to value their portfolio.
+#[derive(Clone, Debug)]
+pub struct Competitor {
+    /// The name the user is shown as.
+    pub display_name: String,
+    /// The user's trades, cash, and the prices recorded for their stocks.
+    pub history: PortfolioHistory,
+}
+
+impl Competitor {
+    /// Loads every user who has opted in to the leaderboard.
+    pub fn load_all(conn: &PgConnection) -> QueryResult<Vec<Competitor>> {
+        User::get_leaderboard_users(conn)?
+            .into_iter()
+            .filter_map(|user| {
+                let user_uuid = user.uuid;
+                user.display_name.map(|name| (user_uuid, name))
+            })
+            .map(|(user_uuid, display_name)| {
+                Ok(Competitor {
+                    display_name,
+                    history: PortfolioHistory::load(user_uuid, conn)?,
+                })
+            })
+            .collect()
+    }
+
+    /// The change in the user's equity since the start of the window, as a percentage.
+    ///
+    /// # Arguments
+    /// * equity - The current value of the user's account.
+    /// * window - The period the return is measured over.
+    /// * now - The current time.
+    fn percent_return(&self, equity: f64, window: LeaderboardWindow, now: NaiveDateTime) -> f64 {
+        let opened = self
+            .history
+            .cash_transactions
+            .iter()
+            .map(|t| t.record_time)
+            .min();
+        let baseline = match (window.start(now), opened) {
+            (Some(start), Some(opened)) if start > opened => self.history.equity_at(start, None),
+            // The window reaches back to when the account was opened, so compare against what was deposited.
+            _ => self
+                .history
+                .cash_transactions
+                .iter()
+                .filter(|t| t.stock_transaction_uuid.is_none())
+                .map(|t| t.amount)
+                .sum(),
+        };
+        if baseline <= 0.0 {
+            0.0
+        } else {
+            (equity - baseline) / baseline * 100.0
+        }
+    }
+}
+
+/// The symbols of every stock held by any of the competitors, without duplicates.
+pub fn symbols(competitors: &[Competitor]) -> Vec<&str> {
+    let mut symbols: Vec<&str> = competitors
+        .iter()
+        .flat_map(|competitor| competitor.history.symbols())
+        .collect();
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+/// Ranks the competitors by their return over the window, best first.
+///
+/// # Arguments
+/// * competitors - The users on the leaderboard.
+/// * current_prices - The current prices, keyed by symbol.
+/// Stocks without a current price are valued at the last price observed for them.
+/// * window - The period returns are measured over.
+/// * now - The current time.
+pub fn rank(
+    competitors: Vec<Competitor>,
+    current_prices: &HashMap<String, f64>,
+    window: LeaderboardWindow,
+    now: NaiveDateTime,
+) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = competitors
+        .into_iter()
+        .map(|competitor| {
+            let equity = competitor.history.equity_at(now, Some(current_prices));
+            let percent_return = competitor.percent_return(equity, window, now);
+            LeaderboardEntry {
+                rank: 0,
+                display_name: competitor.display_name,
+                equity,
+                percent_return,
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.percent_return
+            .partial_cmp(&a.percent_return)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.display_name.cmp(&b.display_name))
+    });
+    let mut previous: Option<(f64, usize)> = None;
+    for (index, entry) in entries.iter_mut().enumerate() {
+        entry.rank = match previous {
+            Some((percent_return, rank)) if percent_return == entry.percent_return => rank,
+            _ => index + 1,
+        };
+        previous = Some((entry.percent_return, entry.rank));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+    use db::{
+        cash::CashTransaction,
+        stock::{Stock, StockTransaction, UserStockResponse},
+        stock_price::StockPrice,
+    };
+    use uuid::Uuid;
+
+    /// A user who deposited 1000, then bought 5 AAPL at 100 on April 1st.
+    fn competitor(name: &str) -> Competitor {
+        let opened = NaiveDate::from_ymd(2019, 3, 1).and_hms(0, 0, 0);
+        let bought = NaiveDate::from_ymd(2019, 4, 1).and_hms(12, 0, 0);
+        let purchase = StockTransaction {
+            uuid: Uuid::new_v4(),
+            user_uuid: Uuid::nil(),
+            stock_uuid: Uuid::nil(),
+            quantity: 5,
+            price_of_stock_at_time_of_trading: 100.0,
+            record_time: bought,
+        };
+        Competitor {
+            display_name: name.to_string(),
+            history: PortfolioHistory {
+                stocks: vec![UserStockResponse {
+                    stock: Stock {
+                        uuid: Uuid::nil(),
+                        symbol: "AAPL".to_string(),
+                        stock_name: "Apple Inc.".to_string(),
+                        exchange: None,
+                    },
+                    transactions: vec![purchase],
+                }],
+                cash_transactions: vec![
+                    CashTransaction {
+                        uuid: Uuid::new_v4(),
+                        user_uuid: Uuid::nil(),
+                        stock_transaction_uuid: None,
+                        amount: 1_000.0,
+                        record_time: opened,
+                    },
+                    CashTransaction {
+                        uuid: Uuid::new_v4(),
+                        user_uuid: Uuid::nil(),
+                        stock_transaction_uuid: Some(purchase.uuid),
+                        amount: -500.0,
+                        record_time: bought,
+                    },
+                ],
+                prices: vec![StockPrice {
+                    uuid: Uuid::new_v4(),
+                    symbol: "AAPL".to_string(),
+                    price: 120.0,
+                    record_time: bought + Duration::days(20),
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn all_time_returns_compare_against_deposits() {
+        let now = NaiveDate::from_ymd(2019, 5, 1).and_hms(0, 0, 0);
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), 140.0);
+
+        let entries = rank(vec![competitor("alice")], &prices, LeaderboardWindow::AllTime, now);
+
+        assert_eq!(entries[0].equity, 1_200.0);
+        assert_eq!(entries[0].percent_return, 20.0);
+    }
+
+    #[test]
+    fn weekly_returns_compare_against_the_start_of_the_week() {
+        let now = NaiveDate::from_ymd(2019, 5, 1).and_hms(0, 0, 0);
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), 140.0);
+
+        let entries = rank(vec![competitor("alice")], &prices, LeaderboardWindow::Week, now);
+
+        // A week ago, the stocks were last seen at 120.
+        assert_eq!(entries[0].percent_return, (1_200.0 - 1_100.0) / 1_100.0 * 100.0);
+    }
+
+    #[test]
+    fn ties_share_a_rank() {
+        let now = NaiveDate::from_ymd(2019, 5, 1).and_hms(0, 0, 0);
+        let mut prices = HashMap::new();
+        prices.insert("AAPL".to_string(), 140.0);
+        let mut loser = competitor("carol");
+        loser.history.stocks[0].transactions[0].price_of_stock_at_time_of_trading = 200.0;
+        loser.history.cash_transactions[1].amount = -1_000.0;
+
+        let entries = rank(
+            vec![loser, competitor("bob"), competitor("alice")],
+            &prices,
+            LeaderboardWindow::AllTime,
+            now,
+        );
+
+        let ranks: Vec<(usize, &str)> = entries
+            .iter()
+            .map(|entry| (entry.rank, entry.display_name.as_str()))
+            .collect();
+        assert_eq!(ranks, vec![(1, "alice"), (1, "bob"), (3, "carol")]);
+    }
+}
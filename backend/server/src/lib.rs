@@ -14,20 +14,44 @@ mod adaptive;
 mod api;
 mod config;
 mod error;
+mod ical;
+mod leaderboard;
+mod listing_refresher;
+mod notifier;
+mod order_matcher;
+mod portfolio;
+mod reminder_scheduler;
 mod server_auth;
 mod state;
 mod static_files;
+mod stock_quote;
 #[cfg(test)]
 mod testing_fixtures;
+mod trade_export;
+mod trading;
+mod trash_purger;
 mod util;
 
 pub use config::Config;
 
 use crate::{
     api::routes,
+    listing_refresher::listing_refresher,
+    order_matcher::order_matcher,
+    reminder_scheduler::reminder_scheduler,
     state::{State, StateConfig},
+    trash_purger::trash_purger,
 };
+use apply::Apply;
+use futures::future::{self, Either};
 use log::info;
+use std::time::Duration;
+
+/// How often the stocks that can be searched for are refreshed from the quote provider.
+const LISTING_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often events that have been in the trash long enough are purged.
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 /// Starts the server.
 pub fn start(config: Config) {
@@ -40,16 +64,33 @@ pub fn start(config: Config) {
         max_pool_size: config.max_pool_size,
         server_lib_root: config.server_lib_root,
         is_production: config.is_production,
+        quote_provider: config.quote_provider,
+        quote_cache_ttl: config.quote_cache_ttl,
     };
 
     let state = State::new(state_config);
     let routes = routes(&state);
+    let matcher = order_matcher(state.db_pool(), state.quotes(), config.order_match_interval);
+    let refresher = listing_refresher(state.db_pool(), state.quotes(), LISTING_REFRESH_INTERVAL);
+    let notifier = config.notifier.into_notifier(state.https(), &state.server_lib_root());
+    let reminders = reminder_scheduler(state.db_pool(), notifier, config.reminder_interval);
+    let purger = trash_purger(state.db_pool(), config.trash_retention, TRASH_PURGE_INTERVAL);
 
-    if config.tls_enabled {
+    let server = if config.tls_enabled {
         warp::serve(routes)
             .tls("tls/cert.pem", "tls/key.rsa")
-            .run(addr);
+            .bind(addr)
+            .apply(Either::A)
     } else {
-        warp::serve(routes).run(addr);
-    }
+        warp::serve(routes).bind(addr).apply(Either::B)
+    };
+
+    // The background tasks run alongside the server on the same runtime.
+    tokio::run(future::lazy(move || {
+        tokio::spawn(matcher);
+        tokio::spawn(refresher);
+        tokio::spawn(reminders);
+        tokio::spawn(purger);
+        server
+    }));
 }
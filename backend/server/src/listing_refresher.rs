@@ -0,0 +1,55 @@
+//! Keeps the cached list of stocks that can be searched for up to date.
+use crate::{error::Error, stock_quote::QuoteProvider};
+use db::stock_listing::StockListing;
+use futures::{future::Future, stream::Stream};
+use log::{error, info};
+use pool::Pool;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::timer::Interval;
+
+/// Replaces the cached listings with the ones from the quote provider, starting immediately, then once every interval.
+///
+/// The returned future never resolves, and should be spawned on the server's runtime.
+///
+/// # Arguments
+/// * pool - The pool of database connections.
+/// * quotes - The source of listings.
+/// * interval - How often the listings are refreshed.
+pub fn listing_refresher(
+    pool: Pool,
+    quotes: Arc<QuoteProvider>,
+    interval: Duration,
+) -> impl Future<Item = (), Error = ()> + Send {
+    info!("Refreshing stock listings every {:?}", interval);
+    Interval::new(Instant::now(), interval)
+        .map_err(|e| error!("Listing refresher timer failed: {}", e))
+        .for_each(move |_| {
+            // A failed refresh keeps the old listings, it will try again next tick.
+            refresh_listings(pool.clone(), quotes.clone()).then(|result| {
+                match result {
+                    Ok(count) => info!("Refreshed {} stock listings", count),
+                    Err(e) => error!("Could not refresh stock listings: {}", e),
+                }
+                Ok(())
+            })
+        })
+}
+
+/// Replaces the cached listings with the ones from the quote provider.
+///
+/// # Return
+/// A Future of the number of listings that were cached.
+pub fn refresh_listings(
+    pool: Pool,
+    quotes: Arc<QuoteProvider>,
+) -> impl Future<Item = usize, Error = Error> {
+    quotes
+        .listings()
+        .and_then(move |listings| -> Result<usize, Error> {
+            let conn = pool.get().map_err(|_| Error::DatabaseUnavailable)?;
+            StockListing::replace_listings(&listings, &conn).map_err(Error::from)
+        })
+}
@@ -0,0 +1,229 @@
+//! Ways of delivering fired event reminders to users.
+//!
+//! The reminder scheduler only ever hands reminders to a `Notifier`,
+//! so how users are reached can be swapped out, or kept on the local machine when developing.
+use crate::{error::Error, state::HttpsClient};
+use apply::Apply;
+use chrono::{DateTime, Utc};
+use db::reminder::FiredReminder;
+use futures::future::{self, Future};
+use hyper::{header::CONTENT_TYPE, Body, Request, Uri};
+use log::info;
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The directory the mail drop writes messages into, relative to the server lib root.
+const DEFAULT_MAIL_DROP: &str = "mail";
+
+/// The address reminder emails are sent from.
+const MAIL_FROM: &str = "Calendar <calendar@localhost>";
+
+/// A future that resolves once a reminder has been delivered.
+pub type DeliveryFuture = Box<Future<Item = (), Error = Error> + Send>;
+
+/// Something that can deliver reminders to users.
+pub trait Notifier: Debug + Send + Sync {
+    /// Delivers a fired reminder to the user it is for.
+    ///
+    /// # Arguments
+    /// * reminder - The reminder, as it is in the outbox.
+    fn deliver(&self, reminder: &FiredReminder) -> DeliveryFuture;
+}
+
+/// Selects which `Notifier` the server will use.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NotifierConfig {
+    /// Write reminders to the server's log.
+    Log,
+    /// POST reminders as JSON to a URL.
+    Webhook(String),
+    /// Write reminders as email messages into a directory, standing in for an SMTP server.
+    MailDrop(Option<PathBuf>),
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        NotifierConfig::Log
+    }
+}
+
+impl NotifierConfig {
+    /// Creates the notifier the configuration selects.
+    ///
+    /// # Arguments
+    /// * client - Https client used by the webhook notifier.
+    /// * server_lib_root - The directory the default mail drop is in.
+    pub fn into_notifier(self, client: HttpsClient, server_lib_root: &Path) -> Arc<Notifier> {
+        match self {
+            NotifierConfig::Log => Arc::new(LogNotifier),
+            NotifierConfig::Webhook(url) => url
+                .parse::<Uri>()
+                .map(|uri| WebhookNotifier::new(client, uri))
+                .expect("Could not parse the reminder webhook url")
+                .apply(Arc::new),
+            NotifierConfig::MailDrop(directory) => directory
+                .unwrap_or_else(|| server_lib_root.join(DEFAULT_MAIL_DROP))
+                .apply(MailDropNotifier::new)
+                .apply(Arc::new),
+        }
+    }
+}
+
+/// Writes reminders to the server's log.
+#[derive(Debug, Clone, Copy)]
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    fn deliver(&self, reminder: &FiredReminder) -> DeliveryFuture {
+        info!(
+            "Reminding user {} that '{}' starts at {} UTC",
+            reminder.user_uuid, reminder.title, reminder.starts_at
+        );
+        future::ok(()).apply(Box::new)
+    }
+}
+
+/// POSTs each reminder as JSON to a URL, which should respond with a success status.
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    /// Https client used to call the webhook.
+    client: HttpsClient,
+    /// Where reminders are sent.
+    uri: Uri,
+}
+
+impl WebhookNotifier {
+    /// Creates a notifier that sends reminders to the uri.
+    pub fn new(client: HttpsClient, uri: Uri) -> Self {
+        WebhookNotifier { client, uri }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn deliver(&self, reminder: &FiredReminder) -> DeliveryFuture {
+        let url = self.uri.to_string();
+        let request = serde_json::to_string(reminder)
+            .map_err(|e| Error::internal_server_error(e.to_string()))
+            .and_then(|json| {
+                Request::post(self.uri.clone())
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(json))
+                    .map_err(|e| Error::internal_server_error(e.to_string()))
+            });
+        let client = self.client.clone();
+        future::result(request)
+            .and_then(move |request| {
+                client
+                    .request(request)
+                    .then(move |response| match response {
+                        Ok(ref response) if response.status().is_success() => Ok(()),
+                        Ok(response) => Err(Error::dependent_connection_failed(
+                            url,
+                            format!("The reminder webhook responded with {}.", response.status()),
+                        )),
+                        Err(e) => Err(Error::dependent_connection_failed(url, e.to_string())),
+                    })
+            })
+            .apply(Box::new)
+    }
+}
+
+/// Writes each reminder as an email message into a directory,
+/// where a developer can read what would have been sent.
+///
+/// Users sign in with Twitter, so there are no real addresses to send to.
+/// Messages are addressed to the user's uuid instead.
+#[derive(Debug, Clone)]
+pub struct MailDropNotifier {
+    /// Where messages are written.
+    directory: PathBuf,
+}
+
+impl MailDropNotifier {
+    /// Creates a notifier that writes messages into the directory, which is created if needed.
+    pub fn new(directory: PathBuf) -> Self {
+        MailDropNotifier { directory }
+    }
+}
+
+impl Notifier for MailDropNotifier {
+    fn deliver(&self, reminder: &FiredReminder) -> DeliveryFuture {
+        let path = self.directory.join(format!("{}.eml", reminder.uuid));
+        fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(&path, mail_message(reminder)))
+            .map_err(|e| {
+                Error::internal_server_error(format!(
+                    "Could not write reminder to {}: {}",
+                    path.display(),
+                    e
+                ))
+            })
+            .apply(future::result)
+            .apply(Box::new)
+    }
+}
+
+/// Writes the reminder as an RFC 5322 email message.
+fn mail_message(reminder: &FiredReminder) -> String {
+    // Line breaks in the title would end the header early.
+    let title: String = reminder
+        .title
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let fired_at = DateTime::<Utc>::from_utc(reminder.fired_at, Utc);
+    [
+        format!("From: {}", MAIL_FROM),
+        format!("To: <{}@localhost>", reminder.user_uuid),
+        format!("Subject: Reminder: {}", title),
+        format!("Date: {}", fired_at.to_rfc2822()),
+        format!("Message-ID: <{}@localhost>", reminder.uuid),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "".to_string(),
+        format!("{} starts at {} UTC.", title, reminder.starts_at),
+        "".to_string(),
+    ]
+    .join("\r\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    #[test]
+    fn mail_drop_writes_a_message() {
+        let at = NaiveDate::from_ymd(2019, 5, 16).and_hms(9, 0, 0);
+        let reminder = FiredReminder {
+            uuid: Uuid::new_v4(),
+            reminder_uuid: Uuid::new_v4(),
+            user_uuid: Uuid::new_v4(),
+            event_uuid: Uuid::new_v4(),
+            title: "Standup\r\nBcc: everyone@example.com".to_string(),
+            starts_at: at,
+            fired_at: at,
+            delivered_at: None,
+            attempts: 0,
+            last_error: None,
+            dismissed_at: None,
+        };
+        let directory = std::env::temp_dir().join(format!("mail-drop-{}", Uuid::new_v4()));
+
+        MailDropNotifier::new(directory.clone())
+            .deliver(&reminder)
+            .wait()
+            .unwrap();
+
+        let message = fs::read_to_string(directory.join(format!("{}.eml", reminder.uuid))).unwrap();
+        assert!(message.contains("Subject: Reminder: Standup  Bcc: everyone@example.com\r\n"));
+        assert!(message.contains(&format!("To: <{}@localhost>\r\n", reminder.user_uuid)));
+        assert!(message.contains("Date: Thu, 16 May 2019 09:00:00 +0000\r\n"));
+        assert!(!message.lines().any(|line| line.starts_with("Bcc:")));
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
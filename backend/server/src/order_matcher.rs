@@ -0,0 +1,208 @@
+//! Fills limit and stop orders in the background as prices change.
+use crate::{error::Error, stock_quote::QuoteProvider, trading};
+use db::order::{Order, OrderKind, OrderStatus, UserOrderResponse};
+use diesel::pg::PgConnection;
+use futures::{
+    future::{self, Future},
+    stream::Stream,
+};
+use log::{error, info, warn};
+use pool::Pool;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::timer::Interval;
+
+/// Periodically checks every open order against the current prices, filling those that have been triggered.
+///
+/// The returned future never resolves, and should be spawned on the server's runtime.
+///
+/// # Arguments
+/// * pool - The pool of database connections.
+/// * quotes - The source of current prices.
+/// * interval - How often the orders are checked.
+pub fn order_matcher(
+    pool: Pool,
+    quotes: Arc<QuoteProvider>,
+    interval: Duration,
+) -> impl Future<Item = (), Error = ()> + Send {
+    info!("Matching orders every {:?}", interval);
+    Interval::new(Instant::now() + interval, interval)
+        .map_err(|e| error!("Order matcher timer failed: {}", e))
+        .for_each(move |_| {
+            // A failed round shouldn't stop the matcher, it will try again next tick.
+            match_open_orders(pool.clone(), quotes.clone()).then(|result| {
+                match result {
+                    Ok(filled) if filled > 0 => info!("Filled {} orders", filled),
+                    Ok(_) => {}
+                    Err(e) => error!("Could not match orders: {}", e),
+                }
+                Ok(())
+            })
+        })
+}
+
+/// Gets the open orders and their current prices, then fills the ones that have been triggered.
+///
+/// # Return
+/// A Future of the number of orders that were filled.
+fn match_open_orders(
+    pool: Pool,
+    quotes: Arc<QuoteProvider>,
+) -> impl Future<Item = usize, Error = Error> {
+    let orders = pool
+        .get()
+        .map_err(|_| Error::DatabaseUnavailable)
+        .and_then(|conn| Order::get_open_orders(&conn).map_err(Error::from));
+
+    future::result(orders)
+        .and_then(move |orders: Vec<UserOrderResponse>| {
+            let mut symbols: Vec<&str> = orders.iter().map(|o| o.stock.symbol.as_str()).collect();
+            symbols.sort();
+            symbols.dedup();
+            quotes.current_prices(&symbols).join(future::ok(orders))
+        })
+        .and_then(move |(prices, orders)| -> Result<usize, Error> {
+            let conn = pool.get().map_err(|_| Error::DatabaseUnavailable)?;
+            Ok(fill_triggered_orders(&prices, orders, &conn))
+        })
+}
+
+/// Fills every order that has been triggered by the current prices.
+///
+/// # Arguments
+/// * prices - The current prices, keyed by symbol.
+/// * orders - The open orders.
+/// * conn - The connection to the database.
+///
+/// # Return
+/// The number of orders that were filled.
+pub fn fill_triggered_orders(
+    prices: &HashMap<String, f64>,
+    orders: Vec<UserOrderResponse>,
+    conn: &PgConnection,
+) -> usize {
+    let mut filled = 0;
+    for order in orders {
+        let price = match prices.get(&order.stock.symbol) {
+            Some(price) if is_triggered(&order.order, *price) => *price,
+            _ => continue,
+        };
+        match fill_order(&order, price, conn) {
+            Ok(order) if order.status == OrderStatus::Filled => filled += 1,
+            Ok(_) => {}
+            Err(e) => warn!("Could not fill order {}: {}", order.order.uuid, e),
+        }
+    }
+    filled
+}
+
+/// Returns true if the order should be filled at the given price.
+///
+/// # Arguments
+/// * order - The open order.
+/// * price - The current price of the stock.
+fn is_triggered(order: &Order, price: f64) -> bool {
+    let is_purchase = order.quantity > 0;
+    match (order.kind, is_purchase) {
+        (OrderKind::Limit, true) | (OrderKind::Stop, false) => price <= order.trigger_price,
+        (OrderKind::Limit, false) | (OrderKind::Stop, true) => price >= order.trigger_price,
+    }
+}
+
+/// Records the trade for an order, and closes it.
+///
+/// If the user can't afford the trade, or doesn't have the stocks to sell, the order is rejected.
+/// If the trade conflicts with a concurrent one, the order is left open to be filled on a later check.
+///
+/// # Arguments
+/// * order - The triggered order.
+/// * price - The price the order is filled at.
+/// * conn - The connection to the database.
+fn fill_order(order: &UserOrderResponse, price: f64, conn: &PgConnection) -> Result<Order, Error> {
+    let result = conn
+        .build_transaction()
+        .serializable()
+        .run(|| -> Result<Order, Error> {
+            // The order may have been cancelled since it was loaded.
+            let current = Order::get_order_for_update(order.order.uuid, conn)?;
+            if current.status != OrderStatus::Open {
+                return Ok(current);
+            }
+            let transaction = trading::execute_trade(
+                current.user_uuid,
+                &order.stock,
+                current.quantity,
+                price,
+                conn,
+            )?;
+            Order::close_order(
+                current.uuid,
+                OrderStatus::Filled,
+                Some(transaction.uuid),
+                conn,
+            )
+            .map_err(Error::from)
+        });
+
+    match result {
+        Err(Error::BadRequest(reason)) => {
+            info!("Rejecting order {}: {}", order.order.uuid, reason);
+            Order::close_order(order.order.uuid, OrderStatus::Rejected, None, conn)
+                .map_err(Error::from)
+        }
+        Err(Error::Conflict { reason, .. }) => {
+            info!("Leaving order {} open: {}", order.order.uuid, reason);
+            Ok(order.order)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn order(kind: OrderKind, quantity: i32, trigger_price: f64) -> Order {
+        Order {
+            uuid: Uuid::new_v4(),
+            user_uuid: Uuid::new_v4(),
+            stock_uuid: Uuid::new_v4(),
+            kind,
+            quantity,
+            trigger_price,
+            status: OrderStatus::Open,
+            stock_transaction_uuid: None,
+            created_at: Utc::now().naive_utc(),
+            closed_at: None,
+        }
+    }
+
+    #[test]
+    fn limit_buy_triggers_at_or_below() {
+        let order = order(OrderKind::Limit, 1, 100.0);
+        assert!(is_triggered(&order, 99.0));
+        assert!(is_triggered(&order, 100.0));
+        assert!(!is_triggered(&order, 101.0));
+    }
+
+    #[test]
+    fn limit_sell_triggers_at_or_above() {
+        let order = order(OrderKind::Limit, -1, 100.0);
+        assert!(!is_triggered(&order, 99.0));
+        assert!(is_triggered(&order, 100.0));
+        assert!(is_triggered(&order, 101.0));
+    }
+
+    #[test]
+    fn stop_loss_triggers_at_or_below() {
+        let order = order(OrderKind::Stop, -1, 100.0);
+        assert!(is_triggered(&order, 99.0));
+        assert!(is_triggered(&order, 100.0));
+        assert!(!is_triggered(&order, 101.0));
+    }
+}
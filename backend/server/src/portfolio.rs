@@ -0,0 +1,442 @@
+//! Lot accounting and valuation over time for the stocks a user has traded.
+//!
+//! Sales are matched against the oldest purchases first (FIFO),
+//! so gains locked in by a sale can be told apart from gains on the stocks that are still held.
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use db::{
+    cash::CashTransaction,
+    stock::{Stock, StockTransaction, UserStockResponse},
+    stock_price::StockPrice,
+};
+use diesel::{pg::PgConnection, result::QueryResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+/// A quantity of stock bought at a single price that hasn't been sold yet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Lot {
+    /// The number of stocks remaining in the lot.
+    quantity: i32,
+    /// The price each stock was bought at.
+    price: f64,
+}
+
+/// The result of matching a stock's sales against its purchases.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LotSummary {
+    /// The number of stocks still held.
+    pub quantity: i32,
+    /// What was paid for the stocks that are still held.
+    pub cost_basis: f64,
+    /// What was paid for every stock that was ever bought.
+    pub invested: f64,
+    /// Gains or losses locked in by sales.
+    pub realized: f64,
+}
+
+impl LotSummary {
+    /// Matches the sales against the purchases, oldest first.
+    ///
+    /// # Arguments
+    /// * transactions - Every transaction the user has made for a single stock, in any order.
+    pub fn fifo(transactions: &[StockTransaction]) -> Self {
+        let mut transactions: Vec<&StockTransaction> = transactions.iter().collect();
+        transactions.sort_by_key(|t| t.record_time);
+
+        let mut lots: VecDeque<Lot> = VecDeque::new();
+        let mut summary = LotSummary::default();
+
+        for transaction in transactions {
+            let price = transaction.price_of_stock_at_time_of_trading;
+            if transaction.quantity > 0 {
+                summary.invested += f64::from(transaction.quantity) * price;
+                lots.push_back(Lot {
+                    quantity: transaction.quantity,
+                    price,
+                });
+                continue;
+            }
+
+            let mut unmatched = -transaction.quantity;
+            while unmatched > 0 {
+                // Trades are checked against holdings, so running out of lots means the history is inconsistent.
+                let lot = match lots.front_mut() {
+                    Some(lot) => lot,
+                    None => break,
+                };
+                let sold = lot.quantity.min(unmatched);
+                summary.realized += f64::from(sold) * (price - lot.price);
+                lot.quantity -= sold;
+                unmatched -= sold;
+                if lot.quantity == 0 {
+                    lots.pop_front();
+                }
+            }
+        }
+
+        summary.quantity = lots.iter().map(|lot| lot.quantity).sum();
+        summary.cost_basis = lots
+            .iter()
+            .map(|lot| f64::from(lot.quantity) * lot.price)
+            .sum();
+        summary
+    }
+
+    /// The average price paid for the stocks that are still held.
+    pub fn average_price(&self) -> f64 {
+        if self.quantity == 0 {
+            0.0
+        } else {
+            self.cost_basis / f64::from(self.quantity)
+        }
+    }
+
+    /// The current value of the stocks that are still held.
+    pub fn market_value(&self, price: f64) -> f64 {
+        f64::from(self.quantity) * price
+    }
+
+    /// Gains or losses on the stocks that are still held, if they were sold at the given price.
+    pub fn unrealized(&self, price: f64) -> f64 {
+        self.market_value(price) - self.cost_basis
+    }
+}
+
+/// The realized and unrealized gains, as a percentage of everything that was invested.
+///
+/// # Arguments
+/// * realized - Gains or losses locked in by sales.
+/// * unrealized - Gains or losses on the stocks that are still held.
+/// * invested - What was paid for every stock that was ever bought.
+pub fn percent_return(realized: f64, unrealized: f64, invested: f64) -> f64 {
+    if invested == 0.0 {
+        0.0
+    } else {
+        (realized + unrealized) / invested * 100.0
+    }
+}
+
+/// The value of a user's account at the end of a day.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EquityPoint {
+    /// The day being valued.
+    pub date: NaiveDate,
+    /// Cash held at the end of the day.
+    pub cash: f64,
+    /// The value of the stocks held at the end of the day.
+    pub holdings_value: f64,
+    /// Cash plus holdings.
+    pub equity: f64,
+}
+
+/// Everything needed to rebuild a user's portfolio over time.
+#[derive(Clone, Debug)]
+pub struct PortfolioHistory {
+    /// The user's stocks and their transactions.
+    pub stocks: Vec<UserStockResponse>,
+    /// Every cash transaction the user has made.
+    pub cash_transactions: Vec<CashTransaction>,
+    /// The prices recorded for the user's stocks since their first trade.
+    pub prices: Vec<StockPrice>,
+}
+
+impl PortfolioHistory {
+    /// Loads the user's trades, cash, and the prices recorded for their stocks.
+    pub fn load(user_uuid: Uuid, conn: &PgConnection) -> QueryResult<Self> {
+        let stocks = Stock::get_stocks_belonging_to_user(user_uuid, conn)?;
+        let cash_transactions = CashTransaction::get_cash_transactions_for_user(user_uuid, conn)?;
+        let prices = match first_trade_time(&stocks) {
+            Some(since) => {
+                let symbols: Vec<String> = stocks
+                    .iter()
+                    .map(|stock| stock.stock.symbol.to_uppercase())
+                    .collect();
+                StockPrice::get_prices_since(&symbols, since, conn)?
+            }
+            None => vec![],
+        };
+        Ok(PortfolioHistory {
+            stocks,
+            cash_transactions,
+            prices,
+        })
+    }
+
+    /// The symbols of every stock the user has traded.
+    pub fn symbols(&self) -> Vec<&str> {
+        self.stocks
+            .iter()
+            .map(|stock| stock.stock.symbol.as_str())
+            .collect()
+    }
+
+    /// Values the user's account at the end of each period, from the day of their first trade until today.
+    ///
+    /// Stocks are valued at the last price observed before the end of each day,
+    /// either recorded from the quote provider, or paid in a trade.
+    /// Today's value uses the current prices where they are available.
+    ///
+    /// # Arguments
+    /// * current_prices - The current prices, keyed by symbol.
+    /// * step - The time between points.
+    /// * today - The last day to value.
+    pub fn equity_curve(
+        &self,
+        current_prices: &HashMap<String, f64>,
+        step: Duration,
+        today: NaiveDate,
+    ) -> Vec<EquityPoint> {
+        let first_day = match first_trade_time(&self.stocks) {
+            Some(time) => time.date(),
+            None => return vec![],
+        };
+        let observed_prices = self.observed_prices();
+
+        let mut days = vec![];
+        let mut day = first_day;
+        while day < today {
+            days.push(day);
+            day = day + step;
+        }
+        days.push(today);
+
+        days.into_iter()
+            .map(|day| {
+                let end_of_day = (day + Duration::days(1)).and_hms(0, 0, 0);
+                let current = if day == today {
+                    Some(current_prices)
+                } else {
+                    None
+                };
+                let (cash, holdings_value) = self.value_before(&observed_prices, end_of_day, current);
+                EquityPoint {
+                    date: day,
+                    cash,
+                    holdings_value,
+                    equity: cash + holdings_value,
+                }
+            })
+            .collect()
+    }
+
+    /// Values the user's account just before a moment in time.
+    ///
+    /// Stocks are valued at the last price observed before that moment,
+    /// unless current prices are provided for them.
+    ///
+    /// # Arguments
+    /// * time - The moment to value the account at.
+    /// * current_prices - The current prices, keyed by symbol, if the account is being valued now.
+    pub fn equity_at(&self, time: NaiveDateTime, current_prices: Option<&HashMap<String, f64>>) -> f64 {
+        let (cash, holdings_value) = self.value_before(&self.observed_prices(), time, current_prices);
+        cash + holdings_value
+    }
+
+    /// Every price observed for each stock, oldest first.
+    ///
+    /// Prices are either recorded from the quote provider, or paid in a trade.
+    fn observed_prices(&self) -> Vec<(&UserStockResponse, Vec<(NaiveDateTime, f64)>)> {
+        self.stocks
+            .iter()
+            .map(|stock| {
+                let symbol = stock.stock.symbol.to_uppercase();
+                let mut observed: Vec<(NaiveDateTime, f64)> = self
+                    .prices
+                    .iter()
+                    .filter(|price| price.symbol == symbol)
+                    .map(|price| (price.record_time, price.price))
+                    .chain(
+                        stock
+                            .transactions
+                            .iter()
+                            .map(|t| (t.record_time, t.price_of_stock_at_time_of_trading)),
+                    )
+                    .collect();
+                observed.sort_by_key(|(time, _)| *time);
+                (stock, observed)
+            })
+            .collect()
+    }
+
+    /// The cash and the value of the stocks held before a moment in time.
+    ///
+    /// # Arguments
+    /// * observed_prices - Every price observed for each stock, oldest first.
+    /// * end - Transactions and prices at or after this moment are ignored.
+    /// * current_prices - Prices that take precedence over the observed ones.
+    fn value_before(
+        &self,
+        observed_prices: &[(&UserStockResponse, Vec<(NaiveDateTime, f64)>)],
+        end: NaiveDateTime,
+        current_prices: Option<&HashMap<String, f64>>,
+    ) -> (f64, f64) {
+        let cash = self
+            .cash_transactions
+            .iter()
+            .filter(|t| t.record_time < end)
+            .map(|t| t.amount)
+            .sum();
+        let holdings_value = observed_prices
+            .iter()
+            .map(|(stock, observed)| {
+                let quantity: i32 = stock
+                    .transactions
+                    .iter()
+                    .filter(|t| t.record_time < end)
+                    .map(|t| t.quantity)
+                    .sum();
+                if quantity == 0 {
+                    return 0.0;
+                }
+                let price = current_prices
+                    .and_then(|prices| prices.get(&stock.stock.symbol).cloned())
+                    .or_else(|| {
+                        observed
+                            .iter()
+                            .take_while(|(time, _)| *time < end)
+                            .last()
+                            .map(|(_, price)| *price)
+                    })
+                    .unwrap_or(0.0);
+                f64::from(quantity) * price
+            })
+            .sum();
+        (cash, holdings_value)
+    }
+}
+
+/// The time of the user's first trade, if they have made one.
+fn first_trade_time(stocks: &[UserStockResponse]) -> Option<NaiveDateTime> {
+    stocks
+        .iter()
+        .flat_map(|stock| stock.transactions.iter())
+        .map(|t| t.record_time)
+        .min()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+
+    fn transactions(trades: &[(i32, f64)]) -> Vec<StockTransaction> {
+        let start = Utc::now().naive_utc();
+        trades
+            .iter()
+            .enumerate()
+            .map(|(i, (quantity, price))| StockTransaction {
+                uuid: Uuid::new_v4(),
+                user_uuid: Uuid::nil(),
+                stock_uuid: Uuid::nil(),
+                quantity: *quantity,
+                price_of_stock_at_time_of_trading: *price,
+                record_time: start + Duration::minutes(i as i64),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn purchases_are_unrealized() {
+        let summary = LotSummary::fifo(&transactions(&[(2, 100.0), (2, 200.0)]));
+        assert_eq!(summary.quantity, 4);
+        assert_eq!(summary.cost_basis, 600.0);
+        assert_eq!(summary.average_price(), 150.0);
+        assert_eq!(summary.realized, 0.0);
+        assert_eq!(summary.unrealized(250.0), 400.0);
+    }
+
+    #[test]
+    fn sales_match_oldest_purchases_first() {
+        let summary = LotSummary::fifo(&transactions(&[(2, 100.0), (2, 200.0), (-3, 300.0)]));
+        // Two sold from the first lot, and one from the second.
+        assert_eq!(summary.realized, 2.0 * 200.0 + 100.0);
+        assert_eq!(summary.quantity, 1);
+        assert_eq!(summary.cost_basis, 200.0);
+        assert_eq!(summary.unrealized(300.0), 100.0);
+        assert_eq!(summary.invested, 600.0);
+    }
+
+    #[test]
+    fn order_of_rows_does_not_matter() {
+        let mut rows = transactions(&[(2, 100.0), (-1, 50.0)]);
+        rows.reverse();
+        let summary = LotSummary::fifo(&rows);
+        assert_eq!(summary.realized, -50.0);
+        assert_eq!(summary.quantity, 1);
+    }
+
+    #[test]
+    fn equity_curve_values_each_day_at_its_last_price() {
+        let start = NaiveDate::from_ymd(2019, 4, 1).and_hms(12, 0, 0);
+        let stock = Stock {
+            uuid: Uuid::nil(),
+            symbol: "AAPL".to_string(),
+            stock_name: "AAPL".to_string(),
+            exchange: None,
+        };
+        let purchase = StockTransaction {
+            uuid: Uuid::new_v4(),
+            user_uuid: Uuid::nil(),
+            stock_uuid: Uuid::nil(),
+            quantity: 2,
+            price_of_stock_at_time_of_trading: 100.0,
+            record_time: start,
+        };
+        let history = PortfolioHistory {
+            stocks: vec![UserStockResponse {
+                stock,
+                transactions: vec![purchase],
+            }],
+            cash_transactions: vec![
+                CashTransaction {
+                    uuid: Uuid::new_v4(),
+                    user_uuid: Uuid::nil(),
+                    stock_transaction_uuid: None,
+                    amount: 1_000.0,
+                    record_time: start - Duration::days(1),
+                },
+                CashTransaction {
+                    uuid: Uuid::new_v4(),
+                    user_uuid: Uuid::nil(),
+                    stock_transaction_uuid: Some(purchase.uuid),
+                    amount: -200.0,
+                    record_time: start,
+                },
+            ],
+            prices: vec![StockPrice {
+                uuid: Uuid::new_v4(),
+                symbol: "AAPL".to_string(),
+                price: 110.0,
+                record_time: start + Duration::days(1),
+            }],
+        };
+        let mut current_prices = HashMap::new();
+        current_prices.insert("AAPL".to_string(), 120.0);
+
+        let curve = history.equity_curve(&current_prices, Duration::days(1), start.date() + Duration::days(3));
+
+        let equity: Vec<f64> = curve.iter().map(|point| point.equity).collect();
+        // Bought at 100, recorded at 110 on the second day, and currently 120.
+        assert_eq!(equity, vec![1_000.0, 1_020.0, 1_020.0, 1_040.0]);
+        assert_eq!(curve[0].cash, 800.0);
+        assert_eq!(curve[0].date, start.date());
+    }
+
+    #[test]
+    fn no_trades_has_no_curve() {
+        let history = PortfolioHistory {
+            stocks: vec![],
+            cash_transactions: vec![],
+            prices: vec![],
+        };
+        let today = NaiveDate::from_ymd(2019, 4, 1);
+        assert!(history.equity_curve(&HashMap::new(), Duration::days(1), today).is_empty());
+    }
+
+    #[test]
+    fn percent_return_of_nothing_is_zero() {
+        assert_eq!(percent_return(0.0, 0.0, 0.0), 0.0);
+        assert_eq!(percent_return(50.0, 50.0, 200.0), 50.0);
+    }
+}
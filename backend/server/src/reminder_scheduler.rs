@@ -0,0 +1,99 @@
+//! Fires event reminders in the background, and delivers them through a notifier.
+use crate::{error::Error, notifier::Notifier};
+use chrono::Utc;
+use db::reminder::FiredReminder;
+use futures::{
+    future::{self, Future},
+    stream::Stream,
+};
+use log::{error, info, warn};
+use pool::Pool;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::timer::Interval;
+
+/// How late a reminder can still fire, if the scheduler wasn't running when it came due.
+const LATE_LIMIT_MINUTES: i64 = 60;
+
+/// How many times delivering a reminder is tried before it is given up on.
+const MAX_DELIVERY_ATTEMPTS: i32 = 5;
+
+/// Periodically fires the reminders that have come due, and delivers them.
+///
+/// The returned future never resolves, and should be spawned on the server's runtime.
+///
+/// # Arguments
+/// * pool - The pool of database connections.
+/// * notifier - What reminders are delivered through.
+/// * interval - How often reminders are checked.
+pub fn reminder_scheduler(
+    pool: Pool,
+    notifier: Arc<Notifier>,
+    interval: Duration,
+) -> impl Future<Item = (), Error = ()> + Send {
+    info!("Sending reminders every {:?} through {:?}", interval, notifier);
+    Interval::new(Instant::now(), interval)
+        .map_err(|e| error!("Reminder scheduler timer failed: {}", e))
+        .for_each(move |_| {
+            // A failed round shouldn't stop the scheduler, it will try again next tick.
+            send_reminders(pool.clone(), notifier.clone()).then(|result| {
+                match result {
+                    Ok((0, 0)) => {}
+                    Ok((fired, delivered)) => {
+                        info!("Fired {} reminders, delivered {}", fired, delivered)
+                    }
+                    Err(e) => error!("Could not send reminders: {}", e),
+                }
+                Ok(())
+            })
+        })
+}
+
+/// Adds the reminders that have come due to the outbox, then delivers every undelivered reminder.
+///
+/// Reminders that can't be delivered stay in the outbox, and are tried again next time.
+///
+/// # Return
+/// A Future of the number of reminders that fired, and the number that were delivered.
+pub fn send_reminders(
+    pool: Pool,
+    notifier: Arc<Notifier>,
+) -> impl Future<Item = (usize, usize), Error = Error> {
+    let outbox = pool
+        .get()
+        .map_err(|_| Error::DatabaseUnavailable)
+        .and_then(|conn| -> Result<(usize, Vec<FiredReminder>), Error> {
+            let late_limit = chrono::Duration::minutes(LATE_LIMIT_MINUTES);
+            let now = Utc::now().naive_utc();
+            let fired = FiredReminder::fire_due_reminders(now, late_limit, &conn)?;
+            let undelivered = FiredReminder::undelivered(MAX_DELIVERY_ATTEMPTS, &conn)?;
+            Ok((fired, undelivered))
+        });
+
+    future::result(outbox).and_then(move |(fired, undelivered)| {
+        let deliveries = undelivered.into_iter().map(move |reminder| {
+            let pool = pool.clone();
+            notifier
+                .deliver(&reminder)
+                .then(move |result| -> Result<bool, Error> {
+                    let conn = pool.get().map_err(|_| Error::DatabaseUnavailable)?;
+                    match result {
+                        Ok(()) => {
+                            let now = Utc::now().naive_utc();
+                            FiredReminder::mark_delivered(reminder.uuid, now, &conn)?;
+                            Ok(true)
+                        }
+                        Err(e) => {
+                            warn!("Could not deliver reminder {}: {}", reminder.uuid, e);
+                            FiredReminder::mark_failed(reminder.uuid, e.to_string(), &conn)?;
+                            Ok(false)
+                        }
+                    }
+                })
+        });
+        future::join_all(deliveries)
+            .map(move |delivered| (fired, delivered.into_iter().filter(|d| *d).count()))
+    })
+}
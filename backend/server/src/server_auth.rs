@@ -140,6 +140,8 @@ mod unit_test {
             max_pool_size: None,
             server_lib_root: None,
             is_production: false,
+            quote_provider: Default::default(),
+            quote_cache_ttl: None,
         };
         let state = State::new(conf);
         let uuid = Uuid::new_v4();
@@ -161,6 +163,8 @@ mod unit_test {
             max_pool_size: None,
             server_lib_root: None,
             is_production: false,
+            quote_provider: Default::default(),
+            quote_cache_ttl: None,
         };
 
         let state = State::new(conf);
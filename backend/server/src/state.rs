@@ -1,5 +1,12 @@
 //! Represents the shared server resources that all requests may utilize.
-use crate::{error::Error, server_auth::secret_filter};
+use crate::{
+    error::Error,
+    server_auth::secret_filter,
+    stock_quote::{
+        CachingQuoteProvider, FixtureQuoteProvider, IexQuoteProvider, QuoteProvider,
+        QuoteProviderConfig,
+    },
+};
 
 use apply::Apply;
 use authorization::Secret;
@@ -11,9 +18,15 @@ use hyper::{
 use hyper_tls::HttpsConnector;
 use pool::{init_pool, Pool, PoolConfig, PooledConn, DATABASE_URL};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 use warp::{Filter, Rejection};
 
+/// The location of the stock quote fixture, relative to the server lib root.
+const DEFAULT_QUOTE_FIXTURE: &str = "fixtures/quotes.json";
+
+/// How long fetched stock quotes are reused for, if unspecified.
+const DEFAULT_QUOTE_CACHE_TTL: Duration = Duration::from_secs(60);
+
 /// Simplified type for representing a HttpClient.
 pub type HttpsClient = Client<HttpsConnector<HttpConnector<GaiResolver>>, Body>;
 
@@ -30,6 +43,8 @@ pub struct State {
     secret: Secret,
     /// Https client
     https: HttpsClient,
+    /// Source of stock prices.
+    quote_provider: Arc<QuoteProvider>,
     /// Twitter consumer token
     twitter_consumer_token: KeyPair,
     /// The path to the server directory.
@@ -48,6 +63,8 @@ pub struct StateConfig {
     pub max_pool_size: Option<u32>,
     pub server_lib_root: Option<PathBuf>,
     pub is_production: bool,
+    pub quote_provider: QuoteProviderConfig,
+    pub quote_cache_ttl: Option<Duration>,
 }
 
 impl State {
@@ -76,10 +93,23 @@ impl State {
 
         let root = conf.server_lib_root.unwrap_or_else(|| PathBuf::from("./"));
 
+        let vendor: Arc<QuoteProvider> = match conf.quote_provider {
+            QuoteProviderConfig::Iex => IexQuoteProvider::new(client.clone()).apply(Arc::new),
+            QuoteProviderConfig::Fixture(path) => {
+                let path = path.unwrap_or_else(|| root.join(DEFAULT_QUOTE_FIXTURE));
+                FixtureQuoteProvider::from_file(path)
+                    .expect("Could not load stock quote fixture")
+                    .apply(Arc::new)
+            }
+        };
+        let ttl = conf.quote_cache_ttl.unwrap_or(DEFAULT_QUOTE_CACHE_TTL);
+        let quote_provider = CachingQuoteProvider::new(vendor, pool.clone(), ttl).apply(Arc::new);
+
         State {
             database_connection_pool: pool, //db_filter(pool),
             secret,
             https: client,
+            quote_provider,
             twitter_consumer_token: twitter_con_token.clone(),
             server_lib_root: root,
             is_production: conf.is_production,
@@ -124,6 +154,20 @@ impl State {
         http_filter(self.https.clone())
     }
 
+    /// Gets the provider of stock prices.
+    pub fn quote_provider(
+        &self,
+    ) -> impl Filter<Extract = (Arc<QuoteProvider>,), Error = Rejection> + Clone {
+        fn quote_provider_filter(
+            quote_provider: Arc<QuoteProvider>,
+        ) -> impl Filter<Extract = (Arc<QuoteProvider>,), Error = Rejection> + Clone {
+            warp::any().and_then(move || -> Result<Arc<QuoteProvider>, Rejection> {
+                Ok(quote_provider.clone())
+            })
+        }
+        quote_provider_filter(self.quote_provider.clone())
+    }
+
     /// Access the twitter consumer token.
     pub fn twitter_consumer_token(&self) -> impl Filter<Extract = (KeyPair,), Error = Rejection> + Clone {
         fn twitter_consumer_token_filter(twitter_consumer_token: KeyPair) -> impl Filter<Extract = (KeyPair,), Error = Rejection> + Clone {
@@ -141,11 +185,25 @@ impl State {
         self.is_production
     }
 
+    /// Gets the pool of database connections, for work done outside of a request.
+    pub fn db_pool(&self) -> Pool {
+        self.database_connection_pool.clone()
+    }
+
+    /// Gets the provider of stock prices, for work done outside of a request.
+    pub fn quotes(&self) -> Arc<QuoteProvider> {
+        self.quote_provider.clone()
+    }
+
+    /// Gets the https client, for work done outside of a request.
+    pub fn https(&self) -> HttpsClient {
+        self.https.clone()
+    }
+
     /// Creates a new state object from an existing object pool.
     /// This is useful if using fixtures.
     #[cfg(test)]
     pub fn testing_init(pool: Pool, secret: Secret) -> Self {
-        use std::time::Duration;
         let https = HttpsConnector::new(1).unwrap();
         let client = Client::builder()
             .keep_alive_timeout(Some(Duration::new(12, 0)))
@@ -153,10 +211,18 @@ impl State {
 
         let twitter_con_token = get_twitter_con_token();
 
+        // Tests should not depend on the availability or prices of a remote vendor.
+        let quote_provider = CachingQuoteProvider::new(
+            Arc::new(FixtureQuoteProvider::testing_quotes()),
+            pool.clone(),
+            DEFAULT_QUOTE_CACHE_TTL,
+        );
+
         State {
             database_connection_pool: pool,
             secret,
             https: client,
+            quote_provider: Arc::new(quote_provider),
             twitter_consumer_token: twitter_con_token,
             server_lib_root: PathBuf::from("./"), // THIS makes the assumption that the tests are run from the backend/server dir.
             is_production: false,
@@ -0,0 +1,734 @@
+//! Sources of stock prices for the market api.
+//!
+//! Routes should only ever ask a `QuoteProvider` for prices,
+//! so that the vendor can be swapped out, or replaced with fixture data when testing or developing offline.
+use crate::{error::Error, state::HttpsClient};
+use apply::Apply;
+use chrono::Utc;
+use db::{
+    stock_listing::StockListing,
+    stock_price::{NewStockPrice, StockPrice},
+};
+use futures::{
+    future::{self, Future},
+    stream::Stream,
+    Async,
+};
+use hyper::{Chunk, StatusCode, Uri};
+use log::{error, info};
+use pool::Pool;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A future that resolves to the current price of a single stock.
+pub type PriceFuture = Box<Future<Item = f64, Error = Error> + Send>;
+
+/// A future that resolves to the current prices of many stocks, keyed by their symbols.
+pub type PricesFuture = Box<Future<Item = HashMap<String, f64>, Error = Error> + Send>;
+
+/// A future that resolves to the company behind a single stock.
+pub type ListingFuture = Box<Future<Item = StockListing, Error = Error> + Send>;
+
+/// A future that resolves to every stock a provider supports.
+pub type ListingsFuture = Box<Future<Item = Vec<StockListing>, Error = Error> + Send>;
+
+/// Something that can provide current prices for stock symbols.
+pub trait QuoteProvider: Debug + Send + Sync {
+    /// Gets the current price for a single stock.
+    ///
+    /// # Arguments
+    /// * stock_symbol - The ticker symbol of the stock.
+    fn current_price(&self, stock_symbol: &str) -> PriceFuture;
+
+    /// Gets the current prices for a set of stocks.
+    ///
+    /// The returned map is keyed by the symbols exactly as they were provided.
+    /// Symbols that the provider has no price for are absent from the map.
+    ///
+    /// # Arguments
+    /// * stock_symbols - The ticker symbols of the stocks.
+    fn current_prices(&self, stock_symbols: &[&str]) -> PricesFuture;
+
+    /// Gets the company and exchange of a stock.
+    ///
+    /// Symbols that the provider doesn't know of resolve to a bad request.
+    ///
+    /// # Arguments
+    /// * stock_symbol - The upper-case ticker symbol of the stock.
+    fn listing(&self, stock_symbol: &str) -> ListingFuture;
+
+    /// Gets every stock the provider supports.
+    fn listings(&self) -> ListingsFuture;
+}
+
+/// Selects which `QuoteProvider` the server will use.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuoteProviderConfig {
+    /// Get prices from the IEX api.
+    Iex,
+    /// Get prices from a JSON file mapping symbols to prices.
+    Fixture(Option<PathBuf>),
+}
+
+impl Default for QuoteProviderConfig {
+    fn default() -> Self {
+        QuoteProviderConfig::Iex
+    }
+}
+
+/// Gets prices from [IEX](https://iextrading.com/developer/docs/).
+#[derive(Debug, Clone)]
+pub struct IexQuoteProvider {
+    /// Https client used to talk to IEX.
+    client: HttpsClient,
+}
+
+impl IexQuoteProvider {
+    /// Creates a new provider that uses the provided client.
+    pub fn new(client: HttpsClient) -> Self {
+        IexQuoteProvider { client }
+    }
+}
+
+impl QuoteProvider for IexQuoteProvider {
+    fn current_price(&self, stock_symbol: &str) -> PriceFuture {
+        get_current_price(stock_symbol, &self.client).apply(Box::new)
+    }
+
+    fn current_prices(&self, stock_symbols: &[&str]) -> PricesFuture {
+        get_current_prices(stock_symbols, &self.client).apply(Box::new)
+    }
+
+    fn listing(&self, stock_symbol: &str) -> ListingFuture {
+        get_listing(stock_symbol, &self.client).apply(Box::new)
+    }
+
+    fn listings(&self) -> ListingsFuture {
+        get_listings(&self.client).apply(Box::new)
+    }
+}
+
+/// A quote in a fixture file.
+///
+/// It is either just the price, or the price along with the details of the company.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum FixtureQuote {
+    /// Only the price is known, so the symbol stands in for the company name.
+    Price(f64),
+    /// The price and the details of the company.
+    Listing {
+        /// The current price.
+        price: f64,
+        /// The name of the company.
+        name: String,
+        /// The exchange the stock is listed on.
+        exchange: Option<String>,
+    },
+}
+
+/// Serves prices from a fixed set of quotes held in memory.
+///
+/// This allows the market to be used without network access.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureQuoteProvider {
+    /// Prices, keyed by upper-case symbols.
+    prices: HashMap<String, f64>,
+    /// Companies, keyed by upper-case symbols.
+    listings: HashMap<String, StockListing>,
+}
+
+impl FixtureQuoteProvider {
+    /// Creates a new provider from a map of symbols to prices.
+    pub fn new(prices: HashMap<String, f64>) -> Self {
+        prices
+            .into_iter()
+            .map(|(symbol, price)| (symbol, FixtureQuote::Price(price)))
+            .collect::<HashMap<_, _>>()
+            .apply(Self::from_quotes)
+    }
+
+    /// Creates a new provider from a map of symbols to quotes.
+    fn from_quotes(quotes: HashMap<String, FixtureQuote>) -> Self {
+        let mut provider = FixtureQuoteProvider::default();
+        for (symbol, quote) in quotes {
+            let symbol = symbol.to_uppercase();
+            let (price, name, exchange) = match quote {
+                FixtureQuote::Price(price) => (price, symbol.clone(), None),
+                FixtureQuote::Listing {
+                    price,
+                    name,
+                    exchange,
+                } => (price, name, exchange),
+            };
+            let listing = StockListing {
+                symbol: symbol.clone(),
+                name,
+                exchange,
+            };
+            provider.prices.insert(symbol.clone(), price);
+            provider.listings.insert(symbol, listing);
+        }
+        provider
+    }
+
+    /// Reads the quotes from a JSON file in the form:
+    /// `{"AAPL": {"price": 170.67, "name": "Apple Inc.", "exchange": "Nasdaq Global Select"}, "FB": 165.465}`.
+    ///
+    /// # Arguments
+    /// * path - The path to the file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| format!("Could not open quote fixture {}: {}", path.display(), e))?;
+        serde_json::from_reader::<_, HashMap<String, FixtureQuote>>(file)
+            .map_err(|e| format!("Could not parse quote fixture {}: {}", path.display(), e))
+            .map(Self::from_quotes)
+    }
+
+    /// A small, fixed set of quotes useful for testing.
+    pub fn testing_quotes() -> Self {
+        [
+            ("AAPL", 170.67, "Apple Inc."),
+            ("FB", 165.465, "Facebook Inc."),
+            ("MSFT", 112.5, "Microsoft Corporation"),
+            ("GOOG", 1_150.0, "Alphabet Inc."),
+        ]
+        .iter()
+        .map(|(symbol, price, name)| {
+            let quote = FixtureQuote::Listing {
+                price: *price,
+                name: name.to_string(),
+                exchange: Some("Nasdaq Global Select".to_string()),
+            };
+            (symbol.to_string(), quote)
+        })
+        .collect::<HashMap<_, _>>()
+        .apply(Self::from_quotes)
+    }
+
+    /// Gets the price for the symbol, if it is known.
+    fn price(&self, stock_symbol: &str) -> Option<f64> {
+        self.prices.get(&stock_symbol.to_uppercase()).cloned()
+    }
+}
+
+impl QuoteProvider for FixtureQuoteProvider {
+    fn current_price(&self, stock_symbol: &str) -> PriceFuture {
+        self.price(stock_symbol)
+            .ok_or_else(|| {
+                Error::bad_request(format!("No quote is available for {}.", stock_symbol))
+            })
+            .apply(future::result)
+            .apply(Box::new)
+    }
+
+    fn current_prices(&self, stock_symbols: &[&str]) -> PricesFuture {
+        // Like IEX, unknown symbols are left out of the response.
+        stock_symbols
+            .iter()
+            .filter_map(|symbol| self.price(symbol).map(|price| (symbol.to_string(), price)))
+            .collect::<HashMap<String, f64>>()
+            .apply(future::ok)
+            .apply(Box::new)
+    }
+
+    fn listing(&self, stock_symbol: &str) -> ListingFuture {
+        self.listings
+            .get(&stock_symbol.to_uppercase())
+            .cloned()
+            .ok_or_else(|| {
+                Error::bad_request(format!("{} is not a listed stock symbol.", stock_symbol))
+            })
+            .apply(future::result)
+            .apply(Box::new)
+    }
+
+    fn listings(&self) -> ListingsFuture {
+        let mut listings: Vec<StockListing> = self.listings.values().cloned().collect();
+        listings.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        listings.apply(future::ok).apply(Box::new)
+    }
+}
+
+/// Gets the current price for a stock from IEX.
+fn get_current_price(
+    stock_symbol: &str,
+    client: &HttpsClient,
+) -> impl Future<Item = f64, Error = Error> {
+    info!("Getting current price for stock: {}", stock_symbol);
+    let stock_symbol_copy = stock_symbol.to_string();
+
+    let uri = format!(
+        "https://api.iextrading.com/1.0/stock/{}/price",
+        stock_symbol
+    )
+    .parse::<Uri>()
+    .map_err(|e| Error::bad_request(format!("{:?}", e)));
+
+    match uri {
+        Ok(uri) => {
+            let uri_string = uri.to_string(); // create this here, so it can be moved into the closure.
+            client
+                .get(uri.clone())
+                .and_then(|res| {
+                    res.into_body().concat2() // Await the whole body
+                })
+                .map_err(move |_| {
+                    Error::dependent_connection_failed(
+                        uri.to_string(),
+                        format!("Could not get current price for {}.", stock_symbol_copy),
+                    )
+                })
+                .and_then(move |chunk: Chunk| -> Result<f64, Error> {
+                    let v = chunk.to_vec();
+                    let body = String::from_utf8_lossy(&v).to_string();
+                    body.parse::<f64>().map_err(move |_| -> Error {
+                        Error::internal_server_error(format!(
+                            "Could not parse body of dependent connection: {}, body: {}",
+                            uri_string, body
+                        ))
+                    })
+                })
+                .apply(future::Either::A)
+        }
+        Err(e) => e.apply(future::err).apply(future::Either::B),
+    }
+}
+
+/// Get the current prices for a set of stocks from IEX.
+fn get_current_prices(
+    stock_symbols: &[&str],
+    client: &HttpsClient,
+) -> future::Either<
+    impl Future<Item = HashMap<String, f64>, Error = Error>,
+    impl Future<Item = HashMap<String, f64>, Error = Error>
+    >
+{
+    if stock_symbols.len() == 0 {
+        info!("Can't get stock prices, because no stocks were provided");
+        future::ok::<HashMap<String, f64>, Error>(HashMap::new()).apply(future::Either::A)
+    } else {
+        info!("Getting current prices for multiple stocks: {:?}", stock_symbols);
+        let uri: Uri = format!(
+            "https://api.iextrading.com/1.0/stock/market/batch?symbols={}&types=price",
+            stock_symbols.join(",")
+        )
+            .parse()
+            .unwrap();
+        info!("Getting current prices for: {}", uri);
+        let requested_symbols: Vec<String> = stock_symbols.iter().map(|s| s.to_string()).collect();
+
+        // handle json in the form: {"AAPL":{"price":170.67},"FB":{"price":165.465}}
+        #[derive(Serialize, Deserialize, Debug)]
+        struct Price {
+            price: f64,
+        }
+        client
+            .get(uri.clone())
+            .and_then(|res| {
+                res.into_body().concat2() // Await the whole body
+            })
+            .map_err(move |_| {
+                Error::dependent_connection_failed(uri.to_string(), "Could not get current stocks.")
+            })
+            .and_then(move |chunk: Chunk| {
+                let v = chunk.to_vec();
+                let body = String::from_utf8_lossy(&v).to_string();
+                serde_json::from_str::<HashMap<String, Price>>(&body)
+                    .map(|r| {
+                        info!("Got current prices: {:#?}", r);
+                        // IEX keys its response by upper-case symbol,
+                        // so map them back onto the symbols that were asked for.
+                        requested_symbols
+                            .into_iter()
+                            .filter_map(|symbol| {
+                                r.get(&symbol.to_uppercase())
+                                    .map(|p| (symbol, p.price))
+                            })
+                            .collect()
+                    })
+                    .map_err(|_| {
+                        Error::internal_server_error(
+                            "Could not get current prices"
+                        )
+                    })
+            })
+            .apply(future::Either::B)
+    }
+}
+
+/// Gets the company behind a stock from IEX.
+fn get_listing(
+    stock_symbol: &str,
+    client: &HttpsClient,
+) -> impl Future<Item = StockListing, Error = Error> {
+    info!("Getting company for stock: {}", stock_symbol);
+    let symbol = stock_symbol.to_uppercase();
+
+    let uri = format!(
+        "https://api.iextrading.com/1.0/stock/{}/company",
+        symbol
+    )
+    .parse::<Uri>()
+    .map_err(|e| Error::bad_request(format!("{:?}", e)));
+
+    // handle json in the form: {"symbol":"AAPL","companyName":"Apple Inc.","exchange":"Nasdaq Global Select", ...}
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Company {
+        company_name: String,
+        exchange: String,
+    }
+
+    match uri {
+        Ok(uri) => {
+            let uri_string = uri.to_string(); // create this here, so it can be moved into the closure.
+            let symbol_copy = symbol.clone();
+            client
+                .get(uri.clone())
+                .and_then(|res| {
+                    let status = res.status();
+                    res.into_body().concat2().map(move |chunk| (status, chunk)) // Await the whole body
+                })
+                .map_err(move |_| {
+                    Error::dependent_connection_failed(
+                        uri.to_string(),
+                        format!("Could not get company for {}.", symbol_copy),
+                    )
+                })
+                .and_then(move |(status, chunk): (StatusCode, Chunk)| -> Result<StockListing, Error> {
+                    // IEX responds with a 404 for symbols it doesn't know of.
+                    if status == StatusCode::NOT_FOUND {
+                        return Error::bad_request(format!("{} is not a listed stock symbol.", symbol))
+                            .apply(Err);
+                    }
+                    serde_json::from_slice::<Company>(&chunk)
+                        .map(|company| StockListing {
+                            symbol,
+                            name: company.company_name,
+                            exchange: Some(company.exchange),
+                        })
+                        .map_err(|_| {
+                            Error::internal_server_error(format!(
+                                "Could not parse body of dependent connection: {}",
+                                uri_string
+                            ))
+                        })
+                })
+                .apply(future::Either::A)
+        }
+        Err(e) => e.apply(future::err).apply(future::Either::B),
+    }
+}
+
+/// Gets every symbol that IEX supports.
+fn get_listings(client: &HttpsClient) -> impl Future<Item = Vec<StockListing>, Error = Error> {
+    info!("Getting the symbols supported by IEX");
+    let uri = Uri::from_static("https://api.iextrading.com/1.0/ref-data/symbols");
+
+    // handle json in the form: [{"symbol":"A","name":"Agilent Technologies Inc.","isEnabled":true, ...}]
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Symbol {
+        symbol: String,
+        name: String,
+        is_enabled: bool,
+    }
+
+    client
+        .get(uri.clone())
+        .and_then(|res| {
+            res.into_body().concat2() // Await the whole body
+        })
+        .map_err(move |_| {
+            Error::dependent_connection_failed(uri.to_string(), "Could not get symbols.")
+        })
+        .and_then(|chunk: Chunk| {
+            serde_json::from_slice::<Vec<Symbol>>(&chunk)
+                .map(|symbols| {
+                    symbols
+                        .into_iter()
+                        .filter(|symbol| symbol.is_enabled)
+                        .map(|symbol| StockListing {
+                            symbol: symbol.symbol.to_uppercase(),
+                            name: symbol.name,
+                            exchange: None,
+                        })
+                        .collect()
+                })
+                .map_err(|_| Error::internal_server_error("Could not parse symbols"))
+        })
+}
+
+/// Reuses recently fetched quotes, and records every quote it fetches in the `stock_prices` table.
+///
+/// It wraps another provider, which is only asked for quotes that are missing from the cache,
+/// or are older than the time to live.
+#[derive(Debug)]
+pub struct CachingQuoteProvider {
+    /// The provider that quotes are fetched from on a cache miss.
+    inner: Arc<QuoteProvider>,
+    /// The recently fetched quotes.
+    cache: Arc<QuoteCache>,
+}
+
+impl CachingQuoteProvider {
+    /// Creates a new caching provider.
+    ///
+    /// # Arguments
+    /// * inner - The provider that quotes are fetched from.
+    /// * pool - The pool of database connections used to record fetched quotes.
+    /// * ttl - How long a fetched quote is reused for.
+    pub fn new(inner: Arc<QuoteProvider>, pool: Pool, ttl: Duration) -> Self {
+        let cache = QuoteCache {
+            pool,
+            ttl,
+            quotes: Mutex::new(HashMap::new()),
+            listings: Mutex::new(HashMap::new()),
+        };
+        CachingQuoteProvider {
+            inner,
+            cache: Arc::new(cache),
+        }
+    }
+}
+
+impl QuoteProvider for CachingQuoteProvider {
+    fn current_price(&self, stock_symbol: &str) -> PriceFuture {
+        if let Some(price) = self.cache.get(stock_symbol) {
+            return price.apply(future::ok).apply(Box::new);
+        }
+
+        let cache = self.cache.clone();
+        let symbol = stock_symbol.to_string();
+        self.inner
+            .current_price(stock_symbol)
+            .and_then(move |price| cache.record(vec![(symbol, price)]).map(move |_| price))
+            .apply(Box::new)
+    }
+
+    fn current_prices(&self, stock_symbols: &[&str]) -> PricesFuture {
+        let mut prices = HashMap::new();
+        let mut missing = vec![];
+        for symbol in stock_symbols {
+            match self.cache.get(symbol) {
+                Some(price) => {
+                    prices.insert(symbol.to_string(), price);
+                }
+                None => missing.push(*symbol),
+            }
+        }
+
+        if missing.is_empty() {
+            return prices.apply(future::ok).apply(Box::new);
+        }
+
+        let cache = self.cache.clone();
+        self.inner
+            .current_prices(&missing)
+            .and_then(move |fetched| {
+                let new_prices = fetched
+                    .iter()
+                    .map(|(symbol, price)| (symbol.clone(), *price))
+                    .collect();
+                cache.record(new_prices).map(move |_| {
+                    prices.extend(fetched);
+                    prices
+                })
+            })
+            .apply(Box::new)
+    }
+
+    fn listing(&self, stock_symbol: &str) -> ListingFuture {
+        if let Some(listing) = self.cache.get_listing(stock_symbol) {
+            return listing.apply(future::ok).apply(Box::new);
+        }
+
+        let cache = self.cache.clone();
+        self.inner
+            .listing(stock_symbol)
+            .map(move |listing| {
+                cache.record_listing(listing.clone());
+                listing
+            })
+            .apply(Box::new)
+    }
+
+    fn listings(&self) -> ListingsFuture {
+        self.inner.listings()
+    }
+}
+
+/// Quotes held by the `CachingQuoteProvider`, keyed by upper-case symbol.
+#[derive(Debug)]
+struct QuoteCache {
+    /// Used to record fetched quotes.
+    pool: Pool,
+    /// How long a fetched quote is reused for.
+    ttl: Duration,
+    /// The price of each symbol, and when it was fetched.
+    quotes: Mutex<HashMap<String, (f64, Instant)>>,
+    /// The company behind each symbol.
+    /// Companies rarely change, so these are kept for as long as the server runs.
+    listings: Mutex<HashMap<String, StockListing>>,
+}
+
+impl QuoteCache {
+    /// Gets the price for the symbol, if it was fetched within the time to live.
+    fn get(&self, stock_symbol: &str) -> Option<f64> {
+        let quotes = self.quotes.lock().expect("Quote cache lock was poisoned");
+        quotes
+            .get(&stock_symbol.to_uppercase())
+            .filter(|(_, fetched_at)| fetched_at.elapsed() < self.ttl)
+            .map(|(price, _)| *price)
+    }
+
+    /// Gets the company behind the symbol, if it was fetched before.
+    fn get_listing(&self, stock_symbol: &str) -> Option<StockListing> {
+        let listings = self.listings.lock().expect("Quote cache lock was poisoned");
+        listings.get(&stock_symbol.to_uppercase()).cloned()
+    }
+
+    /// Stores a freshly fetched company.
+    fn record_listing(&self, listing: StockListing) {
+        let mut listings = self.listings.lock().expect("Quote cache lock was poisoned");
+        listings.insert(listing.symbol.to_uppercase(), listing);
+    }
+
+    /// Stores freshly fetched prices, and records them in the database.
+    ///
+    /// The insert runs in a blocking section, so it doesn't hold up the other futures on the reactor.
+    /// Failing to record the prices is logged, but doesn't fail the request that fetched them.
+    fn record(&self, prices: Vec<(String, f64)>) -> impl Future<Item = (), Error = Error> + Send {
+        let now = Instant::now();
+        let record_time = Utc::now().naive_utc();
+        let prices: HashMap<String, f64> = prices
+            .into_iter()
+            .map(|(symbol, price)| (symbol.to_uppercase(), price))
+            .collect();
+
+        {
+            let mut quotes = self.quotes.lock().expect("Quote cache lock was poisoned");
+            for (symbol, price) in &prices {
+                quotes.insert(symbol.clone(), (*price, now));
+            }
+        }
+
+        let new_prices = prices
+            .into_iter()
+            .map(|(symbol, price)| NewStockPrice {
+                symbol,
+                price,
+                record_time,
+            })
+            .collect();
+        let pool = self.pool.clone();
+        let mut new_prices = Some(new_prices);
+        future::poll_fn(move || {
+            tokio_threadpool::blocking(|| insert_prices(&pool, &mut new_prices)).or_else(
+                |_| -> Result<Async<()>, Error> {
+                    // Not running on a thread pool, so there is nowhere else to run the insert.
+                    insert_prices(&pool, &mut new_prices);
+                    Ok(Async::Ready(()))
+                },
+            )
+        })
+    }
+}
+
+/// Inserts the prices into the `stock_prices` table, logging any failure.
+///
+/// # Arguments
+/// * pool - The pool of database connections.
+/// * new_prices - The prices to insert. They are taken, so they are only ever inserted once.
+fn insert_prices(pool: &Pool, new_prices: &mut Option<Vec<NewStockPrice>>) {
+    let new_prices = match new_prices.take() {
+        Some(new_prices) => new_prices,
+        None => return,
+    };
+    let recorded = pool
+        .get()
+        .map_err(|_| Error::DatabaseUnavailable)
+        .and_then(|conn| StockPrice::create_prices(new_prices, &conn).map_err(Error::from));
+    if let Err(e) = recorded {
+        error!("Could not record stock prices: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fixture_price_ignores_case() {
+        let provider = FixtureQuoteProvider::testing_quotes();
+        let price = provider.current_price("aapl").wait().expect("Should have a price");
+        assert_eq!(price, 170.67);
+    }
+
+    #[test]
+    fn fixture_unknown_symbol_is_an_error() {
+        let provider = FixtureQuoteProvider::testing_quotes();
+        assert!(provider.current_price("NOTREAL").wait().is_err());
+    }
+
+    #[test]
+    fn fixture_prices_are_keyed_by_requested_symbol() {
+        let provider = FixtureQuoteProvider::testing_quotes();
+        let prices = provider
+            .current_prices(&["AAPL", "NOTREAL", "fb"])
+            .wait()
+            .expect("Should have prices");
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices.get("AAPL"), Some(&170.67));
+        assert_eq!(prices.get("fb"), Some(&165.465));
+        assert_eq!(prices.get("NOTREAL"), None);
+    }
+}
+
+#[cfg(test)]
+mod integration {
+    use super::*;
+    use futures::future;
+    use hyper::Client;
+    use hyper_tls::HttpsConnector;
+    use tokio;
+
+    #[test]
+    fn can_get_current_price() {
+        // This test assumes that apple's stock price is above 1 dollar per share.
+        // A fair assumption, but it may not always be true :/.
+        tokio::run(future::lazy(|| {
+            let https = HttpsConnector::new(4).unwrap();
+            let client = Client::builder().build::<_, hyper::Body>(https);
+            get_current_price("aapl", &client)
+                .map(|price| assert!(price > 0.0, "Aapl should have a positive share price."))
+                .map_err(|_| panic!("Could not get current price"))
+        }));
+    }
+
+    #[test]
+    fn can_get_multiple_current_price() {
+        // This test assumes that apple's stock price is above 1 dollar per share.
+        // A fair assumption, but it may not always be true :/.
+        tokio::run(future::lazy(|| {
+            let https = HttpsConnector::new(4).unwrap();
+            let client = Client::builder().build::<_, hyper::Body>(https);
+            get_current_prices(&["aapl", "fb"], &client)
+                .map(|prices| {
+                    assert_eq!(prices.len(), 2);
+                    assert!(prices.contains_key("aapl"));
+                    assert!(prices.contains_key("fb"));
+                })
+                .map_err(|_| panic!("Could not get current prices"))
+        }));
+    }
+}
@@ -0,0 +1,264 @@
+//! Writes a user's trades in formats that spreadsheets and personal finance tools can read,
+//! so paper trades can be reconciled outside of the app.
+use chrono::NaiveDateTime;
+use db::stock::TransactionWithStock;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Identifies the app as the "broker" holding the account in OFX statements.
+const OFX_BROKER_ID: &str = "paper-trading";
+
+/// A file format that trades can be exported in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// Comma separated values, with a header row.
+    Csv,
+    /// An OFX 2.2 investment statement.
+    Ofx,
+}
+
+impl ExportFormat {
+    /// The media type of the exported file.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ofx => "application/x-ofx",
+        }
+    }
+
+    /// The name the exported file should be saved as.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "transactions.csv",
+            ExportFormat::Ofx => "transactions.ofx",
+        }
+    }
+
+    /// Writes the transactions in this format.
+    ///
+    /// # Arguments
+    /// * transactions - The user's transactions, oldest first.
+    /// * user_uuid - The user the transactions belong to.
+    /// * generated_at - The time the export was made.
+    pub fn write(
+        self,
+        transactions: &[TransactionWithStock],
+        user_uuid: Uuid,
+        generated_at: NaiveDateTime,
+    ) -> String {
+        match self {
+            ExportFormat::Csv => write_csv(transactions),
+            ExportFormat::Ofx => write_ofx(transactions, user_uuid, generated_at),
+        }
+    }
+}
+
+/// The change in cash caused by a transaction. Purchases are negative, sales are positive.
+fn cash_total(transaction: &TransactionWithStock) -> f64 {
+    -f64::from(transaction.transaction.quantity)
+        * transaction.transaction.price_of_stock_at_time_of_trading
+}
+
+/// Writes one row per transaction, with the quantity always positive and the side spelled out.
+fn write_csv(transactions: &[TransactionWithStock]) -> String {
+    let mut csv =
+        "Date,Symbol,Company,Exchange,Side,Quantity,Price,Total,Transaction ID\r\n".to_string();
+    for t in transactions {
+        let side = if t.transaction.quantity < 0 { "Sell" } else { "Buy" };
+        let row = [
+            t.transaction.record_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            csv_field(&t.stock.symbol),
+            csv_field(&t.stock.stock_name),
+            csv_field(t.stock.exchange.as_ref().map(String::as_str).unwrap_or("")),
+            side.to_string(),
+            t.transaction.quantity.abs().to_string(),
+            t.transaction.price_of_stock_at_time_of_trading.to_string(),
+            format!("{:.2}", cash_total(t)),
+            t.transaction.uuid.to_string(),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Quotes a field if it contains a delimiter, a quote, or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\r' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Formats a time the way OFX expects, in UTC.
+fn ofx_date(time: NaiveDateTime) -> String {
+    time.format("%Y%m%d%H%M%S%.3f[0:GMT]").to_string()
+}
+
+/// Escapes the characters that have meaning in XML.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Identifies a security by its ticker, as paper trades have no CUSIP.
+fn ofx_sec_id(symbol: &str) -> String {
+    format!(
+        "<SECID><UNIQUEID>{}</UNIQUEID><UNIQUEIDTYPE>TICKER</UNIQUEIDTYPE></SECID>",
+        xml_escape(symbol)
+    )
+}
+
+/// Writes an investment statement with a BUYSTOCK or SELLSTOCK entry for each transaction,
+/// and a list describing every stock that was traded.
+fn write_ofx(
+    transactions: &[TransactionWithStock],
+    user_uuid: Uuid,
+    generated_at: NaiveDateTime,
+) -> String {
+    let now = ofx_date(generated_at);
+    let start = transactions
+        .first()
+        .map(|t| ofx_date(t.transaction.record_time))
+        .unwrap_or_else(|| now.clone());
+    let status = "<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>";
+
+    let mut ofx = String::new();
+    ofx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+    ofx.push_str("<?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n");
+    ofx.push_str("<OFX>\n");
+    ofx.push_str(&format!(
+        "<SIGNONMSGSRSV1><SONRS>{}<DTSERVER>{}</DTSERVER><LANGUAGE>ENG</LANGUAGE></SONRS></SIGNONMSGSRSV1>\n",
+        status, now
+    ));
+    ofx.push_str("<INVSTMTMSGSRSV1><INVSTMTTRNRS>\n");
+    ofx.push_str(&format!("<TRNUID>{}</TRNUID>{}\n", Uuid::new_v4(), status));
+    ofx.push_str(&format!(
+        "<INVSTMTRS><DTASOF>{}</DTASOF><CURDEF>USD</CURDEF>\n",
+        now
+    ));
+    ofx.push_str(&format!(
+        "<INVACCTFROM><BROKERID>{}</BROKERID><ACCTID>{}</ACCTID></INVACCTFROM>\n",
+        OFX_BROKER_ID, user_uuid
+    ));
+    ofx.push_str(&format!(
+        "<INVTRANLIST><DTSTART>{}</DTSTART><DTEND>{}</DTEND>\n",
+        start, now
+    ));
+    for t in transactions {
+        let (aggregate, kind) = if t.transaction.quantity < 0 {
+            ("SELL", "INVSELL")
+        } else {
+            ("BUY", "INVBUY")
+        };
+        ofx.push_str(&format!(
+            "<{agg}STOCK><{kind}><INVTRAN><FITID>{fitid}</FITID><DTTRADE>{date}</DTTRADE></INVTRAN>{secid}\
+             <UNITS>{units}</UNITS><UNITPRICE>{price}</UNITPRICE><TOTAL>{total:.2}</TOTAL>\
+             <SUBACCTSEC>CASH</SUBACCTSEC><SUBACCTFUND>CASH</SUBACCTFUND></{kind}>\
+             <{agg}TYPE>{agg}</{agg}TYPE></{agg}STOCK>\n",
+            agg = aggregate,
+            kind = kind,
+            fitid = t.transaction.uuid,
+            date = ofx_date(t.transaction.record_time),
+            secid = ofx_sec_id(&t.stock.symbol),
+            units = t.transaction.quantity,
+            price = t.transaction.price_of_stock_at_time_of_trading,
+            total = cash_total(t),
+        ));
+    }
+    ofx.push_str("</INVTRANLIST></INVSTMTRS>\n");
+    ofx.push_str("</INVSTMTTRNRS></INVSTMTMSGSRSV1>\n");
+
+    let mut stocks: Vec<_> = transactions.iter().map(|t| &t.stock).collect();
+    stocks.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    stocks.dedup_by(|a, b| a.symbol == b.symbol);
+    ofx.push_str("<SECLISTMSGSRSV1><SECLIST>\n");
+    for stock in stocks {
+        ofx.push_str(&format!(
+            "<STOCKINFO><SECINFO>{}<SECNAME>{}</SECNAME><TICKER>{}</TICKER></SECINFO></STOCKINFO>\n",
+            ofx_sec_id(&stock.symbol),
+            xml_escape(&stock.stock_name),
+            xml_escape(&stock.symbol)
+        ));
+    }
+    ofx.push_str("</SECLIST></SECLISTMSGSRSV1>\n");
+    ofx.push_str("</OFX>\n");
+    ofx
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+    use db::stock::{Stock, StockTransaction};
+
+    fn transaction(symbol: &str, name: &str, quantity: i32, price: f64) -> TransactionWithStock {
+        let stock = Stock {
+            uuid: Uuid::new_v4(),
+            symbol: symbol.to_string(),
+            stock_name: name.to_string(),
+            exchange: Some("Nasdaq Global Select".to_string()),
+        };
+        TransactionWithStock {
+            transaction: StockTransaction {
+                uuid: Uuid::new_v4(),
+                user_uuid: Uuid::new_v4(),
+                stock_uuid: stock.uuid,
+                quantity,
+                price_of_stock_at_time_of_trading: price,
+                record_time: NaiveDate::from_ymd(2019, 4, 30).and_hms(14, 30, 0),
+            },
+            stock,
+        }
+    }
+
+    #[test]
+    fn csv_has_a_row_per_transaction() {
+        let transactions = vec![
+            transaction("AAPL", "Apple Inc.", 2, 170.5),
+            transaction("AAPL", "Apple Inc.", -1, 180.0),
+        ];
+
+        let csv = write_csv(&transactions);
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+
+        assert_eq!(rows.len(), 4, "header, two rows, and a trailing line break");
+        assert!(rows[1].starts_with(
+            "2019-04-30 14:30:00,AAPL,Apple Inc.,Nasdaq Global Select,Buy,2,170.5,-341.00,"
+        ));
+        assert!(rows[2].contains(",Sell,1,180,180.00,"));
+        assert_eq!(rows[3], "");
+    }
+
+    #[test]
+    fn csv_quotes_awkward_fields() {
+        assert_eq!(csv_field("Apple Inc."), "Apple Inc.");
+        assert_eq!(csv_field("Berkshire, Inc."), "\"Berkshire, Inc.\"");
+        assert_eq!(csv_field("The \"Best\" Co"), "\"The \"\"Best\"\" Co\"");
+    }
+
+    #[test]
+    fn ofx_lists_trades_and_securities() {
+        let transactions = vec![
+            transaction("AT&T", "AT&T Inc.", 3, 30.0),
+            transaction("AT&T", "AT&T Inc.", -3, 31.0),
+        ];
+        let user_uuid = Uuid::new_v4();
+        let now = NaiveDate::from_ymd(2019, 5, 1).and_hms(0, 0, 0);
+
+        let ofx = write_ofx(&transactions, user_uuid, now);
+
+        assert!(ofx.contains(&format!("<ACCTID>{}</ACCTID>", user_uuid)));
+        assert!(ofx.contains("<DTSTART>20190430143000.000[0:GMT]</DTSTART>"));
+        assert!(ofx.contains("<UNITS>3</UNITS><UNITPRICE>30</UNITPRICE><TOTAL>-90.00</TOTAL>"));
+        assert!(ofx.contains("<SELLTYPE>SELL</SELLTYPE>"));
+        assert!(ofx.contains("<SECNAME>AT&amp;T Inc.</SECNAME>"));
+        assert!(!ofx.contains("AT&T"), "ampersands should be escaped");
+        assert_eq!(ofx.matches("<STOCKINFO>").count(), 1, "each stock is listed once");
+    }
+}
@@ -0,0 +1,145 @@
+//! Business logic for exchanging stocks, shared by the market api and the order matcher.
+use crate::error::Error;
+use apply::Apply;
+use chrono::Utc;
+use db::{
+    cash::{CashTransaction, NewCashTransaction},
+    stock::{NewStock, NewStockTransaction, Stock, StockTransaction},
+    stock_listing::StockListing,
+    user::User,
+};
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+
+/// The name given to stocks before their companies were looked up.
+const PLACEHOLDER_STOCK_NAME: &str = "VOID - This field is slated for removal";
+
+/// The longest ticker symbol that is accepted.
+const MAX_SYMBOL_LENGTH: usize = 10;
+
+/// Trims and upper-cases a ticker symbol, rejecting anything that couldn't be one.
+///
+/// # Arguments
+/// * symbol - The symbol as provided by the user.
+pub fn normalize_symbol(symbol: &str) -> Result<String, Error> {
+    let symbol = symbol.trim().to_uppercase();
+    let is_valid = !symbol.is_empty()
+        && symbol.len() <= MAX_SYMBOL_LENGTH
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    if is_valid {
+        Ok(symbol)
+    } else {
+        Error::bad_request(format!("'{}' is not a valid stock symbol.", symbol)).apply(Err)
+    }
+}
+
+/// Gets the stock for a listing, creating it if needed.
+///
+/// # Arguments
+/// * listing - The company behind the stock, as found by the quote provider.
+/// * conn - The connection to the database.
+pub fn get_or_create_stock(listing: &StockListing, conn: &PgConnection) -> Result<Stock, Error> {
+    let new_stock = NewStock {
+        symbol: listing.symbol.clone(),
+        stock_name: listing.name.clone(),
+        exchange: listing.exchange.clone(),
+    };
+    let stock = Stock::get_or_create_stock(new_stock, conn)?;
+
+    // Fill in the company of stocks that were created before companies were looked up.
+    if stock.stock_name == PLACEHOLDER_STOCK_NAME {
+        Stock::update_listing(stock.uuid, listing.name.clone(), listing.exchange.clone(), conn)
+            .map_err(Error::from)
+    } else {
+        Ok(stock)
+    }
+}
+
+/// Lock the user, so that concurrent trades for them wait on this one.
+/// Get the current transactions for the user for this stock.
+/// Check if the transactions would cause them to own negative number of stocks.
+/// Check if the user has the funds to make the transaction.
+/// Subtract the funds from the user.
+/// Record the transaction.
+///
+/// # Note
+/// This should be run within a serializable database transaction,
+/// so the checks can't be invalidated by another trade before the transaction is recorded.
+///
+/// # Arguments
+/// * user_uuid - The unique id of the user whose funds are being modified.
+/// * stock - The stock being exchanged.
+/// * quantity - The number of stocks to exchange. Negative indicates a sale.
+/// * current_price - The current price of the stock.
+/// * conn - The connection to the database.
+pub fn execute_trade(
+    user_uuid: Uuid,
+    stock: &Stock,
+    quantity: i32,
+    current_price: f64,
+    conn: &PgConnection,
+) -> Result<StockTransaction, Error> {
+    User::get_user_for_update(user_uuid, conn)?;
+
+    let transactions = Stock::get_user_transactions_for_stock(user_uuid, stock.uuid, conn)?;
+    let owned = transactions.into_iter().fold(0, |acc, t| acc + t.quantity);
+
+    // Users can't sell more than they have.
+    if -quantity > owned {
+        let err = format!(
+            "Can't sell more stocks than you have. Owned: {}, Transaction: {}",
+            owned, quantity
+        );
+        Error::bad_request(err).apply(Err)?;
+    }
+
+    // Users can't spend more than they have.
+    let cost = f64::from(quantity) * current_price;
+    let cash = CashTransaction::get_balance(user_uuid, conn)?;
+    if cost > cash {
+        let err = format!(
+            "Can't buy more than you can afford. Cash: {:.2}, Cost: {:.2}",
+            cash, cost
+        );
+        Error::bad_request(err).apply(Err)?;
+    }
+
+    let new_stock_transaction = NewStockTransaction {
+        user_uuid,
+        stock_uuid: stock.uuid,
+        quantity,
+        price_of_stock_at_time_of_trading: current_price,
+        record_time: Utc::now().naive_utc(),
+    };
+
+    // Record that the stock was purchased for the user
+    let stock_transaction = Stock::create_transaction(new_stock_transaction, conn)?;
+
+    // Move the cash for the trade in or out of the user's account
+    let cash_transaction = NewCashTransaction::for_stock_transaction(&stock_transaction);
+    CashTransaction::create_cash_transaction(cash_transaction, conn)?;
+
+    Ok(stock_transaction)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn symbols_are_trimmed_and_upper_cased() {
+        assert_eq!(normalize_symbol(" aapl ").unwrap(), "AAPL");
+        assert_eq!(normalize_symbol("brk.b").unwrap(), "BRK.B");
+    }
+
+    #[test]
+    fn invalid_symbols_are_rejected() {
+        assert!(normalize_symbol("").is_err());
+        assert!(normalize_symbol("   ").is_err());
+        assert!(normalize_symbol("AA PL").is_err());
+        assert!(normalize_symbol("DROP;TABLE").is_err());
+        assert!(normalize_symbol("WAYTOOLONGSYMBOL").is_err());
+    }
+}
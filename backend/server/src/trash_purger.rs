@@ -0,0 +1,51 @@
+//! Permanently deletes events that have been in the trash for longer than they are kept.
+use crate::error::Error;
+use chrono::Utc;
+use db::event::Event;
+use futures::{future::Future, stream::Stream};
+use log::{error, info};
+use pool::Pool;
+use std::time::{Duration, Instant};
+use tokio::timer::Interval;
+
+/// Purges the trash, starting immediately, then once every interval.
+///
+/// The returned future never resolves, and should be spawned on the server's runtime.
+///
+/// # Arguments
+/// * pool - The pool of database connections.
+/// * retention - How long events are kept in the trash.
+/// * interval - How often the trash is purged.
+pub fn trash_purger(
+    pool: Pool,
+    retention: Duration,
+    interval: Duration,
+) -> impl Future<Item = (), Error = ()> + Send {
+    info!(
+        "Purging events that have been in the trash for {:?}, every {:?}",
+        retention, interval
+    );
+    Interval::new(Instant::now(), interval)
+        .map_err(|e| error!("Trash purger timer failed: {}", e))
+        .for_each(move |_| {
+            // A failed purge leaves the events in the trash, it will try again next tick.
+            match purge_trash(&pool, retention) {
+                Ok(0) => {}
+                Ok(count) => info!("Purged {} events from the trash", count),
+                Err(e) => error!("Could not purge the trash: {}", e),
+            }
+            Ok(())
+        })
+}
+
+/// Permanently deletes the events that were moved to the trash longer than the retention ago.
+///
+/// # Return
+/// The number of events that were deleted.
+pub fn purge_trash(pool: &Pool, retention: Duration) -> Result<usize, Error> {
+    let retention = chrono::Duration::from_std(retention)
+        .map_err(|e| Error::internal_server_error(e.to_string()))?;
+    let conn = pool.get().map_err(|_| Error::DatabaseUnavailable)?;
+    let before = Utc::now().naive_utc() - retention;
+    Event::purge_trash(before, &conn).map_err(Error::from)
+}
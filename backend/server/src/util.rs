@@ -1,7 +1,8 @@
 //! Common utilities
 use crate::error::Error;
+use bytes::Buf;
 use serde::{Deserialize, Serialize};
-use warp::{Filter, Rejection, Reply};
+use warp::{body::FullBody, Filter, Rejection, Reply};
 
 const KILOBYTE: u64 = 1024;
 /// Extracts the body of a request after stipulating that it has a reasonable size in kilobytes.
@@ -16,6 +17,19 @@ where
     warp::body::content_length_limit(KILOBYTE * kb_limit).and(warp::body::json())
 }
 
+/// Extracts the body of a request as text, after stipulating that it has a reasonable size in kilobytes.
+///
+/// # Arguments
+/// * kb_limit - The maximum number of kilobytes, over which the request will be rejected.
+pub fn text_body_filter(kb_limit: u64) -> impl Filter<Extract = (String,), Error = Rejection> + Copy {
+    warp::body::content_length_limit(KILOBYTE * kb_limit)
+        .and(warp::body::concat())
+        .and_then(|body: FullBody| {
+            String::from_utf8(body.bytes().to_vec())
+                .map_err(|_| Error::bad_request("The body must be UTF-8 text.").reject())
+        })
+}
+
 #[allow(dead_code)]
 /// Util function that makes replying easier.
 pub fn json_convert<T, U>(source: T) -> impl Reply
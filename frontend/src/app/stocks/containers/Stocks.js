@@ -117,7 +117,11 @@ export default class Stocks extends React.Component {
   getOwnedStocks() {
     const url = '/api/market/stock/performance';
     authenticatedFetchDe(url)
-      .then(backendResponse => {
+      .then(performanceResponse => {
+        if (performanceResponse.unpriced_symbols.length > 0) {
+          console.log("No current price for: " + performanceResponse.unpriced_symbols.join(", "));
+        }
+        const backendResponse = performanceResponse.stocks;
         let symbols = backendResponse.map(stock => {
           return stock.stock.stock.symbol;
         });